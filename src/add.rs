@@ -0,0 +1,262 @@
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::Result;
+use crate::paths::{self, expand_tilde, same_path};
+use crate::remote;
+use crate::store::{Bookmark, BookmarkStore, Kind, unique_name};
+
+/// What `add_paths` did with each candidate.
+#[derive(Default)]
+pub struct AddSummary {
+    pub added: Vec<Bookmark>,
+    /// Paths that were bookmarked already, or given twice.
+    pub duplicates: Vec<String>,
+    /// Paths that were skipped, with the reason.
+    pub invalid: Vec<(String, String)>,
+}
+
+/// Name for a bookmark on `path`: its last component. Roots have none, so
+/// `C:\` is named `C` and `\\server\share` is named `share`; `/` keeps its path.
+pub fn name_from_path(path: &str) -> String {
+    if let Some(name) = Path::new(path).file_name() {
+        return name.to_string_lossy().into_owned();
+    }
+    let trimmed = path.trim_end_matches(['/', '\\']);
+    match trimmed.rsplit(['/', '\\']).next().map(|last| last.trim_end_matches(':')) {
+        Some(last) if !last.is_empty() => last.to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Name for a new bookmark on `path` that no bookmark in `bookmarks` uses:
+/// its last component, with parent components prepended while that is
+/// taken (`api-src`), and a `-2`, `-3`, ... suffix if every one of those is.
+pub fn default_name(bookmarks: &[Bookmark], path: &str) -> String {
+    let components: Vec<String> = Path::new(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let base = name_from_path(path);
+    for start in (0..components.len()).rev() {
+        let name = components[start..].join("-");
+        if !bookmarks.iter().any(|b| b.name == name) {
+            return name;
+        }
+    }
+    unique_name(bookmarks, &base)
+}
+
+/// Turns `input` into an absolute path to an existing directory or file,
+/// resolving relative paths against the working directory, and tells which
+/// of the two it is. With `canonicalize` set, symlinks and `..` are
+/// resolved as well.
+pub fn resolve_path(input: &str, canonicalize: bool) -> Result<(String, Kind), String> {
+    let mut path = PathBuf::from(input);
+    if path.is_relative() {
+        let cwd = env::current_dir().map_err(|e| format!("no working directory: {e}"))?;
+        path = cwd.join(path);
+    }
+    // Drops `.` components, so `bm add .` stores the plain directory path.
+    let path: PathBuf = path.components().collect();
+    let kind = match fs::metadata(&path) {
+        Ok(meta) if meta.is_dir() => Kind::Dir,
+        Ok(_) => Kind::File,
+        Err(e) => return Err(e.to_string()),
+    };
+    let path = if canonicalize { paths::canonicalize(&path).map_err(|e| e.to_string())? } else { path };
+    let path = path.into_os_string().into_string().map_err(|_| "path is not valid UTF-8".to_string())?;
+    Ok((path, kind))
+}
+
+/// `path` with symlinks resolved, or `None` when it can't be resolved.
+pub fn canonical(path: &str) -> Option<String> {
+    paths::canonicalize(Path::new(path)).ok()?.into_os_string().into_string().ok()
+}
+
+/// Whether `a` and `b` name the same directory, ignoring trailing slashes
+/// and following symlinks when both exist.
+pub fn same_dir(a: &str, b: &str) -> bool {
+    same_path(a, b) || canonical(a).is_some_and(|a| canonical(b) == Some(a))
+}
+
+/// The root of the git repository `dir` is in: the nearest directory at or
+/// above it with a `.git` entry, which is a file in worktrees and submodules.
+pub fn git_root(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|d| d.join(".git").exists())
+}
+
+/// Paths listed one per line; blank lines and `#` comments are ignored.
+pub fn parse_path_list(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// `value` without the single or double quotes around it.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
+
+/// The named directories in `content`, one `name=path` per line, as in the
+/// `~/.sdirs` of bashmarks and shellmarks (`export DIR_name="$HOME/src"`)
+/// and in alias files (`alias name='cd /path'`). Blank lines, `#` comments
+/// and other lines are ignored.
+pub fn parse_named_list(content: &str) -> Vec<(String, String)> {
+    parse_path_list(content)
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").or(line.strip_prefix("alias ")).unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let name = name.trim();
+            let name = name.strip_prefix("DIR_").unwrap_or(name);
+            let value = unquote(value);
+            let path = value.strip_prefix("cd ").map(unquote).unwrap_or(value);
+            // bashmarks writes the home directory as `$HOME`.
+            let path = match path.strip_prefix("$HOME").or(path.strip_prefix("${HOME}")) {
+                Some(rest) => format!("~{rest}"),
+                None => path.to_string(),
+            };
+            (!name.is_empty() && !path.is_empty()).then(|| (name.to_string(), path))
+        })
+        .collect()
+}
+
+/// The first of the `exclude` glob patterns that `path` matches, if any.
+/// Patterns may start with `~`. `*` stays within a component while `**`
+/// spans any number of them, and `dir/**` matches `dir` itself too.
+pub fn excluded<'a>(exclude: &'a [String], path: &str) -> Option<&'a str> {
+    let options = glob::MatchOptions { require_literal_separator: true, ..glob::MatchOptions::new() };
+    let matches = |pattern: &str| glob::Pattern::new(pattern).is_ok_and(|p| p.matches_with(path, options));
+    exclude
+        .iter()
+        .find(|pattern| {
+            let pattern = expand_tilde(pattern);
+            matches(&pattern) || pattern.strip_suffix("/**").is_some_and(matches)
+        })
+        .map(String::as_str)
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// The paths `input` names: itself, or every directory a glob pattern
+/// matches. Files matched by a pattern are left out, and remote paths such
+/// as `host:/dir` are taken as they are.
+fn expand_input(input: &str) -> Result<Vec<String>, String> {
+    if remote::split(input).is_some() {
+        return Ok(vec![input.to_string()]);
+    }
+    let input = expand_tilde(input);
+    if !is_glob(&input) {
+        return Ok(vec![input]);
+    }
+    let dirs: Vec<String> = glob::glob(&input)
+        .map_err(|e| format!("invalid pattern: {e}"))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_dir())
+        .filter_map(|path| path.into_os_string().into_string().ok())
+        .collect();
+    if dirs.is_empty() {
+        return Err("no matching directories".to_string());
+    }
+    Ok(dirs)
+}
+
+/// How `add_paths` names and stores new bookmarks.
+#[derive(Default)]
+pub struct AddOptions {
+    /// Given to every added bookmark.
+    pub tags: Vec<String>,
+    /// See [`resolve_path`].
+    pub canonicalize: bool,
+    /// Append `-2`, `-3`, ... to names that are taken instead of skipping
+    /// the directory.
+    pub suffix_names: bool,
+    /// Glob patterns of paths never to bookmark, see [`excluded`].
+    pub exclude: Vec<String>,
+}
+
+/// Bookmarks every directory, file or remote `host:/dir` in `inputs` that
+/// is not bookmarked yet, naming each after its last path component, and
+/// saves once at the end. Inputs may be glob patterns such as
+/// `~/projects/*`, which only match directories.
+pub fn add_paths<'a>(
+    store: &dyn BookmarkStore,
+    inputs: impl IntoIterator<Item = &'a str>,
+    options: &AddOptions,
+) -> Result<AddSummary> {
+    add_named_paths(store, inputs.into_iter().map(|input| (None, input)), options)
+}
+
+/// Like [`add_paths`], but an input that comes with a name is bookmarked
+/// under that name rather than its last path component.
+pub fn add_named_paths<'a>(
+    store: &dyn BookmarkStore,
+    inputs: impl IntoIterator<Item = (Option<&'a str>, &'a str)>,
+    options: &AddOptions,
+) -> Result<AddSummary> {
+    let base = store.load()?;
+    let mut bookmarks = base.clone();
+    let mut summary = AddSummary::default();
+    for (given_name, input) in inputs {
+        let candidates = match expand_input(input) {
+            Ok(candidates) => candidates,
+            Err(reason) => {
+                summary.invalid.push((input.to_string(), reason));
+                continue;
+            }
+        };
+        for candidate in candidates {
+            let resolved = match remote::split(&candidate) {
+                Some(_) => Ok((candidate.clone(), Kind::Remote)),
+                None => resolve_path(&candidate, options.canonicalize),
+            };
+            let (path, kind) = match resolved {
+                Ok(resolved) => resolved,
+                Err(reason) => {
+                    summary.invalid.push((candidate, reason));
+                    continue;
+                }
+            };
+            if let Some(pattern) = excluded(&options.exclude, &path) {
+                let reason = format!("excluded by `{pattern}`");
+                summary.invalid.push((path, reason));
+                continue;
+            }
+            if bookmarks.iter().any(|b| same_path(&b.path, &path)) {
+                summary.duplicates.push(path);
+                continue;
+            }
+            let mut name = given_name.map_or_else(|| name_from_path(&path), str::to_string);
+            if let Some(other) = bookmarks.iter().find(|b| b.name == name) {
+                if !options.suffix_names {
+                    let reason = format!("the name `{name}` is already used by {}", other.path);
+                    summary.invalid.push((path, reason));
+                    continue;
+                }
+                name = unique_name(&bookmarks, &name);
+            }
+            let mut bookmark = Bookmark::new(name, path);
+            bookmark.kind = kind;
+            bookmark.tags = options.tags.clone();
+            summary.added.push(bookmark.clone());
+            bookmarks.push(bookmark);
+        }
+    }
+    if !summary.added.is_empty() {
+        store.save_merged(&base, &bookmarks)?;
+    }
+    Ok(summary)
+}
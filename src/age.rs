@@ -0,0 +1,83 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::error::{BmError, Result};
+
+/// Runs `program` with `input` on its stdin and returns what it printed.
+/// The input is written from a thread of its own, as a large file fills
+/// the pipe before the program starts writing its output.
+fn run(path: &Path, program: &str, args: &[&str], input: Vec<u8>) -> Result<Vec<u8>> {
+    let err = |message: String| BmError::Encryption { path: path.to_path_buf(), message };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| err(format!("could not run {program}: {e}")))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().map_err(|e| err(format!("{program}: {e}")))?;
+    let written = writer.join().expect("writer thread panicked");
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(err(format!("{program} failed ({}): {message}", output.status)));
+    }
+    written.map_err(|e| err(format!("{program}: {e}")))?;
+    Ok(output.stdout)
+}
+
+/// What a keyring lookup command prints for bm's entry, if anything.
+fn keyring() -> Option<String> {
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+        ("security", &["find-generic-password", "-s", "bm", "-a", "age", "-w"])
+    } else if cfg!(unix) {
+        ("secret-tool", &["lookup", "service", "bm", "account", "age"])
+    } else {
+        return None;
+    };
+    let output = Command::new(program).args(args).stderr(Stdio::null()).output().ok()?;
+    let key = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !key.trim().is_empty()).then_some(key)
+}
+
+/// The age identity (`AGE-SECRET-KEY-1...`) the file at `path` is encrypted
+/// with: `BM_AGE_KEY`, the contents of the file `BM_AGE_IDENTITY` names, or
+/// bm's entry in the system keyring.
+fn identity(path: &Path) -> Result<String> {
+    if let Some(key) = std::env::var("BM_AGE_KEY").ok().filter(|k| !k.trim().is_empty()) {
+        return Ok(key);
+    }
+    if let Some(file) = std::env::var_os("BM_AGE_IDENTITY").filter(|f| !f.is_empty()) {
+        let file = Path::new(&file);
+        return std::fs::read_to_string(file).map_err(|e| BmError::io(file, e));
+    }
+    keyring().ok_or_else(|| BmError::Encryption {
+        path: path.to_path_buf(),
+        message: "no key; set BM_AGE_KEY or BM_AGE_IDENTITY, or store one in the keyring as service `bm`, account `age`"
+            .to_string(),
+    })
+}
+
+/// The contents of the age-encrypted file at `path`. age reads the file
+/// itself, as its stdin carries the key, so it never touches the disk.
+pub fn decrypt(path: &Path) -> Result<String> {
+    let key = identity(path)?;
+    let arg = path.to_string_lossy();
+    let data = run(path, "age", &["--decrypt", "--identity", "-", "--", &arg], key.into_bytes())?;
+    String::from_utf8(data).map_err(|e| BmError::Encryption { path: path.to_path_buf(), message: e.to_string() })
+}
+
+/// `content` encrypted for the file at `path`, to the recipients of its key.
+pub fn encrypt(path: &Path, content: &str) -> Result<Vec<u8>> {
+    let key = identity(path)?;
+    let recipients = run(path, "age-keygen", &["-y"], key.into_bytes())?;
+    let recipients = String::from_utf8_lossy(&recipients);
+    let mut args = vec!["--encrypt"];
+    for recipient in recipients.lines().filter(|r| !r.trim().is_empty()) {
+        args.extend(["--recipient", recipient.trim()]);
+    }
+    run(path, "age", &args, content.as_bytes().to_vec())
+}
@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::store::{Bookmark, BookmarkStore, is_encrypted};
+
+const DAY: u64 = 86_400;
+
+/// Where the bookmarks archived from the file at `path` are kept:
+/// `bookmarks.toml` archives into `bookmarks.archive.toml`, and an encrypted
+/// `bookmarks.toml.age` into `bookmarks.archive.toml.age`.
+pub fn archive_path(path: &Path) -> PathBuf {
+    if is_encrypted(path) {
+        let mut archive = archive_path(&path.with_extension("")).into_os_string();
+        archive.push(".age");
+        return archive.into();
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.archive.{}", ext.to_string_lossy()),
+        None => format!("{stem}.archive"),
+    };
+    path.with_file_name(name)
+}
+
+/// The archive of `store`, kept in a store of the same kind.
+pub fn open_archive(store: &dyn BookmarkStore) -> Box<dyn BookmarkStore> {
+    store.with_path(archive_path(store.path()))
+}
+
+/// Adds `bookmarks` to the archive of `store`, replacing archived entries
+/// for the same path.
+pub fn archive(store: &dyn BookmarkStore, bookmarks: &[Bookmark]) -> Result<()> {
+    let archive = open_archive(store);
+    let base = archive.load()?;
+    let mut archived = base.clone();
+    archived.retain(|a| !bookmarks.iter().any(|b| b.path == a.path));
+    archived.extend(bookmarks.iter().cloned());
+    archive.save_merged(&base, &archived)?;
+    Ok(())
+}
+
+/// Whole days since `b` was last used, or created if it never was. `None`
+/// for bookmarks from before bm recorded either.
+pub fn idle_days(b: &Bookmark, now: u64) -> Option<u64> {
+    let since = b.last_used.or(b.added)?;
+    Some(now.saturating_sub(since) / DAY)
+}
+
+/// Whether `b` went unused for at least `days` days.
+pub fn is_stale(b: &Bookmark, days: u64, now: u64) -> bool {
+    idle_days(b, now).is_some_and(|idle| idle >= days)
+}
+
+/// Moves every bookmark of `store` that is stale after `days` days into its
+/// archive and returns them.
+pub fn archive_stale(store: &dyn BookmarkStore, days: u64, now: u64) -> Result<Vec<Bookmark>> {
+    let base = store.load()?;
+    let (stale, kept): (Vec<Bookmark>, Vec<Bookmark>) =
+        base.iter().cloned().partition(|b| is_stale(b, days, now));
+    if !stale.is_empty() {
+        // Archived first, so a failure never loses a bookmark.
+        archive(store, &stale)?;
+        store.save_merged(&base, &kept)?;
+    }
+    Ok(stale)
+}
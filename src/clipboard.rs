@@ -0,0 +1,111 @@
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::error::{BmError, Result};
+use crate::paths::parse_cwd;
+use crate::wsl;
+
+/// Commands that print the clipboard, in the order they are tried.
+fn paste_commands() -> Vec<&'static [&'static str]> {
+    if cfg!(target_os = "macos") {
+        return vec![&["pbpaste"]];
+    }
+    if cfg!(windows) {
+        return vec![&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]];
+    }
+    let mut commands: Vec<&'static [&'static str]> = Vec::new();
+    if wsl::inside() {
+        commands.push(&["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"]);
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(&["wl-paste", "--no-newline"]);
+    }
+    commands.push(&["xclip", "-selection", "clipboard", "-o"]);
+    commands.push(&["xsel", "--clipboard", "--output"]);
+    commands
+}
+
+/// Commands that put what they read on stdin on the clipboard, in the
+/// order they are tried.
+fn copy_commands() -> Vec<&'static [&'static str]> {
+    if cfg!(target_os = "macos") {
+        return vec![&["pbcopy"]];
+    }
+    if cfg!(windows) {
+        return vec![&["clip"]];
+    }
+    let mut commands: Vec<&'static [&'static str]> = Vec::new();
+    if wsl::inside() {
+        commands.push(&["clip.exe"]);
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(&["wl-copy"]);
+    }
+    commands.push(&["xclip", "-selection", "clipboard", "-i"]);
+    commands.push(&["xsel", "--clipboard", "--input"]);
+    commands
+}
+
+/// Puts `text` on the clipboard with the first of `copy_commands` that
+/// succeeds.
+pub fn copy(text: &str) -> Result<()> {
+    for command in copy_commands() {
+        // xclip and wl-copy stay around to serve the clipboard, so their
+        // output is not waited for.
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        if written && child.wait().is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+        tracing::debug!("could not write the clipboard with {}", command[0]);
+    }
+    Err(BmError::Unsupported("can't write the clipboard: install wl-clipboard, xclip or xsel".to_string()))
+}
+
+/// The text on the clipboard, from the first of `paste_commands` that
+/// succeeds.
+pub fn paste() -> Result<String> {
+    for command in paste_commands() {
+        let output = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => return Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            _ => tracing::debug!("could not read the clipboard with {}", command[0]),
+        }
+    }
+    Err(BmError::Unsupported("can't read the clipboard: install wl-clipboard, xclip or xsel".to_string()))
+}
+
+/// The path in `text` as file managers copy one: the first line, without
+/// the quotes Windows' "Copy as path" adds, and as a `file://` URL
+/// decoded. Inside WSL, Windows paths are turned into `/mnt/...` ones.
+pub fn pasted_path(text: &str) -> std::result::Result<PathBuf, String> {
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty()).ok_or("the clipboard is empty")?;
+    let line = [('"', '"'), ('\'', '\'')]
+        .iter()
+        .find_map(|&(open, close)| line.strip_prefix(open)?.strip_suffix(close))
+        .unwrap_or(line);
+    let path = parse_cwd(line)?;
+    if wsl::inside()
+        && let Some(local) = path.to_str().and_then(wsl::to_wsl)
+    {
+        return Ok(PathBuf::from(local));
+    }
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path", path.display()));
+    }
+    Ok(path)
+}
@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::Path;
+
+use crate::paths::expand_tilde;
+
+/// Result of completing a partly typed directory path.
+pub struct Completion {
+    /// The input extended as far as every candidate agrees.
+    pub input: String,
+    /// Directory names that still match when the input is ambiguous.
+    pub candidates: Vec<String>,
+}
+
+/// Completes the last component of `input` against the directories on disk,
+/// like a shell does on Tab. `~` is expanded for the lookup but kept in the
+/// returned input; hidden directories are only offered once a `.` is typed.
+pub fn complete_dir(input: &str) -> Completion {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(slash) => input.split_at(slash + 1),
+        None => ("", input),
+    };
+    let lookup = match dir {
+        "" => ".".to_string(),
+        dir => expand_tilde(dir),
+    };
+    let mut names: Vec<String> = fs::read_dir(Path::new(&lookup))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.')))
+        .collect();
+    names.sort();
+    let completed = match names.as_slice() {
+        [] => return Completion { input: input.to_string(), candidates: Vec::new() },
+        [only] => return Completion { input: format!("{dir}{only}/"), candidates: Vec::new() },
+        [first, rest @ ..] => rest.iter().fold(first.as_str(), |common, name| common_prefix(common, name)),
+    };
+    Completion { input: format!("{dir}{completed}"), candidates: names }
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .chars()
+        .zip(b.chars())
+        .take_while(|(x, y)| x == y)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    &a[..len]
+}
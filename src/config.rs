@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BmError, Result};
+use crate::hooks::Hooks;
+use crate::i18n::Language;
+use crate::paths::{Remap, config_dir};
+use crate::rank::Ranking;
+
+/// Settings read from `config.toml` in the config directory. Every key is
+/// optional; a missing file means all defaults.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Number of backups kept next to the bookmarks file.
+    pub backups: usize,
+    /// Store bookmarked directories with symlinks resolved instead of
+    /// exactly as typed.
+    pub canonicalize: bool,
+    /// Save paths under the home directory as `~/...` so the file works
+    /// for other users and machines.
+    pub home_relative: bool,
+    /// Translate between `/mnt/c/...` and `C:\...` so one bookmarks file
+    /// works both inside WSL and on Windows.
+    pub wsl_paths: bool,
+    /// Directories this machine has elsewhere than the bookmarks file says.
+    pub remap: Remap,
+    /// The list's columns, in order; see `list_columns`.
+    pub columns: Vec<Column>,
+    /// Fixed widths for columns, which otherwise fit their content.
+    pub column_widths: HashMap<Column, u16>,
+    /// The column the list is sorted by when the UI starts without a saved
+    /// state; file order if unset.
+    pub sort: Option<Column>,
+    /// Bookmarks not used for this many days are flagged in the list.
+    pub stale_after_days: Option<u64>,
+    /// How the list's `s` order, `bm fzf` and the suggestions rank by use.
+    pub ranking: Ranking,
+    /// Days after which a use counts for half as much in frecency.
+    pub half_life_days: u64,
+    /// Directories `bm track` remembers visits to, besides bookmarked ones;
+    /// past it the least recently visited is forgotten. 0 for no limit.
+    pub max_visits: usize,
+    /// Glob patterns of directories that are never bookmarked or tracked,
+    /// such as `**/node_modules/**`.
+    pub exclude: Vec<String>,
+    /// Move flagged bookmarks to the archive when the UI starts.
+    pub archive_stale: bool,
+    /// Commands run when bookmarks are jumped to, added or removed.
+    pub hooks: Hooks,
+    /// Which actions ask before they are carried out.
+    pub confirm: Confirm,
+    /// Show paths in the list as OSC 8 `file://` hyperlinks.
+    pub hyperlinks: bool,
+    /// How the list marks project directories, files and remote paths.
+    pub icons: Icons,
+    /// What `bm status --prompt` prints inside a bookmark, with `{name}`
+    /// replaced by its name.
+    pub prompt_format: String,
+    /// The language of help and error messages; the locale's by default.
+    pub language: Option<Language>,
+    /// Never write the bookmarks file or its archive. Set by `--read-only`
+    /// rather than in the file.
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+/// Every key `config.toml` may have, with the tables' own keys after a dot,
+/// for `bm doctor` to point out the ones it doesn't know. The keys of
+/// `remap` are paths, so any goes there. Kept in step with `Config`.
+const KEYS: &[&str] = &[
+    "backups",
+    "canonicalize",
+    "home_relative",
+    "wsl_paths",
+    "remap",
+    "columns",
+    "column_widths.name",
+    "column_widths.path",
+    "column_widths.tags",
+    "column_widths.last_used",
+    "column_widths.hits",
+    "sort",
+    "stale_after_days",
+    "ranking",
+    "half_life_days",
+    "max_visits",
+    "exclude",
+    "archive_stale",
+    "hooks.pre_jump",
+    "hooks.post_add",
+    "hooks.post_delete",
+    "confirm.delete",
+    "confirm.prune",
+    "confirm.quit",
+    "hyperlinks",
+    "icons",
+    "prompt_format",
+    "language",
+];
+
+/// The keys of the TOML `content` that aren't in `KEYS`, as `table.key`
+/// for those inside a table. Content that doesn't parse has none.
+pub fn unknown_keys(content: &str) -> Vec<String> {
+    let Ok(table) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let mut unknown = Vec::new();
+    for (key, value) in &table {
+        match value.as_table() {
+            Some(inner) if key != "remap" => {
+                let prefix = format!("{key}.");
+                if !KEYS.iter().any(|k| k.starts_with(&prefix)) {
+                    unknown.push(key.clone());
+                    continue;
+                }
+                unknown.extend(inner.keys().map(|k| format!("{prefix}{k}")).filter(|k| !KEYS.contains(&k.as_str())));
+            }
+            _ if !KEYS.contains(&key.as_str()) => unknown.push(key.clone()),
+            _ => {}
+        }
+    }
+    unknown
+}
+
+/// Per-bookmark information the list can show.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Name,
+    /// Always shown.
+    Path,
+    /// The tags and the first line of the note.
+    Tags,
+    /// How long ago the bookmark was last jumped to.
+    LastUsed,
+    /// How many times it was jumped to.
+    Hits,
+}
+
+/// Whether each action that can't simply be taken back asks first.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Confirm {
+    /// Archiving bookmarks with `!` in the list.
+    pub delete: bool,
+    /// Archiving stale bookmarks with `bm expire`.
+    pub prune: bool,
+    /// Quitting the list before its changes are written.
+    pub quit: bool,
+}
+
+impl Default for Confirm {
+    fn default() -> Self {
+        Confirm { delete: true, prune: false, quit: false }
+    }
+}
+
+/// The icons drawn before each path in the list.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Icons {
+    /// Nerd Font glyphs, which need a patched font.
+    Nerd,
+    /// Badges like `[rs]`, for project directories only.
+    Ascii,
+    Off,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backups: 10,
+            canonicalize: false,
+            home_relative: false,
+            wsl_paths: false,
+            remap: Remap::default(),
+            columns: vec![Column::Name, Column::Path, Column::Tags, Column::LastUsed],
+            column_widths: HashMap::new(),
+            sort: None,
+            stale_after_days: None,
+            ranking: Ranking::default(),
+            half_life_days: 14,
+            max_visits: 1000,
+            exclude: Vec::new(),
+            archive_stale: false,
+            hooks: Hooks::default(),
+            confirm: Confirm::default(),
+            hyperlinks: false,
+            icons: Icons::Nerd,
+            prompt_format: "📌 {name}".to_string(),
+            language: None,
+            read_only: false,
+        }
+    }
+}
+
+impl Config {
+    /// The columns the list shows. Without `path`, `columns` is read the
+    /// way it used to be: what follows the name, path and tags.
+    pub fn list_columns(&self) -> Vec<Column> {
+        let mut columns = self.columns.clone();
+        if !columns.contains(&Column::Path) {
+            columns.splice(0..0, [Column::Name, Column::Path, Column::Tags]);
+        }
+        let mut seen = Vec::new();
+        columns.retain(|column| {
+            let first = !seen.contains(column);
+            seen.push(*column);
+            first
+        });
+        columns
+    }
+
+    pub fn path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("config.toml"))
+    }
+
+    pub fn load() -> Result<Config> {
+        Config::load_from(&Config::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Config> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| BmError::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(BmError::io(path, e)),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::add::name_from_path;
+use crate::store::{Bookmark, Kind};
+
+/// The columns of a CSV bookmarks file, in order. Tags are separated by
+/// `;` and workspace commands by line breaks; times are seconds since the
+/// Unix epoch, so a spreadsheet round trip keeps them exact.
+const HEADER: [&str; 10] = ["name", "path", "kind", "note", "tags", "added", "last_used", "hits", "workspace", "command"];
+
+/// `field` quoted as RFC 4180 requires when it holds a separator, quote or
+/// line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// `bookmarks` as CSV with a header line.
+pub fn write(bookmarks: &[Bookmark]) -> String {
+    let mut out = HEADER.join(",") + "\n";
+    for b in bookmarks {
+        let number = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_default();
+        let fields = [
+            b.name.clone(),
+            b.path.clone(),
+            b.kind.as_str().to_string(),
+            b.note.clone(),
+            b.tags.join(";"),
+            number(b.added),
+            number(b.last_used),
+            b.hits.to_string(),
+            b.workspace.join("\n"),
+            b.command.clone().unwrap_or_default(),
+        ];
+        out.push_str(&fields.iter().map(|f| quote(f)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Splits `content` into records of fields, undoing the quoting of `quote`.
+/// Each record comes with the line it starts on.
+fn records(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let (mut line, mut start) = (1, 1);
+    let mut chars = content.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            '\n' if quoted => {
+                line += 1;
+                field.push(c);
+            }
+            _ if quoted => field.push(c),
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("line {start}: unterminated quoted field"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+/// The bookmarks in CSV `content` as `write` produces it. Columns are found
+/// by the header, so they may come in any order and only `path` is required;
+/// a missing name is derived from the path like `bm add` does.
+pub fn parse(content: &str) -> Result<Vec<Bookmark>, String> {
+    let mut records = records(content)?.into_iter().filter(|(_, r)| r.iter().any(|f| !f.trim().is_empty()));
+    let Some((_, header)) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let Some(path_column) = column("path") else {
+        return Err("line 1: no `path` column".to_string());
+    };
+    let columns: Vec<Option<usize>> = HEADER.iter().map(|name| column(name)).collect();
+    let mut bookmarks = Vec::new();
+    for (line, record) in records {
+        let get = |i: usize| columns[i].and_then(|c| record.get(c)).map(|f| f.trim()).unwrap_or("");
+        let number = |i: usize| -> Result<Option<u64>, String> {
+            match get(i) {
+                "" => Ok(None),
+                n => n.parse().map(Some).map_err(|_| format!("line {line}: {} is not a number: `{n}`", HEADER[i])),
+            }
+        };
+        let path = record.get(path_column).map(|f| f.trim()).unwrap_or("");
+        if path.is_empty() {
+            return Err(format!("line {line}: empty path"));
+        }
+        let name = match get(0) {
+            "" => name_from_path(path),
+            name => name.to_string(),
+        };
+        let kind = match get(2) {
+            "" | "dir" => Kind::Dir,
+            "file" => Kind::File,
+            "remote" => Kind::Remote,
+            other => return Err(format!("line {line}: unknown kind `{other}`")),
+        };
+        let mut b = Bookmark::new(name, path.to_string());
+        b.kind = kind;
+        b.note = get(3).to_string();
+        b.tags = get(4).split(';').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect();
+        b.added = number(5)?;
+        b.last_used = number(6)?;
+        b.hits = number(7)?.unwrap_or(0);
+        b.workspace = get(8).lines().map(str::trim).filter(|c| !c.is_empty()).map(str::to_string).collect();
+        b.command = Some(get(9).to_string()).filter(|c| !c.is_empty());
+        bookmarks.push(b);
+    }
+    Ok(bookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_separators_quotes_and_line_breaks() {
+        let mut b = Bookmark::new("web, \"prod\"".to_string(), "/srv/www".to_string());
+        b.kind = Kind::Remote;
+        b.note = "deploys go \"here\",\nnot to staging".to_string();
+        b.tags = vec!["work".to_string(), "ops".to_string()];
+        b.added = Some(1_700_000_000);
+        b.last_used = Some(1_700_000_600);
+        b.hits = 3;
+        b.workspace = vec!["git pull, then build".to_string(), "echo \"ready\"".to_string()];
+        b.command = Some("make serve".to_string());
+        let plain = Bookmark::new("tmp".to_string(), "/tmp".to_string());
+        let bookmarks = vec![b, plain];
+
+        let parsed = parse(&write(&bookmarks)).unwrap();
+        assert!(parsed == bookmarks);
+    }
+
+    #[test]
+    fn reports_an_unterminated_quote_with_its_line() {
+        let content = "name,path,note\nok,/tmp,fine\nbad,/srv,\"never\nclosed\n";
+        assert_eq!(parse(content).err().unwrap(), "line 3: unterminated quoted field");
+    }
+
+    #[test]
+    fn reports_a_field_that_is_not_a_number() {
+        let content = "name,path,hits\nok,/tmp,2\nbad,/srv,many\n";
+        assert_eq!(parse(content).err().unwrap(), "line 3: hits is not a number: `many`");
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{BmError, Result};
+use crate::fuzzy;
+use crate::paths::data_dir;
+use crate::probe::{self, Status};
+use crate::rank::Ranker;
+use crate::store::{Bookmark, BookmarkStore};
+
+/// How often the daemon checks again whether the bookmarked paths exist.
+const PROBE_EVERY: Duration = Duration::from_secs(60);
+
+/// How long a client may take to send its request or read the answer.
+/// Clients are served one at a time, so one that stalls holds up the rest
+/// until then.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The socket the daemon for the bookmarks file at `path` listens on, in
+/// the runtime directory (the data directory where there is none). It is
+/// named after a hash of the path, so every file and profile has its own
+/// and the name stays short enough for a socket. The hash is FNV-1a, which
+/// unlike std's hasher gives the same name whichever Rust built the client
+/// and the daemon.
+pub fn socket_path(path: &Path) -> Result<PathBuf> {
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir.join("bm"),
+        None => data_dir()?,
+    };
+    let hash = path
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+    Ok(dir.join(format!("daemon-{hash:016x}.sock")))
+}
+
+/// The bookmarks the daemon holds, shared with the thread that checks
+/// their paths.
+struct Loaded {
+    /// The bookmarks as last loaded or saved.
+    bookmarks: Vec<Bookmark>,
+    /// Names of the bookmarks whose path was missing at the last check.
+    missing: Vec<String>,
+}
+
+/// What the daemon keeps in memory.
+struct State {
+    store: Box<dyn BookmarkStore>,
+    loaded: Arc<Mutex<Loaded>>,
+    /// Signalled when the bookmarks file changes.
+    changes: Receiver<()>,
+    ranker: Ranker,
+}
+
+impl State {
+    /// The bookmarks, loaded again first if the file changed since.
+    fn bookmarks(&mut self) -> Vec<Bookmark> {
+        let mut loaded = self.loaded.lock().expect("daemon state poisoned");
+        if self.changes.try_iter().count() > 0 {
+            match self.store.load() {
+                Ok(bookmarks) => loaded.bookmarks = bookmarks,
+                Err(e) => tracing::warn!("keeping the bookmarks loaded before: {e}"),
+            }
+        }
+        loaded.bookmarks.clone()
+    }
+
+    /// Records a visit to the bookmark at `index` of `base` like `bm get`
+    /// does, and answers with its name, directory and kind.
+    fn visit(&mut self, base: Vec<Bookmark>, index: usize) -> Result<Vec<String>> {
+        let mut bookmarks = base.clone();
+        let bookmark = &mut bookmarks[index];
+        let target = bookmark
+            .target()
+            .map_err(|var| BmError::UnsetVariable { path: bookmark.path.clone(), var })?;
+        bookmark.touch();
+        let line = format!("{}\t{target}\t{}", bookmark.name, bookmark.kind.as_str());
+        if !self.store.is_read_only() {
+            let (saved, _) = self.store.save_merged(&base, &bookmarks)?;
+            self.loaded.lock().expect("daemon state poisoned").bookmarks = saved;
+        }
+        Ok(vec![line])
+    }
+
+    /// The answer to `request`, one of `get`, `pick`, `fzf` and `status`,
+    /// with the name or query after a tab.
+    fn answer(&mut self, request: &str) -> Result<Vec<String>> {
+        let (command, arg) = request.split_once('\t').unwrap_or((request, ""));
+        let mut bookmarks = self.bookmarks();
+        match command {
+            "get" => {
+                let index = bookmarks.iter().position(|b| b.name == arg);
+                self.visit(bookmarks, index.ok_or_else(|| BmError::UnknownBookmark(arg.to_string()))?)
+            }
+            "pick" => {
+                let index = fuzzy::best_match(arg, &bookmarks);
+                self.visit(bookmarks, index.ok_or_else(|| BmError::NoMatch(arg.to_string()))?)
+            }
+            "fzf" => {
+                self.ranker.sort(&mut bookmarks);
+                Ok(bookmarks.iter().map(|b| format!("{}\t{}", b.name, b.path)).collect())
+            }
+            "status" => {
+                let mut lines = vec![format!("{} bookmarks from {}", bookmarks.len(), self.store.path().display())];
+                let missing = &self.loaded.lock().expect("daemon state poisoned").missing;
+                if !missing.is_empty() {
+                    lines.push(format!("missing: {}", missing.join(", ")));
+                }
+                Ok(lines)
+            }
+            _ => Err(BmError::Unsupported(format!("unknown request `{command}`"))),
+        }
+    }
+}
+
+/// Writes the answer to one request: `ok` and its lines, or `err`, the kind
+/// of error and its message.
+fn respond(stream: &mut UnixStream, answer: Result<Vec<String>>) -> std::io::Result<()> {
+    let mut out = String::new();
+    match answer {
+        Ok(lines) => {
+            out.push_str("ok\n");
+            for line in lines {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        Err(BmError::UnknownBookmark(name)) => out.push_str(&format!("err\tunknown\t{name}\n")),
+        Err(BmError::NoMatch(query)) => out.push_str(&format!("err\tno_match\t{query}\n")),
+        Err(e) => out.push_str(&format!("err\tother\t{e}\n")),
+    }
+    stream.write_all(out.as_bytes())
+}
+
+/// Serves the bookmarks of `store` on its socket until bm is stopped.
+/// They are loaded once and again after the file changes, so queries
+/// don't parse it each time; every `PROBE_EVERY` the paths are checked again.
+pub fn serve(store: Box<dyn BookmarkStore>, ranker: Ranker) -> Result<()> {
+    let socket = socket_path(store.path())?;
+    if UnixStream::connect(&socket).is_ok() {
+        return Err(BmError::Unsupported(format!("a daemon is already serving {}", store.path().display())));
+    }
+    // Left behind by a daemon that didn't get to clean up.
+    let _ = fs::remove_file(&socket);
+    if let Some(dir) = socket.parent() {
+        fs::create_dir_all(dir).map_err(|e| BmError::io(dir, e))?;
+    }
+    let listener = UnixListener::bind(&socket).map_err(|e| BmError::io(&socket, e))?;
+    remove_on_exit(socket.clone());
+
+    let (_watcher, changes) = store.watch().map_err(|e| BmError::Unsupported(format!("can't watch the bookmarks file: {e}")))?;
+    let loaded = Arc::new(Mutex::new(Loaded { bookmarks: store.load()?, missing: Vec::new() }));
+    let probed = Arc::clone(&loaded);
+    thread::spawn(move || {
+        loop {
+            let bookmarks = probed.lock().expect("daemon state poisoned").bookmarks.clone();
+            let status = probe::check_all(&bookmarks, probe::TIMEOUT);
+            let missing = bookmarks.iter().zip(status).filter(|(_, s)| *s == Status::Missing).map(|(b, _)| b.name.clone());
+            probed.lock().expect("daemon state poisoned").missing = missing.collect();
+            thread::sleep(PROBE_EVERY);
+        }
+    });
+    let mut state = State { store, loaded, changes, ranker };
+
+    tracing::info!("serving {} on {}", state.store.path().display(), socket.display());
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if let Err(e) = stream.set_read_timeout(Some(CLIENT_TIMEOUT)).and_then(|()| stream.set_write_timeout(Some(CLIENT_TIMEOUT))) {
+            tracing::debug!("could not set a timeout on a connection: {e}");
+            continue;
+        }
+        let mut request = String::new();
+        if BufReader::new(&stream).read_line(&mut request).is_err() {
+            continue;
+        }
+        let answer = state.answer(request.trim_end_matches('\n'));
+        if let Err(e) = respond(&mut stream, answer) {
+            tracing::debug!("could not answer `{}`: {e}", request.trim_end());
+        }
+    }
+    Ok(())
+}
+
+/// Removes `socket` and exits when bm is told to stop.
+fn remove_on_exit(socket: PathBuf) {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    let Ok(mut signals) = signal_hook::iterator::Signals::new([SIGTERM, SIGINT, SIGHUP]) else {
+        return;
+    };
+    thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            let _ = fs::remove_file(&socket);
+            std::process::exit(0);
+        }
+    });
+}
+
+/// Asks the daemon serving the bookmarks file at `path` to answer
+/// `request`, see `State::answer`. `None` if no daemon is running, so the
+/// caller reads the file itself.
+pub fn query(path: &Path, request: &str) -> Option<Result<Vec<String>>> {
+    let mut stream = UnixStream::connect(socket_path(path).ok()?).ok()?;
+    stream.write_all(format!("{request}\n").as_bytes()).ok()?;
+    let mut lines = BufReader::new(stream).lines();
+    let status = lines.next()?.ok()?;
+    if status == "ok" {
+        return Some(Ok(lines.map_while(|l| l.ok()).collect()));
+    }
+    let mut fields = status.splitn(3, '\t').skip(1);
+    let (kind, message) = (fields.next()?, fields.next().unwrap_or("").to_string());
+    Some(Err(match kind {
+        "unknown" => BmError::UnknownBookmark(message),
+        "no_match" => BmError::NoMatch(message),
+        _ => BmError::Unsupported(format!("daemon: {message}")),
+    }))
+}
@@ -0,0 +1,194 @@
+use crate::add::canonical;
+use crate::paths::{normalize, same_path};
+use crate::store::Bookmark;
+
+/// Indexes of the bookmarks whose paths only differ by a trailing separator
+/// (or by case, where the filesystem ignores it), in list order. Only
+/// groups with more than one bookmark are returned.
+pub fn duplicate_groups(bookmarks: &[Bookmark]) -> Vec<Vec<usize>> {
+    groups_by(bookmarks, |b| normalize(&b.path))
+}
+
+/// Like [`duplicate_groups`], but also groups bookmarks that reach the same
+/// directory through symlinks, `..` or environment variables.
+pub fn same_directory_groups(bookmarks: &[Bookmark]) -> Vec<Vec<usize>> {
+    groups_by(bookmarks, |b| {
+        let target = b.target().unwrap_or_else(|_| b.path.clone());
+        canonical(&target).unwrap_or_else(|| normalize(&target))
+    })
+}
+
+fn groups_by(bookmarks: &[Bookmark], key: impl Fn(&Bookmark) -> String) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, b) in bookmarks.iter().enumerate() {
+        let key = key(b);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).filter(|g| g.len() > 1).collect()
+}
+
+/// Folds `other` into `target`: notes are joined when they differ, tags are
+/// combined, visits are added up and the earlier creation and later use are
+/// kept. `other`'s workspace and command are only taken when `target` has
+/// none.
+pub fn merge_into(target: &mut Bookmark, other: &Bookmark) {
+    if target.note.is_empty() {
+        target.note = other.note.clone();
+    } else if !other.note.is_empty() && other.note != target.note {
+        target.note = format!("{}\n{}", target.note, other.note);
+    }
+    for tag in &other.tags {
+        if !target.tags.contains(tag) {
+            target.tags.push(tag.clone());
+        }
+    }
+    target.added = target.added.into_iter().chain(other.added).min();
+    target.last_used = target.last_used.max(other.last_used);
+    target.hits += other.hits;
+    if target.workspace.is_empty() {
+        target.workspace = other.workspace.clone();
+    }
+    if target.command.is_none() {
+        target.command = other.command.clone();
+    }
+}
+
+/// A bookmark both copies of a file have, edited differently on each side.
+pub struct Conflict {
+    /// Position of the bookmark in the merged list, which holds the newer version.
+    pub index: usize,
+    pub ours: Bookmark,
+    pub theirs: Bookmark,
+}
+
+/// When the bookmark was last used, or else created.
+fn last_touched(b: &Bookmark) -> Option<u64> {
+    b.last_used.or(b.added)
+}
+
+/// Whether `a` and `b` only differ in how the path is written and in their
+/// creation and use.
+fn same_content(a: &Bookmark, b: &Bookmark) -> bool {
+    let mut a = a.clone();
+    a.path.clone_from(&b.path);
+    a.added = b.added;
+    a.last_used = b.last_used;
+    a.hits = b.hits;
+    a == *b
+}
+
+/// Merges `theirs` into `ours`, two copies of a bookmarks file that
+/// diverged, say in a sync service, matching bookmarks by path. Bookmarks
+/// only one side has are kept. Of one both have, the version used (or
+/// created) more recently is kept, with the earlier creation, later use
+/// and higher visit count of the two. Returns the merged list and the
+/// bookmarks whose name, note, tags or commands differ between the copies.
+pub fn merge_copies(ours: &[Bookmark], theirs: &[Bookmark]) -> (Vec<Bookmark>, Vec<Conflict>) {
+    let mut merged = ours.to_vec();
+    let mut conflicts = Vec::new();
+    for t in theirs {
+        let Some(index) = merged.iter().position(|o| same_path(&o.path, &t.path)) else {
+            merged.push(t.clone());
+            continue;
+        };
+        let theirs_newer = last_touched(t) > last_touched(&merged[index]);
+        let mut o = merged[index].clone();
+        let mut t = t.clone();
+        let added = o.added.into_iter().chain(t.added).min();
+        let last_used = o.last_used.max(t.last_used);
+        let hits = o.hits.max(t.hits);
+        for b in [&mut o, &mut t] {
+            b.added = added;
+            b.last_used = last_used;
+            b.hits = hits;
+        }
+        merged[index] = if theirs_newer { t.clone() } else { o.clone() };
+        if !same_content(&o, &t) {
+            conflicts.push(Conflict { index, ours: o, theirs: t });
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Which entry of a duplicate group each part of the merged bookmark is
+/// taken from, as positions in the group.
+pub struct Choice {
+    pub name: usize,
+    /// The merged bookmark also takes this entry's place in the list.
+    pub path: usize,
+    pub note: usize,
+    /// Whether each entry's tags are kept.
+    pub tags: Vec<bool>,
+}
+
+impl Choice {
+    /// Everything from the first entry, with the tags of all of them.
+    pub fn new(len: usize) -> Self {
+        Choice { name: 0, path: 0, note: 0, tags: vec![true; len] }
+    }
+
+    /// The bookmark that replaces `group`.
+    pub fn merged(&self, bookmarks: &[Bookmark], group: &[usize]) -> Bookmark {
+        let entry = |pos: usize| &bookmarks[group[pos]];
+        let mut merged = Bookmark::new(entry(self.name).name.clone(), entry(self.path).path.clone());
+        merged.kind = entry(self.path).kind;
+        merged.note = entry(self.note).note.clone();
+        // The workspace and command go with the path, unless only another
+        // entry has one.
+        let path_first = || std::iter::once(group[self.path]).chain(group.iter().copied()).map(|i| &bookmarks[i]);
+        merged.workspace = path_first().map(|b| &b.workspace).find(|w| !w.is_empty()).cloned().unwrap_or_default();
+        merged.command = path_first().find_map(|b| b.command.clone());
+        merged.added = group.iter().filter_map(|&i| bookmarks[i].added).min();
+        merged.last_used = group.iter().filter_map(|&i| bookmarks[i].last_used).max();
+        merged.hits = group.iter().map(|&i| bookmarks[i].hits).sum();
+        for (pos, _) in self.tags.iter().enumerate().filter(|(_, keep)| **keep) {
+            for tag in &entry(pos).tags {
+                if !merged.tags.contains(tag) {
+                    merged.tags.push(tag.clone());
+                }
+            }
+        }
+        merged
+    }
+}
+
+/// Replaces each group by the bookmark its choice describes. Returns the
+/// number of bookmarks removed.
+pub fn apply_choices(bookmarks: &mut Vec<Bookmark>, choices: &[(Vec<usize>, Choice)]) -> usize {
+    let mut drop = vec![false; bookmarks.len()];
+    for (group, choice) in choices {
+        let merged = choice.merged(bookmarks, group);
+        for (pos, &i) in group.iter().enumerate() {
+            drop[i] = pos != choice.path;
+        }
+        bookmarks[group[choice.path]] = merged;
+    }
+    let removed = drop.iter().filter(|d| **d).count();
+    let mut drop = drop.into_iter();
+    bookmarks.retain(|_| !drop.next().unwrap_or(false));
+    removed
+}
+
+/// Merges every group of near-duplicates into its first bookmark, which
+/// keeps its path as written so saving leaves it in place. Returns the
+/// removed bookmarks, each with the path of the one it was merged into.
+pub fn dedup(bookmarks: &mut Vec<Bookmark>) -> Vec<(Bookmark, String)> {
+    let mut removed = Vec::new();
+    let mut drop = vec![false; bookmarks.len()];
+    for group in duplicate_groups(bookmarks) {
+        let (&first, rest) = group.split_first().expect("groups have several entries");
+        let kept = bookmarks[first].path.clone();
+        for &i in rest {
+            let other = bookmarks[i].clone();
+            merge_into(&mut bookmarks[first], &other);
+            drop[i] = true;
+            removed.push((other, kept.clone()));
+        }
+    }
+    let mut drop = drop.into_iter();
+    bookmarks.retain(|_| !drop.next().unwrap_or(false));
+    removed
+}
@@ -0,0 +1,180 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dirs::home_dir;
+
+use crate::config::{self, Config};
+use crate::probe::{self, Status};
+use crate::store::{Bookmark, BookmarkStore};
+
+/// What one check of `bm doctor` found.
+pub struct Check {
+    /// What was checked, and how it went.
+    pub summary: String,
+    /// What to do about it, if something is wrong.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(summary: String) -> Self {
+        Check { summary, fix: None }
+    }
+
+    fn problem(summary: String, fix: String) -> Self {
+        Check { summary, fix: Some(fix) }
+    }
+}
+
+/// Whether `config.toml` at `path` parses and has only keys bm knows.
+pub fn check_config(path: &Path) -> Check {
+    let shown = path.display();
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Check::ok(format!("no config file at {shown}; the defaults apply"));
+        }
+        Err(e) => return Check::problem(format!("{shown} can't be read: {e}"), format!("check its permissions: ls -l {shown}")),
+    };
+    if let Err(e) = Config::load_from(path) {
+        return Check::problem(e.to_string(), format!("correct {shown}; every key is described in the README"));
+    }
+    let unknown = config::unknown_keys(&content);
+    if unknown.is_empty() {
+        return Check::ok(format!("{shown} is valid"));
+    }
+    Check::problem(
+        format!("{shown} has keys bm doesn't know, which are ignored: {}", unknown.join(", ")),
+        "remove them, or correct their spelling; every key is described in the README".to_string(),
+    )
+}
+
+/// Whether the bookmarks file of `store` loads, with its bookmarks if it does.
+pub fn check_file(store: &dyn BookmarkStore) -> (Check, Option<Vec<Bookmark>>) {
+    let shown = store.path().display();
+    match store.load() {
+        Ok(bookmarks) => (Check::ok(format!("{shown} holds {} bookmarks", bookmarks.len())), Some(bookmarks)),
+        Err(e) => {
+            let fix = "fix it with `bm edit`, or open `bm` and press `r` to restore the latest backup".to_string();
+            (Check::problem(e.to_string(), fix), None)
+        }
+    }
+}
+
+/// Whether every bookmarked path is there.
+pub fn check_paths(bookmarks: &[Bookmark]) -> Vec<Check> {
+    let status = probe::check_all(bookmarks, probe::TIMEOUT);
+    let with = |wanted: Status| -> Vec<&str> {
+        bookmarks.iter().zip(&status).filter(|&(_, &s)| s == wanted).map(|(b, _)| b.name.as_str()).collect()
+    };
+    let (missing, unknown) = (with(Status::Missing), with(Status::Unknown));
+    let mut checks = Vec::new();
+    if missing.is_empty() && unknown.is_empty() {
+        checks.push(Check::ok("every bookmarked path exists".to_string()));
+    }
+    if !missing.is_empty() {
+        checks.push(Check::problem(
+            format!("{} bookmarks point at nothing: {}", missing.len(), missing.join(", ")),
+            "map directories that moved under [remap] in config.toml, or archive the rest with `!` in `bm`".to_string(),
+        ));
+    }
+    if !unknown.is_empty() {
+        checks.push(Check::problem(
+            format!("{} bookmarks could not be checked in time: {}", unknown.len(), unknown.join(", ")),
+            "they are likely on a network mount that is down; try again once it is back".to_string(),
+        ));
+    }
+    checks
+}
+
+/// The shell bm is run from, by `$SHELL` or the variables Nushell and
+/// PowerShell set, with the files it reads at startup.
+fn current_shell() -> Option<(&'static str, Vec<PathBuf>)> {
+    let home = home_dir()?;
+    let config = dirs::config_dir().unwrap_or_else(|| home.join(".config"));
+    if env::var_os("NU_VERSION").is_some() {
+        let dir = config.join("nushell");
+        return Some(("nu", vec![dir.join("config.nu"), dir.join("env.nu")]));
+    }
+    if env::var_os("PSModulePath").is_some() && env::var_os("SHELL").is_none() {
+        let dir = dirs::document_dir().unwrap_or_else(|| home.join("Documents"));
+        return Some(("powershell", vec![dir.join("PowerShell/Microsoft.PowerShell_profile.ps1")]));
+    }
+    let shell = env::var("SHELL").ok()?;
+    match Path::new(&shell).file_name()?.to_str()? {
+        "bash" => Some(("bash", vec![home.join(".bashrc"), home.join(".bash_profile"), home.join(".profile")])),
+        "zsh" => {
+            let dir = env::var_os("ZDOTDIR").map_or(home, PathBuf::from);
+            Some(("zsh", vec![dir.join(".zshrc"), dir.join(".zprofile")]))
+        }
+        "fish" => {
+            let dir = config.join("fish");
+            let mut files = vec![dir.join("config.fish")];
+            if let Ok(entries) = fs::read_dir(dir.join("conf.d")) {
+                files.extend(entries.filter_map(|e| e.ok()).map(|e| e.path()));
+            }
+            Some(("fish", files))
+        }
+        _ => None,
+    }
+}
+
+/// The line that loads `bm init` into `shell`, as the README has it.
+fn init_line(shell: &str) -> &'static str {
+    match shell {
+        "zsh" => "eval \"$(bm init zsh)\"",
+        "fish" => "bm init fish | source",
+        "powershell" => "bm init powershell | Out-String | Invoke-Expression",
+        "nu" => "bm init nu | save -f ~/.config/nushell/bm.nu, then `source ~/.config/nushell/bm.nu`",
+        _ => "eval \"$(bm init bash)\"",
+    }
+}
+
+/// Whether the startup files of the current shell load `bm init`.
+pub fn check_shell() -> Check {
+    let Some((shell, files)) = current_shell() else {
+        return Check::ok("could not tell which shell this is; skipped the shell integration".to_string());
+    };
+    let loads = |file: &PathBuf| fs::read_to_string(file).is_ok_and(|c| c.contains("bm init") || c.contains("bm.nu"));
+    match files.iter().find(|f| loads(f)) {
+        Some(file) => Check::ok(format!("{shell} loads the shell integration from {}", file.display())),
+        None => {
+            let file = files.first().map(|f| f.display().to_string()).unwrap_or_default();
+            Check::problem(
+                format!("{shell} doesn't load the shell integration, so bmgo, bmpush and bmpop are missing"),
+                format!("add this to {file}: {}", init_line(shell)),
+            )
+        }
+    }
+}
+
+/// Whether the bookmarks file and its directory can be written, and by
+/// no one else; another user who can write it picks where `bmgo` goes.
+pub fn check_permissions(path: &Path, read_only: bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    for (target, is_dir) in [(path, false), (dir, true)] {
+        let shown = target.display();
+        let Ok(meta) = fs::metadata(target) else {
+            continue;
+        };
+        if meta.permissions().readonly() && !read_only {
+            let what = if is_dir { "backups and new files can't be created in it" } else { "changes can't be saved" };
+            checks.push(Check::problem(format!("{shown} is read-only, so {what}"), format!("chmod u+w {shown}")));
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if meta.permissions().mode() & 0o022 != 0 {
+                checks.push(Check::problem(
+                    format!("{shown} can be written by other users, who could change where bookmarks lead"),
+                    format!("chmod go-w {shown}"),
+                ));
+            }
+        }
+    }
+    if checks.is_empty() {
+        checks.push(Check::ok(format!("{} and its directory have sane permissions", path.display())));
+    }
+    checks
+}
@@ -0,0 +1,68 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{BmError, Result};
+use crate::launch;
+
+/// Opens `path` in `$VISUAL`/`$EDITOR` (falling back to `vi`) and waits for it to exit.
+pub fn run_editor(path: &Path) -> Result<ExitStatus> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    Command::new(program)
+        .args(parts)
+        .arg(path)
+        .stdout(launch::terminal_stdout())
+        .status()
+        .map_err(|e| BmError::io(Path::new(program), e))
+}
+
+/// Creates a file for a note in the temp directory that nobody else can
+/// read, under a name not taken yet, so a file or symlink planted there
+/// beforehand is never written through.
+fn create_note_file() -> Result<(PathBuf, File)> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    let mut last = None;
+    for attempt in 0..100u32 {
+        let name = format!("bm-note-{}-{:08x}.txt", std::process::id(), nanos.wrapping_add(attempt.wrapping_mul(0x9e37_79b9)));
+        let path = std::env::temp_dir().join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last = Some((path, e)),
+            Err(e) => return Err(BmError::io(&path, e)),
+        }
+    }
+    let (path, e) = last.expect("at least one attempt");
+    Err(BmError::io(&path, e))
+}
+
+/// Writes `text` to a temp file, lets the user edit it, and returns the result.
+/// Returns `None` when the editor exits unsuccessfully.
+pub fn edit_text_externally(text: &str) -> Result<Option<String>> {
+    let (tmp, mut file) = create_note_file()?;
+    let written = file.write_all(text.as_bytes());
+    drop(file);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(BmError::io(&tmp, e));
+    }
+    let status = run_editor(&tmp);
+    let edited = fs::read_to_string(&tmp).map_err(|e| BmError::io(&tmp, e));
+    let _ = fs::remove_file(&tmp);
+    if !status?.success() {
+        return Ok(None);
+    }
+    Ok(Some(edited?.trim_end().to_string()))
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::i18n::{self, Language};
+
+/// Every failure bm can report, with enough context to act on.
+#[derive(Debug)]
+pub enum BmError {
+    /// Reading or writing a file failed.
+    Io { path: PathBuf, source: io::Error },
+    /// A bookmarks file exists but is not valid.
+    Parse { path: PathBuf, message: String },
+    /// The bookmarks could not be serialized for saving.
+    Serialize(String),
+    /// An SQLite bookmarks database could not be read or written.
+    #[cfg(feature = "sqlite")]
+    Database { path: PathBuf, message: String },
+    /// An encrypted bookmarks file could not be decrypted or encrypted.
+    #[cfg(feature = "encryption")]
+    Encryption { path: PathBuf, message: String },
+    /// The requested operation is not available in this build or for this file.
+    Unsupported(String),
+    /// The home directory could not be determined.
+    NoHome,
+    /// No bookmark has the requested name.
+    UnknownBookmark(String),
+    /// No bookmark matches a fuzzy query.
+    NoMatch(String),
+    /// A bookmark's path uses an environment variable that is not set.
+    UnsetVariable { path: String, var: String },
+    /// A profile name that can't be used as a file name.
+    InvalidProfile(String),
+    /// The bookmark has no `command` to run.
+    NoCommand(String),
+    /// A bookmark's command could not be run or failed.
+    Launch { command: String, message: String },
+    /// A hook from `config.toml` could not be run or failed.
+    Hook { hook: &'static str, message: String },
+    /// A tmux command failed; holds what tmux reported.
+    Tmux(String),
+    /// A git command run by `bm sync` failed, or the repository can't be synced.
+    Git(String),
+    /// A write to the bookmarks file at the path was refused, as bm runs read-only.
+    ReadOnly(PathBuf),
+    /// Setting up, drawing or restoring the terminal failed.
+    Terminal(io::Error),
+    /// The user backed out: answered no to a prompt or pressed Ctrl-C.
+    Cancelled,
+    /// The UI or a menu was closed without picking a bookmark.
+    NoSelection,
+}
+
+pub type Result<T, E = BmError> = std::result::Result<T, E>;
+
+impl BmError {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        BmError::Io { path: path.to_path_buf(), source }
+    }
+
+    /// The status bm exits with, for scripts to branch on: 3 when nothing
+    /// was picked, 4 when no bookmark has the name or matches, 5 when a file
+    /// isn't valid, 130 when cancelled and 1 otherwise. Bad arguments exit
+    /// with clap's 2.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            BmError::NoSelection => 3,
+            BmError::UnknownBookmark(_) | BmError::NoMatch(_) => 4,
+            BmError::Parse { .. } | BmError::Serialize(_) => 5,
+            #[cfg(feature = "sqlite")]
+            BmError::Database { .. } => 5,
+            #[cfg(feature = "encryption")]
+            BmError::Encryption { .. } => 5,
+            BmError::Cancelled => 130,
+            _ => 1,
+        }
+    }
+
+    /// Whether there is nothing to report beyond the exit status, as the
+    /// user chose it.
+    pub fn is_silent(&self) -> bool {
+        matches!(self, BmError::Cancelled | BmError::NoSelection)
+    }
+
+    /// The message in Japanese. What other programs and the system report
+    /// is passed through as is.
+    fn fmt_ja(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            BmError::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+            BmError::Serialize(message) => write!(f, "ブックマークを書き出せませんでした: {message}"),
+            #[cfg(feature = "sqlite")]
+            BmError::Database { path, message } => write!(f, "{}: {}", path.display(), message),
+            #[cfg(feature = "encryption")]
+            BmError::Encryption { path, message } => write!(f, "{}: 暗号化のエラー: {}", path.display(), message),
+            BmError::Unsupported(message) => write!(f, "{message}"),
+            BmError::NoHome => write!(f, "ホームディレクトリがわかりません。$HOME を設定してください"),
+            BmError::UnknownBookmark(name) => write!(f, "`{name}` という名前のブックマークはありません"),
+            BmError::NoMatch(query) => write!(f, "`{query}` に一致するブックマークはありません"),
+            BmError::UnsetVariable { path, var } => write!(f, "{path}: ${var} が設定されていません"),
+            BmError::InvalidProfile(name) => write!(f, "プロファイル名 `{name}` は使えません"),
+            BmError::NoCommand(name) => {
+                write!(f, "ブックマーク `{name}` にはコマンドがありません。ブックマークファイルで `command` を設定してください")
+            }
+            BmError::Launch { command, message } => write!(f, "`{command}`: {message}"),
+            BmError::Hook { hook, message } => write!(f, "{hook} フック: {message}"),
+            BmError::Tmux(message) => write!(f, "tmux: {message}"),
+            BmError::Git(message) => write!(f, "git: {message}"),
+            BmError::ReadOnly(path) => write!(f, "{}: 読み取り専用です (--read-only または BM_READ_ONLY)", path.display()),
+            BmError::Terminal(e) => write!(f, "端末のエラー: {e}"),
+            BmError::Cancelled => write!(f, "キャンセルしました"),
+            BmError::NoSelection => write!(f, "ブックマークが選ばれませんでした"),
+        }
+    }
+}
+
+impl fmt::Display for BmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if i18n::language() == Language::Ja {
+            return self.fmt_ja(f);
+        }
+        match self {
+            BmError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            BmError::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+            BmError::Serialize(message) => write!(f, "could not serialize bookmarks: {message}"),
+            #[cfg(feature = "sqlite")]
+            BmError::Database { path, message } => write!(f, "{}: {}", path.display(), message),
+            #[cfg(feature = "encryption")]
+            BmError::Encryption { path, message } => write!(f, "{}: encryption: {}", path.display(), message),
+            BmError::Unsupported(message) => write!(f, "{message}"),
+            BmError::NoHome => write!(f, "could not determine the home directory; set $HOME"),
+            BmError::UnknownBookmark(name) => write!(f, "no bookmark named `{name}`"),
+            BmError::NoMatch(query) => write!(f, "no bookmark matches `{query}`"),
+            BmError::UnsetVariable { path, var } => write!(f, "{path}: ${var} is not set"),
+            BmError::InvalidProfile(name) => write!(f, "invalid profile name `{name}`"),
+            BmError::NoCommand(name) => write!(f, "bookmark `{name}` has no command; set `command` for it in the bookmarks file"),
+            BmError::Launch { command, message } => write!(f, "`{command}`: {message}"),
+            BmError::Hook { hook, message } => write!(f, "{hook} hook: {message}"),
+            BmError::Tmux(message) => write!(f, "tmux: {message}"),
+            BmError::Git(message) => write!(f, "git: {message}"),
+            BmError::ReadOnly(path) => write!(f, "{}: read-only (--read-only or BM_READ_ONLY is set)", path.display()),
+            BmError::Terminal(e) => write!(f, "terminal error: {e}"),
+            BmError::Cancelled => write!(f, "cancelled"),
+            BmError::NoSelection => write!(f, "no bookmark was picked"),
+        }
+    }
+}
+
+impl std::error::Error for BmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BmError::Io { source, .. } | BmError::Terminal(source) => Some(source),
+            _ => None,
+        }
+    }
+}
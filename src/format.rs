@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
+
+use crate::csv;
+use crate::error::{BmError, Result};
+use crate::migrate::{self, SCHEMA_VERSION, VersionProbe};
+use crate::paths::expand_tilde;
+use crate::store::{Bookmark, BookmarkFile, Kind};
+
+/// Serialization used for a bookmarks file, chosen from its extension.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+    /// One line per bookmark with every field, for spreadsheets.
+    Csv,
+}
+
+impl Format {
+    /// The format for `path`'s extension; anything unrecognised is TOML.
+    pub fn from_path(path: &Path) -> Format {
+        Format::from_extension(path).unwrap_or(Format::Toml)
+    }
+
+    /// The format for `path`'s extension, if it names one.
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    /// Parses `content` read from `path`, migrating it from an older schema
+    /// version if needed and expanding `~` in the paths; the error message
+    /// carries the line and column of the problem.
+    pub fn parse(self, path: &Path, content: &str) -> Result<BookmarkFile> {
+        let mut file = self.parse_file(path, content)?;
+        for b in &mut file.bookmarks {
+            b.path = expand_tilde(&b.path);
+        }
+        Ok(file)
+    }
+
+    fn parse_file(self, path: &Path, content: &str) -> Result<BookmarkFile> {
+        let err = |message: String| BmError::Parse { path: path.to_path_buf(), message };
+        // An empty YAML or JSON document is not an empty mapping.
+        if content.trim().is_empty() {
+            return Ok(BookmarkFile::new(Vec::new()));
+        }
+        // CSV has no room for a version and is always read as the current one.
+        if self == Format::Csv {
+            return csv::parse(content).map(BookmarkFile::new).map_err(err);
+        }
+        let version = self.decode::<VersionProbe>(content).map_err(err)?.version;
+        if version == SCHEMA_VERSION {
+            return self.decode(content).map_err(err);
+        }
+        if version > SCHEMA_VERSION {
+            return Err(err(format!(
+                "schema version {version} is newer than this bm supports ({SCHEMA_VERSION}); upgrade bm"
+            )));
+        }
+        let mut value: serde_json::Value = self.decode(content).map_err(err)?;
+        migrate::migrate(&mut value, version).map_err(err)?;
+        let mut file: BookmarkFile = serde_json::from_value(value).map_err(|e| err(e.to_string()))?;
+        file.migrated_from = Some(version);
+        Ok(file)
+    }
+
+    fn decode<T: DeserializeOwned>(self, content: &str) -> Result<T, String> {
+        match self {
+            Format::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            Format::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            Format::Csv => unreachable!("CSV is parsed by parse_file"),
+        }
+    }
+
+    pub fn serialize(self, file: &BookmarkFile) -> Result<String> {
+        let data = match self {
+            Format::Toml => toml::to_string(file).map_err(|e| e.to_string()),
+            Format::Json => serde_json::to_string_pretty(file)
+                .map(|s| s + "\n")
+                .map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::to_string(file).map_err(|e| e.to_string()),
+            Format::Csv => Ok(csv::write(&file.bookmarks)),
+        };
+        data.map_err(BmError::Serialize)
+    }
+}
+
+/// Rewrites the `[[bookmarks]]` tables of `previous` to hold `bookmarks`,
+/// keeping comments, key order and formatting of every entry that is still
+/// there and only touching the values that changed. Returns `None` when
+/// `previous` is not in a shape that can be updated in place.
+pub(crate) fn update_toml(previous: &str, bookmarks: &[Bookmark]) -> Option<String> {
+    let mut doc: DocumentMut = previous.parse().ok()?;
+    if doc.get("version").and_then(Item::as_integer) != Some(i64::from(SCHEMA_VERSION)) {
+        doc.insert("version", toml_edit::value(i64::from(SCHEMA_VERSION)));
+    }
+    let mut old = match doc.remove("bookmarks") {
+        Some(Item::ArrayOfTables(tables)) => tables.into_iter().map(Some).collect(),
+        None => Vec::new(),
+        Some(_) => return None,
+    };
+    let mut tables = ArrayOfTables::new();
+    for b in bookmarks {
+        // Entries are matched on the path, like the merge does; `~` and
+        // the home directory it stands for are the same path.
+        let path = expand_tilde(&b.path);
+        let existing = old.iter_mut().find(|t: &&mut Option<Table>| {
+            t.as_ref().and_then(|t| t.get("path")?.as_str()).map(expand_tilde) == Some(path.clone())
+        });
+        let mut table = existing.and_then(Option::take).unwrap_or_default();
+        set_str(&mut table, "name", &b.name);
+        set_str(&mut table, "path", &b.path);
+        match b.kind {
+            Kind::Dir => {
+                table.remove("kind");
+            }
+            kind => set_str(&mut table, "kind", kind.as_str()),
+        }
+        if b.note.is_empty() {
+            table.remove("note");
+        } else {
+            set_str(&mut table, "note", &b.note);
+        }
+        set_strings(&mut table, "tags", &b.tags);
+        set_int(&mut table, "added", b.added);
+        set_int(&mut table, "last_used", b.last_used);
+        set_int(&mut table, "hits", Some(b.hits).filter(|&n| n > 0));
+        set_strings(&mut table, "workspace", &b.workspace);
+        match &b.command {
+            Some(command) => set_str(&mut table, "command", command),
+            None => {
+                table.remove("command");
+            }
+        }
+        tables.push(table);
+    }
+    doc.insert("bookmarks", Item::ArrayOfTables(tables));
+    Some(doc.to_string())
+}
+
+/// Sets `key` to an array of `values` unless it already holds exactly these
+/// strings, keeping the comments around the old value. An empty list
+/// removes the key.
+fn set_strings(table: &mut Table, key: &str, values: &[String]) {
+    if values.is_empty() {
+        table.remove(key);
+        return;
+    }
+    let old = table.get(key).and_then(Item::as_value);
+    let current = old.and_then(Value::as_array).map(|a| a.iter().map(Value::as_str).collect::<Vec<_>>());
+    if current.is_some_and(|c| c.iter().copied().eq(values.iter().map(|v| Some(v.as_str())))) {
+        return;
+    }
+    let mut new = Value::Array(values.iter().map(String::as_str).collect());
+    if let Some(old) = old {
+        *new.decor_mut() = old.decor().clone();
+    }
+    replace_value(table, key, new);
+}
+
+/// Sets `key` to `value`, or removes it for `None`, leaving it alone when it
+/// already holds that number.
+fn set_int(table: &mut Table, key: &str, value: Option<u64>) {
+    match value {
+        Some(n) if table.get(key).and_then(Item::as_integer) != Some(n as i64) => {
+            replace_value(table, key, Value::from(n as i64));
+        }
+        Some(_) => {}
+        None => {
+            table.remove(key);
+        }
+    }
+}
+
+/// Sets `key` to `value` unless it already holds it, keeping the comments
+/// and spacing around the old value.
+fn set_str(table: &mut Table, key: &str, value: &str) {
+    let old = table.get(key).and_then(Item::as_value);
+    if old.and_then(Value::as_str) == Some(value) {
+        return;
+    }
+    let mut new = Value::from(value);
+    if let Some(old) = old {
+        *new.decor_mut() = old.decor().clone();
+    }
+    replace_value(table, key, new);
+}
+
+/// Stores `value` under `key`, replacing an existing item in place so the
+/// key keeps its own formatting.
+fn replace_value(table: &mut Table, key: &str, value: Value) {
+    match table.get_mut(key) {
+        Some(item) => *item = Item::Value(value),
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
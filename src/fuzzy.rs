@@ -0,0 +1,79 @@
+use crate::store::Bookmark;
+
+/// Score for every matched character.
+const MATCH: i64 = 16;
+/// Extra score for a character right after the previous match.
+const CONSECUTIVE: i64 = 16;
+/// Extra score for a character that starts a path component or a word.
+const WORD_START: i64 = 24;
+/// Extra score for a character in the last path component.
+const LAST_COMPONENT: i64 = 8;
+/// Cost of each skipped character between two matches, up to `MAX_GAP`.
+const GAP: i64 = 1;
+const MAX_GAP: i64 = 16;
+
+/// How well `query` matches `text` as a subsequence, ignoring case, or
+/// `None` if some query character can't be found in order. Matches that
+/// start path components or words, run together and fall in the last
+/// component score higher.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let last_component = text.iter().rposition(|&c| c == '/').map_or(0, |slash| slash + 1);
+    let bonus = |j: usize| {
+        let start = j == 0 || matches!(text[j - 1], '/' | '-' | '_' | '.' | ' ');
+        let mut bonus = if start { WORD_START } else { 0 };
+        if j >= last_component {
+            bonus += LAST_COMPONENT;
+        }
+        bonus
+    };
+    // best[j]: the best score of the query so far with its last character
+    // matched at text[j].
+    let mut best: Vec<Option<i64>> = text
+        .iter()
+        .enumerate()
+        .map(|(j, &c)| (c == query[0]).then(|| MATCH + bonus(j)))
+        .collect();
+    for &q in &query[1..] {
+        let mut next = vec![None; text.len()];
+        for (j, &c) in text.iter().enumerate() {
+            if c != q {
+                continue;
+            }
+            let from = (0..j)
+                .filter_map(|k| {
+                    let prev = best[k]?;
+                    let gap = (j - k - 1) as i64;
+                    Some(if gap == 0 { prev + CONSECUTIVE } else { prev - (gap * GAP).min(MAX_GAP) })
+                })
+                .max();
+            next[j] = from.map(|s| s + MATCH + bonus(j));
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// A bookmark's score for a space-separated query: every word has to match
+/// its name or its path, and the better of the two counts.
+pub fn bookmark_score(query: &str, b: &Bookmark) -> Option<i64> {
+    query
+        .split_whitespace()
+        .map(|word| score(word, &b.name).max(score(word, &b.path)))
+        .sum()
+}
+
+/// Index of the bookmark matching `query` best. Ties go to the one used
+/// more often, then to the one listed first.
+pub fn best_match(query: &str, bookmarks: &[Bookmark]) -> Option<usize> {
+    bookmarks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| Some((bookmark_score(query, b)?, b.hits, std::cmp::Reverse(i))))
+        .max()
+        .map(|(_, _, std::cmp::Reverse(i))| i)
+}
@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{BmError, Result};
+use crate::paths::{data_dir, same_path};
+use crate::store::write_atomic;
+
+/// Name of the jump history inside the data directory.
+const HISTORY_FILE: &str = "jumps";
+
+/// Number of directories the history remembers.
+const HISTORY_LEN: usize = 50;
+
+/// The jump history shared by every bookmarks file and profile.
+pub fn history_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join(HISTORY_FILE))
+}
+
+/// Directories jumped to, most recent first and each listed once. A
+/// history that doesn't exist yet is empty.
+pub fn load() -> Result<Vec<String>> {
+    read_lines(&history_path()?)
+}
+
+/// The non-empty lines of `path`; a file that doesn't exist has none.
+pub(crate) fn read_lines(path: &Path) -> Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.lines().filter(|l| !l.is_empty()).map(str::to_string).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(BmError::io(path, e)),
+    }
+}
+
+/// Replaces `path` with one line per entry of `lines`.
+pub(crate) fn write_lines(path: &Path, lines: &[String]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| BmError::io(parent, e))?;
+    }
+    let content: String = lines.iter().map(|l| format!("{l}\n")).collect();
+    write_atomic(path, content.as_bytes())
+}
+
+/// Moves `dir` to the front of the history, dropping the oldest entries
+/// beyond `HISTORY_LEN`.
+pub fn record(dir: &str) -> Result<()> {
+    let mut dirs = load()?;
+    dirs.retain(|d| !same_path(d, dir));
+    dirs.insert(0, dir.to_string());
+    dirs.truncate(HISTORY_LEN);
+    write_lines(&history_path()?, &dirs)
+}
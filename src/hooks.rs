@@ -0,0 +1,117 @@
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::error::{BmError, Result};
+use crate::journal::Change;
+use crate::paths::expand_vars;
+
+/// Shell commands from the `[hooks]` table of `config.toml`, run with
+/// `BM_HOOK`, `BM_NAME` and `BM_PATH` set.
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Hooks {
+    /// Before bm prints the directory to jump to.
+    pub pre_jump: Option<String>,
+    /// After a bookmark was added and saved.
+    pub post_add: Option<String>,
+    /// After a bookmark was removed or archived and saved.
+    pub post_delete: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Event {
+    PreJump,
+    PostAdd,
+    PostDelete,
+}
+
+impl Event {
+    fn as_str(self) -> &'static str {
+        match self {
+            Event::PreJump => "pre_jump",
+            Event::PostAdd => "post_add",
+            Event::PostDelete => "post_delete",
+        }
+    }
+}
+
+/// Where a hook's output goes, besides the log. Stdout is never passed
+/// through, as it carries the path the shell changes to.
+#[derive(Clone, Copy)]
+pub enum Output {
+    Stderr,
+    /// Dropped, for hooks run while the UI owns the terminal.
+    Discard,
+}
+
+/// `command` run by the platform's shell: `sh -c`, or `cmd /C` on Windows.
+pub fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let (program, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (program, flag) = ("sh", "-c");
+    let mut cmd = Command::new(program);
+    cmd.arg(flag).arg(command);
+    cmd
+}
+
+impl Hooks {
+    fn command(&self, event: Event) -> Option<&str> {
+        match event {
+            Event::PreJump => self.pre_jump.as_deref(),
+            Event::PostAdd => self.post_add.as_deref(),
+            Event::PostDelete => self.post_delete.as_deref(),
+        }
+        .filter(|c| !c.trim().is_empty())
+    }
+
+    /// Runs the hook for `event`, if one is set, and waits for it. `path`
+    /// is passed with its variables expanded.
+    pub fn run(&self, event: Event, name: &str, path: &str, output: Output) -> Result<()> {
+        let Some(command) = self.command(event) else {
+            return Ok(());
+        };
+        let err = |message: String| BmError::Hook { hook: event.as_str(), message };
+        tracing::info!(hook = event.as_str(), name, "running `{command}`");
+        // Captured rather than passed through, so the log has it too.
+        let ran = shell(command)
+            .env("BM_HOOK", event.as_str())
+            .env("BM_NAME", name)
+            .env("BM_PATH", expand_vars(path).unwrap_or_else(|_| path.to_string()))
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| err(e.to_string()))?;
+        for printed in [&ran.stdout, &ran.stderr] {
+            let printed = String::from_utf8_lossy(printed);
+            for line in printed.lines() {
+                tracing::info!(hook = event.as_str(), "{line}");
+            }
+            if let Output::Stderr = output {
+                eprint!("{printed}");
+            }
+        }
+        if !ran.status.success() {
+            tracing::warn!(hook = event.as_str(), "`{command}` failed ({})", ran.status);
+            return Err(err(format!("`{command}` failed ({})", ran.status)));
+        }
+        Ok(())
+    }
+
+    /// Runs `post_add` for every added bookmark and `post_delete` for every
+    /// removed one. All of them run; the first failure is returned.
+    pub fn run_for_changes(&self, changes: &[Change], output: Output) -> Result<()> {
+        let mut result = Ok(());
+        for change in changes {
+            let ran = match change {
+                Change::Added { name, path } => self.run(Event::PostAdd, name, path, output),
+                Change::Removed { name, path } => self.run(Event::PostDelete, name, path, output),
+                Change::Renamed { .. } => Ok(()),
+            };
+            if result.is_ok() {
+                result = ran;
+            }
+        }
+        result
+    }
+}
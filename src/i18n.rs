@@ -0,0 +1,180 @@
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// Languages the UI's help, confirmations and error messages exist in.
+/// Messages not listed in `Text` stay in English.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    En,
+    Ja,
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Uses `language` from now on instead of the locale's. Only the first call
+/// counts, so it has to come before the first message is looked up.
+pub fn set(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+/// The language messages are shown in: the one `set`, or else the one the
+/// locale asks for, as in `LANG=ja_JP.UTF-8`.
+pub fn language() -> Language {
+    *LANGUAGE.get_or_init(|| {
+        // The first of these that is set wins, like gettext does it.
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.starts_with("ja") { Language::Ja } else { Language::En }
+    })
+}
+
+/// A fixed piece of UI text.
+#[derive(Clone, Copy)]
+pub enum Text {
+    HelpNormal,
+    HelpHint,
+    HelpStats,
+    HelpArchive,
+    HelpBrowse,
+    HelpFileAction,
+    HelpNote,
+    HelpDedup,
+    HelpAddPath,
+    HelpNewDir,
+    HelpRetry,
+    HelpDismiss,
+    HelpPalette,
+    LoadFailedRestore,
+    LoadFailedReadOnly,
+    LoadFailedQuit,
+    ConfirmArchive,
+    ConfirmQuit,
+    TitleBookmarks,
+    TitleConfirm,
+    TitleError,
+    TitleStats,
+    TitleArchive,
+    TitleBrowse,
+    TitlePalette,
+    ColumnName,
+    ColumnPath,
+    ColumnTags,
+    ColumnLastUsed,
+    ColumnHits,
+}
+
+impl Text {
+    pub fn get(self) -> &'static str {
+        match language() {
+            Language::En => self.en(),
+            Language::Ja => self.ja(),
+        }
+    }
+
+    fn en(self) -> &'static str {
+        match self {
+            Text::HelpNormal => {
+                "j/k: move  f: jump  /n/N: search  u/U/a/c/P: add  b: browse  +/z: suggested  s/S/F: sort/stats/filter  e/E: note  Space/y/Y: mark/copy/print  !/A: archive  p: profile  Enter/x/T: select/run/tmux  :/Ctrl-P: command/actions  q: quit"
+            }
+            Text::HelpHint => "type a label to jump  Esc: cancel",
+            Text::HelpStats => "j/k: scroll  any other key: back",
+            Text::HelpArchive => "j/k: move  r/Enter: restore  Esc: back",
+            Text::HelpBrowse => "j/k: move  l/Enter: open  h: up  u: bookmark  Esc: back",
+            Text::HelpFileAction => "p/Enter: print path  o: open  e: editor  Esc: cancel",
+            Text::HelpNote => "Note (Enter: save  Esc: cancel)",
+            Text::HelpDedup => {
+                "h/l: column  n/p/o: take name/path/note  a: take all  t: toggle tags  Enter: merge  s: skip  q: quit"
+            }
+            Text::HelpAddPath => "Add directory (Tab: complete  Enter: add  Esc: cancel)",
+            Text::HelpNewDir => "New directory (Tab: complete  Enter: create and add  Esc: cancel)",
+            Text::HelpRetry => "r: retry  i: ignore",
+            Text::HelpDismiss => "Enter: dismiss",
+            Text::HelpPalette => "type to search  Up/Down: move  Enter: run  Esc: cancel",
+            Text::LoadFailedRestore => "r: restore the newest valid backup",
+            Text::LoadFailedReadOnly => "o: continue read-only (the file is not modified)",
+            Text::LoadFailedQuit => "q: quit",
+            Text::ConfirmArchive => "Move this bookmark to the archive? (y/n)",
+            Text::ConfirmQuit => "Some changes aren't saved yet. Save them and quit? (y/n)",
+            Text::TitleBookmarks => "Bookmarks",
+            Text::TitleConfirm => "Confirm",
+            Text::TitleError => "Error",
+            Text::TitleStats => "Statistics",
+            Text::TitleArchive => "Archive",
+            Text::TitleBrowse => "Browse",
+            Text::TitlePalette => "Actions",
+            Text::ColumnName => "Name",
+            Text::ColumnPath => "Path",
+            Text::ColumnTags => "Tags",
+            Text::ColumnLastUsed => "Used",
+            Text::ColumnHits => "Hits",
+        }
+    }
+
+    fn ja(self) -> &'static str {
+        match self {
+            Text::HelpNormal => {
+                "j/k: 移動  f: ジャンプ  /n/N: 検索  u/U/a/c/P: 追加  b: 参照  +/z: 候補  s/S/F: 並べ替え/統計/絞り込み  e/E: メモ  Space/y/Y: 選択/コピー/出力  !/A: アーカイブ  p: プロファイル  Enter/x/T: 選択/実行/tmux  :/Ctrl-P: コマンド/操作  q: 終了"
+            }
+            Text::HelpHint => "ラベルを入力してジャンプ  Esc: キャンセル",
+            Text::HelpStats => "j/k: スクロール  その他のキー: 戻る",
+            Text::HelpArchive => "j/k: 移動  r/Enter: 復元  Esc: 戻る",
+            Text::HelpBrowse => "j/k: 移動  l/Enter: 開く  h: 上へ  u: ブックマーク  Esc: 戻る",
+            Text::HelpFileAction => "p/Enter: パスを出力  o: 開く  e: エディタ  Esc: キャンセル",
+            Text::HelpNote => "メモ (Enter: 保存  Esc: キャンセル)",
+            Text::HelpDedup => {
+                "h/l: 列  n/p/o: 名前/パス/メモを採用  a: すべて採用  t: タグの切り替え  Enter: 統合  s: スキップ  q: 終了"
+            }
+            Text::HelpAddPath => "ディレクトリを追加 (Tab: 補完  Enter: 追加  Esc: キャンセル)",
+            Text::HelpNewDir => "新しいディレクトリ (Tab: 補完  Enter: 作成して追加  Esc: キャンセル)",
+            Text::HelpRetry => "r: 再試行  i: 無視",
+            Text::HelpDismiss => "Enter: 閉じる",
+            Text::HelpPalette => "入力して検索  Up/Down: 移動  Enter: 実行  Esc: キャンセル",
+            Text::LoadFailedRestore => "r: 最新の有効なバックアップから復元",
+            Text::LoadFailedReadOnly => "o: 読み取り専用で続行 (ファイルは変更されません)",
+            Text::LoadFailedQuit => "q: 終了",
+            Text::ConfirmArchive => "このブックマークをアーカイブに移動しますか? (y/n)",
+            Text::ConfirmQuit => "まだ保存されていない変更があります。保存して終了しますか? (y/n)",
+            Text::TitleBookmarks => "ブックマーク",
+            Text::TitleConfirm => "確認",
+            Text::TitleError => "エラー",
+            Text::TitleStats => "統計",
+            Text::TitleArchive => "アーカイブ",
+            Text::TitleBrowse => "参照",
+            Text::TitlePalette => "操作",
+            Text::ColumnName => "名前",
+            Text::ColumnPath => "パス",
+            Text::ColumnTags => "タグ",
+            Text::ColumnLastUsed => "使用",
+            Text::ColumnHits => "回数",
+        }
+    }
+}
+
+/// The question asked before creating a directory that doesn't exist.
+pub fn confirm_create(path: &str) -> String {
+    match language() {
+        Language::En => format!("{path} does not exist. Create it? (y/n)"),
+        Language::Ja => format!("{path} は存在しません。作成しますか? (y/n)"),
+    }
+}
+
+/// The question asked before archiving more than one bookmark.
+pub fn confirm_archive_many(count: usize) -> String {
+    match language() {
+        Language::En => format!("Move these {count} bookmarks to the archive? (y/n)"),
+        Language::Ja => format!("これら {count} 件のブックマークをアーカイブに移動しますか? (y/n)"),
+    }
+}
+
+/// The heading of the dialog shown when the bookmarks file can't be read.
+pub fn could_not_load(path: &str) -> String {
+    match language() {
+        Language::En => format!("Could not load {path}"),
+        Language::Ja => format!("{path} を読み込めませんでした"),
+    }
+}
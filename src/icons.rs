@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Icons;
+use crate::store::Kind;
+
+/// What a bookmarked directory holds, going by the files at its top.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectType {
+    Rust,
+    Node,
+    Go,
+    /// A git checkout that isn't one of the above.
+    Git,
+}
+
+impl ProjectType {
+    /// Every type, in the order the list groups them.
+    pub const ALL: [ProjectType; 4] = [ProjectType::Rust, ProjectType::Node, ProjectType::Go, ProjectType::Git];
+
+    /// How the UI names a group of these.
+    pub fn label(self) -> &'static str {
+        match self {
+            ProjectType::Rust => "Rust projects",
+            ProjectType::Node => "Node projects",
+            ProjectType::Go => "Go projects",
+            ProjectType::Git => "other git checkouts",
+        }
+    }
+}
+
+/// Files that mark each project type, most specific first: a Rust crate
+/// is usually a git checkout too.
+const MARKERS: [(&str, ProjectType); 4] = [
+    ("Cargo.toml", ProjectType::Rust),
+    ("package.json", ProjectType::Node),
+    ("go.mod", ProjectType::Go),
+    (".git", ProjectType::Git),
+];
+
+/// The project type of `dir`, if it has one of the marker files.
+pub fn detect(dir: &Path) -> Option<ProjectType> {
+    MARKERS.iter().find(|(file, _)| dir.join(file).exists()).map(|&(_, project)| project)
+}
+
+/// The icon shown before a bookmark's path, and its color. Nerd Font
+/// glyphs mark every kind of bookmark; in ASCII only projects get a
+/// badge. `None` when there is nothing to show.
+pub fn icon(style: Icons, kind: Kind, project: Option<ProjectType>) -> Option<(&'static str, Color)> {
+    let icon = match (style, kind, project) {
+        (Icons::Off, _, _) => return None,
+        (Icons::Nerd, Kind::Dir, Some(ProjectType::Rust)) => ("\u{e7a8}", Color::LightRed),
+        (Icons::Nerd, Kind::Dir, Some(ProjectType::Node)) => ("\u{e718}", Color::Green),
+        (Icons::Nerd, Kind::Dir, Some(ProjectType::Go)) => ("\u{e627}", Color::Cyan),
+        (Icons::Nerd, Kind::Dir, Some(ProjectType::Git)) => ("\u{e702}", Color::Red),
+        (Icons::Nerd, Kind::Dir, None) => ("\u{f07b}", Color::Blue),
+        (Icons::Nerd, Kind::File, _) => ("\u{f15b}", Color::Gray),
+        (Icons::Nerd, Kind::Remote, _) => ("\u{f233}", Color::Magenta),
+        (Icons::Ascii, Kind::Dir, Some(ProjectType::Rust)) => ("[rs]", Color::LightRed),
+        (Icons::Ascii, Kind::Dir, Some(ProjectType::Node)) => ("[js]", Color::Green),
+        (Icons::Ascii, Kind::Dir, Some(ProjectType::Go)) => ("[go]", Color::Cyan),
+        (Icons::Ascii, Kind::Dir, Some(ProjectType::Git)) => ("[git]", Color::Red),
+        (Icons::Ascii, _, _) => return None,
+    };
+    Some(icon)
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::{BmError, Result};
+use crate::paths::data_dir;
+use crate::store::{Bookmark, unix_now, utc_timestamp};
+
+/// Name of the change log inside the data directory.
+const JOURNAL_FILE: &str = "history.log";
+
+/// Which part of bm made a change.
+#[derive(Clone, Copy)]
+pub enum Source {
+    Tui,
+    Cli,
+    /// An editor plugin, through `bm serve`.
+    Rpc,
+}
+
+impl Source {
+    fn as_str(self) -> &'static str {
+        match self {
+            Source::Tui => "tui",
+            Source::Cli => "cli",
+            Source::Rpc => "rpc",
+        }
+    }
+}
+
+/// One change to the list of bookmarks.
+pub enum Change {
+    Added { name: String, path: String },
+    Removed { name: String, path: String },
+    /// The bookmark on `path` was called `from` before.
+    Renamed { from: String, name: String, path: String },
+}
+
+/// What changed between `before` and `after`; bookmarks are matched on the
+/// path, and edits to anything but the name are not recorded.
+pub fn changes(before: &[Bookmark], after: &[Bookmark]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for b in after {
+        match before.iter().find(|old| old.path == b.path) {
+            None => changes.push(Change::Added { name: b.name.clone(), path: b.path.clone() }),
+            Some(old) if old.name != b.name => changes.push(Change::Renamed {
+                from: old.name.clone(),
+                name: b.name.clone(),
+                path: b.path.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for old in before {
+        if !after.iter().any(|b| b.path == old.path) {
+            changes.push(Change::Removed { name: old.name.clone(), path: old.path.clone() });
+        }
+    }
+    changes
+}
+
+/// The change log shared by every bookmarks file and profile.
+pub fn journal_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join(JOURNAL_FILE))
+}
+
+/// Appends `changes` to the change log as tab-separated lines of time,
+/// source, action, name, path and the bookmarks file they were made to.
+pub fn record(source: Source, file: &Path, changes: &[Change]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let path = journal_path()?;
+    let time = utc_timestamp(unix_now());
+    let mut lines = String::new();
+    for change in changes {
+        let (action, name, target) = match change {
+            Change::Added { name, path } => ("added".to_string(), name, path),
+            Change::Removed { name, path } => ("removed".to_string(), name, path),
+            Change::Renamed { from, name, path } => (format!("renamed from {from}"), name, path),
+        };
+        lines.push_str(&format!(
+            "{time}\t{}\t{action}\t{name}\t{target}\t{}\n",
+            source.as_str(),
+            file.display()
+        ));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| BmError::io(dir, e))?;
+    }
+    let mut log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| BmError::io(&path, e))?;
+    // One write per batch, so lines from concurrent instances don't interleave.
+    log.write_all(lines.as_bytes()).map_err(|e| BmError::io(&path, e))
+}
+
+/// The last `limit` lines of the change log (all of them without a limit),
+/// oldest first. A log that doesn't exist yet is empty.
+pub fn read(limit: Option<usize>) -> Result<Vec<String>> {
+    let path = journal_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(BmError::io(&path, e)),
+    };
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let skip = limit.map_or(0, |n| lines.len().saturating_sub(n));
+    Ok(lines.into_iter().skip(skip).collect())
+}
@@ -0,0 +1,85 @@
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{BmError, Result};
+use crate::hooks::shell;
+use crate::store::{Bookmark, Kind};
+
+/// Stdout for a program that takes over the terminal, such as an editor or
+/// ssh: the terminal itself when bm's stdout is captured, as in `cd "$(bm)"`,
+/// so what the program prints is seen rather than taken for the path.
+pub fn terminal_stdout() -> Stdio {
+    if !io::stdout().is_terminal()
+        && let Ok(tty) = OpenOptions::new().write(true).open("/dev/tty")
+    {
+        return tty.into();
+    }
+    Stdio::inherit()
+}
+
+/// Runs the bookmark's `command` in `dir`, which is also what `{path}`
+/// stands for, on this terminal and waits for it to finish. `BM_NAME` and
+/// `BM_PATH` are set for commands that need the path quoted. Commands of
+/// file bookmarks run in the file's directory, those of remote ones in the
+/// current directory.
+pub fn run_command(b: &Bookmark, dir: &str) -> Result<()> {
+    let command = b.command_line(dir).ok_or_else(|| BmError::NoCommand(b.name.clone()))?;
+    let cwd = match b.kind {
+        Kind::Dir => Path::new(dir),
+        Kind::File => Path::new(dir).parent().unwrap_or(Path::new(dir)),
+        Kind::Remote => Path::new("."),
+    };
+    let err = |message: String| BmError::Launch { command: command.clone(), message };
+    let status = shell(&command)
+        .current_dir(cwd)
+        .env("BM_NAME", &b.name)
+        .env("BM_PATH", dir)
+        .stdout(terminal_stdout())
+        .status()
+        .map_err(|e| err(e.to_string()))?;
+    if !status.success() {
+        return Err(err(format!("failed ({status})")));
+    }
+    Ok(())
+}
+
+/// The program that opens a file with its default application.
+#[cfg(target_os = "macos")]
+fn opener() -> Command {
+    Command::new("open")
+}
+
+#[cfg(windows)]
+fn opener() -> Command {
+    let mut cmd = Command::new("cmd");
+    // `start` takes the first quoted argument as the window title.
+    cmd.args(["/C", "start", ""]);
+    cmd
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn opener() -> Command {
+    Command::new("xdg-open")
+}
+
+/// Opens `path` with the desktop's default application for it: `xdg-open`,
+/// `open` on macOS or `start` on Windows. Returns once the opener has
+/// handed the file on.
+pub fn open_file(path: &str) -> Result<()> {
+    let mut cmd = opener();
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let err = |message: String| BmError::Launch { command: format!("{program} {path}"), message };
+    let status = cmd
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| err(e.to_string()))?;
+    if !status.success() {
+        return Err(err(format!("failed ({status})")));
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! The bookmark engine behind the `bm` binary: the bookmark model, storage
+//! backends with locking, merging and backups, file formats, profiles and
+//! the terminal UI. Other tools can load and save bm's bookmarks through
+//! [`store::open`] without running the UI.
+
+pub mod add;
+#[cfg(feature = "encryption")]
+pub mod age;
+pub mod archive;
+pub mod clipboard;
+pub mod complete;
+pub mod config;
+pub mod csv;
+#[cfg(unix)]
+pub mod daemon;
+pub mod dedup;
+pub mod doctor;
+pub mod editor;
+pub mod error;
+pub mod format;
+pub mod fuzzy;
+pub mod history;
+pub mod hooks;
+pub mod i18n;
+pub mod icons;
+pub mod journal;
+pub mod launch;
+pub mod logging;
+pub mod menu;
+pub mod migrate;
+pub mod paths;
+pub mod probe;
+pub mod profile;
+pub mod project;
+pub mod rank;
+pub mod rpc;
+pub mod remote;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod shell;
+pub mod stack;
+pub mod stats;
+pub mod store;
+pub mod sync;
+pub mod tmux;
+pub mod tui;
+pub mod visits;
+pub mod width;
+pub mod wsl;
+
+pub use error::{BmError, Result};
+pub use store::{Bookmark, BookmarkStore};
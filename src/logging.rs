@@ -0,0 +1,79 @@
+use std::fs;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Layer, fmt};
+
+use crate::paths::data_dir;
+
+/// Name of the directory of log files inside the data directory.
+const LOGS_DIR: &str = "logs";
+
+/// Number of daily log files kept; older ones are deleted.
+const LOG_FILES: usize = 7;
+
+/// Variable holding an `EnvFilter` directive such as `bm=debug`, for both
+/// the log file and `--verbose`.
+const FILTER_VAR: &str = "BM_LOG";
+
+/// Set while the UI owns the terminal, so `--verbose` output doesn't draw
+/// over it. It goes to the log file all the same.
+static STDERR_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Stderr, or nowhere while it is paused.
+struct Stderr;
+
+impl<'a> MakeWriter<'a> for Stderr {
+    type Writer = Box<dyn Write>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        if STDERR_PAUSED.load(Ordering::SeqCst) {
+            Box::new(io::sink())
+        } else {
+            Box::new(io::stderr())
+        }
+    }
+}
+
+/// The filter `BM_LOG` sets, or `fallback` if it's unset or invalid.
+fn filter(fallback: &str) -> EnvFilter {
+    EnvFilter::try_from_env(FILTER_VAR).unwrap_or_else(|_| EnvFilter::new(fallback))
+}
+
+/// Logs warnings, errors and hook runs to a file a day in `logs` in the data
+/// directory, and with `verbose` set also to stderr: informational messages
+/// once, debug messages twice, everything three times. A log directory that
+/// can't be created only leaves the file out.
+pub fn init(verbose: u8) {
+    let file = data_dir()
+        .ok()
+        .and_then(|dir| {
+            let dir = dir.join(LOGS_DIR);
+            fs::create_dir_all(&dir).ok()?;
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix("bm")
+                .filename_suffix("log")
+                .max_log_files(LOG_FILES)
+                .build(dir)
+                .ok()
+        })
+        .map(|file| fmt::layer().with_writer(file).with_ansi(false).with_filter(filter("bm=info")));
+    let stderr = (verbose > 0).then(|| {
+        let level = match verbose {
+            1 => "bm=info",
+            2 => "bm=debug",
+            _ => "bm=trace",
+        };
+        fmt::layer().with_writer(Stderr).without_time().with_target(false).with_filter(filter(level))
+    });
+    let _ = tracing_subscriber::registry().with(file).with(stderr).try_init();
+}
+
+/// Stops or resumes `--verbose` output, for the UI to take over the terminal.
+pub fn pause_stderr(paused: bool) {
+    STDERR_PAUSED.store(paused, Ordering::SeqCst);
+}
@@ -1,215 +1,1289 @@
 use std::fs;
-use std::io;
-use std::path::PathBuf;
-use std::error::Error;
-
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    execute,
-};
-use dirs::home_dir;
-use ratatui::{
-    backend::CrosstermBackend,
-    layout::*,
-    style::*,
-    text::Span,
-    widgets::*,
-    Terminal,
-};
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize, Deserialize, Clone)]
-struct Bookmark {
-    name: String,
-    path: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct BookmarkFile {
-    bookmarks: Vec<Bookmark>,
-}
-
-fn get_bookmark_path() -> PathBuf {
-    home_dir().unwrap().join(".bm/bookmarks.toml")
-}
-
-fn load_bookmarks() -> Vec<Bookmark> {
-    let path = get_bookmark_path();
-    if path.exists() {
-        let content = fs::read_to_string(path).unwrap_or_default();
-        toml::from_str::<BookmarkFile>(&content).map(|f| f.bookmarks).unwrap_or_default()
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::{self, EnvCompleter};
+use clap_complete::CompleteEnv;
+use serde::Serialize;
+
+use bm::config::Config;
+use bm::dedup;
+use bm::format::Format;
+use bm::hooks::{Event, Hooks, Output};
+use bm::icons::ProjectType;
+use bm::paths::{self, same_path};
+use bm::rank::Ranker;
+use bm::shell::{self, Shell};
+use bm::stats::Stats;
+use bm::store::{self, parse_bookmarks, write_atomic, BookmarkFile, Kind};
+use bm::add::AddOptions;
+use bm::{add, archive, doctor, editor, fuzzy, history, i18n, journal, launch, logging, menu, probe, profile, remote, rpc, stack, sync, tmux, tui, visits, width, BmError, Bookmark, BookmarkStore, Result};
+
+/// Set by `--quiet`: only results, prompts, warnings and errors are printed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Where the picked path goes, set by `--output` and `--output-fd`; stdout
+/// when unset.
+static PATH_OUTPUT: OnceLock<PathOutput> = OnceLock::new();
+
+enum PathOutput {
+    File(PathBuf),
+    Fd(i32),
+}
+
+/// Prints what bm did to stderr, unless `--quiet` is given.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[derive(Parser)]
+#[command(version, about = "Directory bookmarks with a terminal UI")]
+struct Cli {
+    /// Bookmarks file to use instead of the default one
+    #[arg(long, global = true, env = "BM_FILE", value_name = "PATH")]
+    file: Option<PathBuf>,
+
+    /// Named bookmark set to use, stored as profiles/<NAME>.toml in the data directory
+    #[arg(long, short, global = true, env = "BM_PROFILE", value_name = "NAME", conflicts_with = "file")]
+    profile: Option<String>,
+
+    /// Resolve symlinks in newly bookmarked paths (overrides `canonicalize` in config.toml)
+    #[arg(long, global = true, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    canonicalize: Option<bool>,
+
+    /// Directory that `u` in the UI and a bare `bm add` bookmark, for launches from a keybinding;
+    /// also takes the `file://host/path` URL a terminal reports through OSC 7
+    #[arg(long, global = true, env = "BM_CWD", value_name = "DIR", value_parser = paths::parse_cwd)]
+    cwd: Option<PathBuf>,
+
+    /// Write the picked path to FILE instead of stdout, replacing what it held
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "output_fd")]
+    output: Option<PathBuf>,
+
+    /// Write the picked path to the already open file descriptor N instead of stdout, as fzf's shell widgets do
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(i32).range(0..))]
+    output_fd: Option<i32>,
+
+    /// Don't report what was done, e.g. `added NAME`; results, warnings and errors are still printed
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print what bm does to stderr: -v for hooks and warnings, -vv to also see saves
+    #[arg(long, short, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Never write the bookmarks file: changes in the UI are refused, and so are commands that edit it
+    #[arg(long, global = true, env = "BM_READ_ONLY", value_parser = clap::builder::BoolishValueParser::new())]
+    read_only: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Open the bookmarks file in $EDITOR and validate it afterwards
+    Edit,
+    /// Write all bookmarks to FILE, or to stdout
+    Export {
+        /// Output file; its extension picks the format unless --format is given
+        #[arg(value_name = "FILE")]
+        output: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+    },
+    /// Add the bookmarks from FILE (`-` for stdin) that are not bookmarked yet
+    Import {
+        #[arg(value_name = "FILE", required_unless_present_any = ["from", "bashmarks"])]
+        input: Option<PathBuf>,
+        /// Format of FILE; defaults to the one its extension names, else TOML
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        /// Read a plain list of directories, one per line, instead of a bookmarks file
+        #[arg(long, value_name = "PATHS_FILE", conflicts_with_all = ["input", "format"])]
+        from: Option<PathBuf>,
+        /// Also replace bookmarks that are already there with FILE's version of them
+        #[arg(long, conflicts_with = "from")]
+        update: bool,
+        /// Read `name=path` lines instead, as in the ~/.sdirs of bashmarks and shellmarks (the default)
+        /// or in alias files
+        #[arg(
+            long,
+            value_name = "SDIRS_FILE",
+            num_args = 0..=1,
+            default_missing_value = "~/.sdirs",
+            conflicts_with_all = ["input", "format", "from", "update"]
+        )]
+        bashmarks: Option<String>,
+    },
+    /// Bookmark directories or files, named after their last path component
+    Add {
+        /// Directories, files or glob patterns (e.g. '~/projects/*'); defaults to the current directory
+        paths: Vec<String>,
+        /// Read paths from stdin, one per line
+        #[arg(long, conflicts_with = "paths")]
+        stdin: bool,
+        /// Tag every added bookmark; may be repeated
+        #[arg(long = "tag", short, value_name = "TAG")]
+        tags: Vec<String>,
+        /// Append -2, -3, ... to names that are already taken instead of skipping those directories
+        #[arg(long)]
+        suffix_names: bool,
+    },
+    /// Print every bookmark
+    List {
+        #[arg(long, value_enum, default_value = "text")]
+        format: ListFormat,
+        /// Only list directories holding this kind of project
+        #[arg(long = "type", value_enum, value_name = "TYPE")]
+        project_type: Option<ProjectType>,
+    },
+    /// Summarize the bookmarks: dead paths, most and least used, tags and recent activity
+    Stats,
+    /// Check the config, the bookmarks file, its paths and permissions and the shell integration, with a fix for each problem
+    Doctor,
+    /// Answer JSON-RPC requests to list, add, remove and resolve bookmarks, for editor plugins
+    Serve {
+        /// Read one request per line from stdin and write the responses to stdout
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+    /// Print the bookmark the current directory is in
+    Status {
+        /// Print only `prompt_format` from config.toml with the name, or nothing outside bookmarks, for a shell prompt
+        #[arg(long)]
+        prompt: bool,
+    },
+    /// Keep the bookmarks in memory and answer `get`, `pick` and `fzf` from there, so they don't read the file
+    Daemon {
+        /// Only print what the running daemon holds
+        #[arg(long)]
+        status: bool,
+    },
+    /// Move bookmarks not used for a number of days to the archive
+    Expire {
+        /// Days without use; defaults to `stale_after_days` in config.toml
+        #[arg(long)]
+        days: Option<u64>,
+        /// Only list what would be archived
+        #[arg(long)]
+        dry_run: bool,
+        /// Archive without asking, even with `prune` set under [confirm] in config.toml
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Print the path of the bookmark called NAME and record the visit
+    Get {
+        /// A bookmark name, or a `NAME<TAB>PATH` line from `bm fzf`
+        #[arg(add = ArgValueCandidates::new(bookmark_names))]
+        name: String,
+    },
+    /// Print the path of the bookmark that fuzzy-matches QUERY best and record the visit
+    Pick {
+        /// Characters to find in order in a bookmark's name or path; every word has to match
+        #[arg(required_unless_present = "tui")]
+        query: Vec<String>,
+        /// Pick from the matches as you type in a bare centered list, for a keybinding that switches directories
+        #[arg(long)]
+        tui: bool,
+    },
+    /// Pick a bookmark in dmenu, rofi or a similar menu and print its path
+    Menu {
+        /// Menu command that reads entries on stdin and prints the chosen one, e.g. "rofi -dmenu -i -p bm"
+        #[arg(long, value_name = "COMMAND", num_args = 0..=1, default_value = menu::DEFAULT_MENU, default_missing_value = menu::DEFAULT_MENU)]
+        dmenu: String,
+    },
+    /// Open a tmux window named after the bookmark NAME in its directory, or switch to it
+    Tmux {
+        #[arg(add = ArgValueCandidates::new(bookmark_names))]
+        name: String,
+        /// Use a session instead of a window (always the case outside tmux)
+        #[arg(long)]
+        session: bool,
+    },
+    /// Run the command set for the bookmark NAME in its directory
+    Run {
+        #[arg(add = ArgValueCandidates::new(bookmark_names))]
+        name: String,
+    },
+    /// Print `NAME<TAB>PATH` lines, best ranked by use first, for piping into fzf
+    Fzf,
+    /// Save the current directory on the stack and print the path of the bookmark called NAME
+    Push {
+        #[arg(add = ArgValueCandidates::new(bookmark_names))]
+        name: String,
+    },
+    /// Remove the directory on top of the stack and print it
+    Pop,
+    /// Print shell functions (bmgo, bmpush, bmpop) that change to the directory bm prints
+    Init {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print a hook that counts every directory the shell changes to, for suggestions in the UI
+    Hook {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Count a visit to DIR; the script `bm hook` prints runs this on every change of directory
+    Track {
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+    /// Print the directory jumped to before the last one (`bm - 2` for the one before that)
+    #[command(name = "-")]
+    Back {
+        #[arg(default_value_t = 1)]
+        steps: usize,
+    },
+    /// Merge bookmarks whose paths only differ by a trailing slash (or by case on macOS and Windows)
+    Dedup {
+        /// Also find bookmarks that reach the same directory through symlinks, and pick what survives each merge
+        #[arg(long, short)]
+        interactive: bool,
+    },
+    /// Merge in OTHER, a diverged copy of the bookmarks file, asking only about bookmarks edited in both
+    Merge {
+        #[arg(value_name = "OTHER")]
+        other: PathBuf,
+    },
+    /// Print a script that sets up tab completion, bookmark names included
+    Completions {
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// Show when bookmarks were added, removed or renamed, oldest first
+    Log {
+        /// Only show the last N changes
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Commit the bookmarks to the git repository they are kept in, merge in the remote's and push
+    Sync {
+        /// Make the directory of the bookmarks file a git repository first; later saves are committed
+        #[arg(long)]
+        init: bool,
+        /// Remote to sync with, added as `origin` by --init
+        #[arg(long, value_name = "URL", requires = "init")]
+        remote: Option<String>,
+    },
+}
+
+/// Output of `bm export`: a bookmarks file, or lines to source from a shell.
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Toml,
+    Json,
+    Yaml,
+    /// Every field of every bookmark, one line each, for spreadsheets
+    Csv,
+    /// `export CDPATH=...` with the parent directory of every bookmark
+    Cdpath,
+    /// `export BM_NAME=...` for every bookmark
+    Env,
+    /// `alias cdNAME='cd ...'` for every bookmark
+    Aliases,
+}
+
+/// Shells `bm completions` can write a completion script for.
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Environment variable the completion scripts set when they ask bm for
+/// candidates.
+const COMPLETE_VAR: &str = "BM_COMPLETE";
+
+/// Bookmark names offered when completing `bm get` and `bm push`. The
+/// bookmarks file is found from `BM_FILE` and `BM_PROFILE` only, as the rest
+/// of the command line hasn't been parsed.
+fn bookmark_names() -> Vec<CompletionCandidate> {
+    let path = match std::env::var_os("BM_FILE") {
+        Some(file) => PathBuf::from(file),
+        None => match profile::profile_path(std::env::var("BM_PROFILE").ok().as_deref()) {
+            Ok(path) => path,
+            Err(_) => return Vec::new(),
+        },
+    };
+    let bookmarks = Config::load()
+        .and_then(|config| store::open(path, &config))
+        .and_then(|store| store.load())
+        .unwrap_or_default();
+    bookmarks
+        .into_iter()
+        .map(|b| CompletionCandidate::new(b.name).help(Some(b.path.into())))
+        .collect()
+}
+
+/// `bm completions`: writes the script that hooks bm into the shell's
+/// completion, which then calls bm back with `BM_COMPLETE` set.
+fn write_completions(shell: CompletionShell) -> Result<()> {
+    let shell: &dyn EnvCompleter = match shell {
+        CompletionShell::Bash => &env::Bash,
+        CompletionShell::Zsh => &env::Zsh,
+        CompletionShell::Fish => &env::Fish,
+        CompletionShell::Powershell => &env::Powershell,
+    };
+    shell
+        .write_registration(COMPLETE_VAR, "bm", "bm", "bm", &mut io::stdout())
+        .map_err(|e| BmError::io(Path::new("<stdout>"), e))
+}
+
+/// Output of `bm list`.
+#[derive(Clone, Copy, ValueEnum)]
+enum ListFormat {
+    /// One `name  path` line per bookmark
+    Text,
+    /// An array of objects with every field, including defaults
+    Json,
+    /// The same fields as a Nushell table literal, for `bm list --format nuon | from nuon`
+    Nuon,
+}
+
+/// A bookmark as `bm list --format json` prints it. Unlike the bookmarks
+/// file, every field is always present, so Nushell reads the list as one
+/// table.
+#[derive(Serialize)]
+struct ListEntry<'a> {
+    name: &'a str,
+    path: &'a str,
+    note: &'a str,
+    tags: &'a [String],
+    added: Option<u64>,
+    last_used: Option<u64>,
+    hits: u64,
+    workspace: &'a [String],
+    command: Option<&'a str>,
+    kind: &'static str,
+}
+
+/// Saves `bookmarks` and records how they differ from `base` in the change
+/// log. A change log that can't be written only gets a warning.
+fn save(store: &dyn BookmarkStore, base: &[Bookmark], bookmarks: &[Bookmark]) -> Result<()> {
+    store.save_merged(base, bookmarks)?;
+    log_changes(store.path(), &journal::changes(base, bookmarks));
+    Ok(())
+}
+
+/// Records `changes` in the change log and runs the add and delete hooks
+/// for them; failures only get a warning.
+fn log_changes(file: &Path, changes: &[journal::Change]) {
+    if changes.is_empty() {
+        return;
+    }
+    if let Err(e) = journal::record(journal::Source::Cli, file, changes) {
+        eprintln!("bm: could not write the change log: {e}");
+    }
+    if let Err(e) = hooks().run_for_changes(changes, Output::Stderr) {
+        eprintln!("bm: {e}");
+    }
+}
+
+/// The hooks from `config.toml`. Commands that don't otherwise need the
+/// config warn about a broken one here instead of failing.
+fn hooks() -> Hooks {
+    Config::load().map(|config| config.hooks).unwrap_or_else(|e| {
+        eprintln!("bm: not running hooks: {e}");
+        Hooks::default()
+    })
+}
+
+/// Runs the `pre_jump` hook for the bookmark `name` in `dir`, warning if it fails.
+fn pre_jump(name: &str, dir: &str) {
+    if let Err(e) = hooks().run(Event::PreJump, name, dir, Output::Stderr) {
+        eprintln!("bm: {e}");
+    }
+}
+
+/// `bm edit`: opens the bookmarks file in the editor and re-parses it on exit,
+/// offering to reopen the editor until the file is valid.
+fn edit_bookmark_file(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| BmError::io(dir, e))?;
+    }
+    let before = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| parse_bookmarks(path, &content).ok())
+        .map(|file| file.bookmarks)
+        .unwrap_or_default();
+    loop {
+        let status = editor::run_editor(path)?;
+        if !status.success() {
+            eprintln!("editor exited with {status}; {} was not checked", path.display());
+            return Ok(());
+        }
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(BmError::io(path, e)),
+        };
+        match parse_bookmarks(path, &content) {
+            Ok(file) => {
+                note!("{}: {} bookmarks OK", path.display(), file.bookmarks.len());
+                log_changes(path, &journal::changes(&before, &file.bookmarks));
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                eprint!("Reopen the editor? [Y/n] ");
+                let mut answer = String::new();
+                io::stdin().read_line(&mut answer).map_err(BmError::Terminal)?;
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    return Err(BmError::Parse {
+                        path: path.to_path_buf(),
+                        message: "left invalid after editing".to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// `bm export`: serializes every bookmark in `format`, defaulting to the one
+/// named by the output file's extension and then to TOML, or writes shell
+/// exports for them.
+fn export_bookmarks(store: &dyn BookmarkStore, file: Option<&Path>, format: Option<ExportFormat>) -> Result<()> {
+    let bookmarks = store.load()?;
+    let format = match format {
+        Some(ExportFormat::Toml) => Format::Toml,
+        Some(ExportFormat::Json) => Format::Json,
+        Some(ExportFormat::Yaml) => Format::Yaml,
+        Some(ExportFormat::Csv) => Format::Csv,
+        Some(ExportFormat::Cdpath) => return write_output(file, &shell::cdpath_export(&bookmarks)),
+        Some(ExportFormat::Env) => return write_output(file, &shell::env_exports(&bookmarks)),
+        Some(ExportFormat::Aliases) => return write_output(file, &shell::alias_definitions(&bookmarks)),
+        None => file.and_then(Format::from_extension).unwrap_or(Format::Toml),
+    };
+    write_output(file, &format.serialize(&BookmarkFile::new(bookmarks))?)
+}
+
+/// Writes `data` to `file`, or to stdout without one.
+fn write_output(file: Option<&Path>, data: &str) -> Result<()> {
+    match file {
+        Some(file) => write_atomic(file, data.as_bytes()),
+        None => io::stdout()
+            .write_all(data.as_bytes())
+            .map_err(|e| BmError::io(Path::new("<stdout>"), e)),
+    }
+}
+
+/// `bm import`: appends the bookmarks from `file` whose path is not already
+/// bookmarked, and with `update` replaces the ones that are.
+fn import_bookmarks(store: &dyn BookmarkStore, file: &Path, format: Option<Format>, update: bool) -> Result<()> {
+    let content = read_input(file)?;
+    let incoming = format
+        .unwrap_or_else(|| Format::from_path(file))
+        .parse(file, &content)?
+        .bookmarks;
+    let base = store.load()?;
+    let mut bookmarks = base.clone();
+    let total = incoming.len();
+    let mut updated = 0;
+    for b in incoming {
+        match bookmarks.iter_mut().find(|existing| same_path(&existing.path, &b.path)) {
+            Some(existing) if update => {
+                if *existing != b {
+                    *existing = b;
+                    updated += 1;
+                }
+            }
+            Some(_) => {}
+            None => bookmarks.push(b),
+        }
+    }
+    let added = bookmarks.len() - base.len();
+    if bookmarks != base {
+        save(store, &base, &bookmarks)?;
+    }
+    if update {
+        note!("imported {added} bookmarks from {} and updated {updated}", file.display());
     } else {
-        Vec::new()
-    }
-}
-
-fn save_bookmarks(bookmarks: &[Bookmark]) {
-    let path = get_bookmark_path();
-    let dir = path.parent().unwrap();
-    fs::create_dir_all(dir).unwrap();
-    let data = toml::to_string(&BookmarkFile { bookmarks: bookmarks.to_vec() }).unwrap();
-    fs::write(path, data).unwrap();
-}
-
-fn run_tui() -> Result<(), Box<dyn Error>> {
-    // Terminal setup
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(
-        stdout,
-        EnterAlternateScreen,
-        crossterm::cursor::Hide
-    )?;
-
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    terminal.clear()?;
-
-    let mut bookmarks = load_bookmarks();
-    let mut selected = 0;
-
-    let result = loop {
-        terminal.draw(|f| {
-            let size = f.area();
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(3),
-                    Constraint::Length(1),
-                ])
-                .split(size);
-
-            let items: Vec<ListItem> = bookmarks
+        note!(
+            "imported {added} bookmarks from {} ({} already present)",
+            file.display(),
+            total - added
+        );
+    }
+    Ok(())
+}
+
+/// Reads `file`, or stdin for `-`.
+fn read_input(file: &Path) -> Result<String> {
+    if file == Path::new("-") {
+        io::read_to_string(io::stdin()).map_err(|e| BmError::io(file, e))
+    } else {
+        fs::read_to_string(file).map_err(|e| BmError::io(file, e))
+    }
+}
+
+/// `bm add`, `bm import --from` and `bm import --bashmarks`: bookmarks each
+/// directory, under the name it comes with if any, and reports what was skipped.
+fn add_directories<'a>(
+    store: &dyn BookmarkStore,
+    paths: impl IntoIterator<Item = (Option<&'a str>, &'a str)>,
+    options: &AddOptions,
+) -> Result<()> {
+    let summary = add::add_named_paths(store, paths, options)?;
+    log_changes(store.path(), &journal::changes(&[], &summary.added));
+    for (path, reason) in &summary.invalid {
+        eprintln!("skipping {path}: {reason}");
+    }
+    for b in &summary.added {
+        note!("added {} -> {}", b.name, b.path);
+    }
+    note!(
+        "added {} bookmarks ({} already present, {} skipped)",
+        summary.added.len(),
+        summary.duplicates.len(),
+        summary.invalid.len()
+    );
+    Ok(())
+}
+
+/// The bookmark `dir` is in, the innermost of nested ones.
+fn containing<'a>(bookmarks: &'a [Bookmark], dir: &str) -> Option<&'a Bookmark> {
+    bookmarks
+        .iter()
+        .filter_map(|b| Some((b, paths::expand_tilde(&b.target().ok()?))))
+        .filter(|(b, target)| b.kind == Kind::Dir && paths::is_within(dir, target))
+        .max_by_key(|(_, target)| target.len())
+        .map(|(b, _)| b)
+}
+
+/// `bm status`: prints the name and path of the bookmark `dir` is in. With
+/// a `prompt` format only that is printed, with `{name}` filled in, and
+/// outside bookmarks nothing at all; a prompt shouldn't show errors either.
+fn show_status(store: &dyn BookmarkStore, dir: &str, prompt: Option<&str>) -> Result<()> {
+    let Some(format) = prompt else {
+        let bookmarks = store.load()?;
+        let bookmark = containing(&bookmarks, dir).ok_or_else(|| BmError::NoMatch(dir.to_string()))?;
+        println!("{}  {}", bookmark.name, bookmark.path);
+        return Ok(());
+    };
+    let bookmarks = store.load().unwrap_or_default();
+    if let Some(bookmark) = containing(&bookmarks, dir) {
+        println!("{}", format.replace("{name}", &bookmark.name));
+    }
+    Ok(())
+}
+
+/// `bm daemon`: serves the bookmarks of `store` until stopped, or with
+/// `status` prints what the running daemon holds.
+#[cfg(unix)]
+fn run_daemon(store: Box<dyn BookmarkStore>, config: &Config, status: bool) -> Result<()> {
+    if !status {
+        return bm::daemon::serve(store, Ranker::new(config));
+    }
+    let lines = bm::daemon::query(store.path(), "status")
+        .ok_or_else(|| BmError::Unsupported(format!("no daemon is serving {}", store.path().display())))??;
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn run_daemon(_store: Box<dyn BookmarkStore>, _config: &Config, _status: bool) -> Result<()> {
+    Err(BmError::Unsupported("bm daemon needs a Unix system".to_string()))
+}
+
+/// `bm doctor`: runs every check and prints what it found, with a fix for
+/// each problem. Fails if there was one, so scripts can tell.
+fn diagnose(path: PathBuf, cli_canonicalize: Option<bool>, read_only: bool) -> Result<()> {
+    let mut checks = vec![doctor::check_config(&Config::path()?)];
+    // A config.toml that doesn't parse is reported above; the rest goes on with the defaults.
+    let config = load_config(cli_canonicalize, read_only).unwrap_or_else(|_| Config { read_only, ..Config::default() });
+    let store = store::open(path, &config)?;
+    let (check, bookmarks) = doctor::check_file(store.as_ref());
+    checks.push(check);
+    if let Some(bookmarks) = bookmarks {
+        checks.extend(doctor::check_paths(&bookmarks));
+    }
+    checks.push(doctor::check_shell());
+    checks.extend(doctor::check_permissions(store.path(), config.read_only));
+    let mut problems = 0;
+    for check in &checks {
+        match &check.fix {
+            None => println!("ok    {}", check.summary),
+            Some(fix) => {
+                problems += 1;
+                println!("fail  {}", check.summary);
+                println!("      fix: {fix}");
+            }
+        }
+    }
+    if problems > 0 {
+        return Err(BmError::Unsupported(format!("{problems} problems found")));
+    }
+    Ok(())
+}
+
+/// `config.toml` with the command-line overrides applied.
+fn load_config(cli_canonicalize: Option<bool>, read_only: bool) -> Result<Config> {
+    let mut config = Config::load()?;
+    if let Some(language) = config.language {
+        i18n::set(language);
+    }
+    if let Some(canonicalize) = cli_canonicalize {
+        config.canonicalize = canonicalize;
+    }
+    config.read_only = read_only;
+    Ok(config)
+}
+
+/// `bm fzf`: prints one `name<TAB>path` line per bookmark, the best ranked
+/// by use first.
+fn fzf_lines(store: &dyn BookmarkStore, ranker: &Ranker) -> Result<()> {
+    if let Some(lines) = ask_daemon(store, "fzf") {
+        let data: String = lines?.iter().map(|line| format!("{line}\n")).collect();
+        return write_output(None, &data);
+    }
+    let mut bookmarks = store.load()?;
+    ranker.sort(&mut bookmarks);
+    let data: String = bookmarks.iter().map(|b| format!("{}\t{}\n", b.name, b.path)).collect();
+    write_output(None, &data)
+}
+
+/// `bm list`: prints the bookmarks to stdout.
+fn list_bookmarks(store: &dyn BookmarkStore, format: ListFormat, project_type: Option<ProjectType>) -> Result<()> {
+    let mut bookmarks = store.load()?;
+    if let Some(wanted) = project_type {
+        let found = probe::probe_all(&bookmarks, probe::TIMEOUT);
+        let mut found = found.into_iter();
+        bookmarks.retain(|_| found.next().is_some_and(|(_, project)| project == Some(wanted)));
+    }
+    let data = match format {
+        ListFormat::Text => {
+            let width = bookmarks.iter().map(|b| width::columns(&b.name)).max().unwrap_or(0);
+            bookmarks.iter().map(|b| format!("{}  {}\n", width::pad(&b.name, width), b.path)).collect()
+        }
+        ListFormat::Json | ListFormat::Nuon => {
+            let entries: Vec<ListEntry> = bookmarks
                 .iter()
-                .map(|b| ListItem::new(b.path.clone()))
+                .map(|b| ListEntry {
+                    name: &b.name,
+                    path: &b.path,
+                    note: &b.note,
+                    tags: &b.tags,
+                    added: b.added,
+                    last_used: b.last_used,
+                    hits: b.hits,
+                    workspace: &b.workspace,
+                    command: b.command.as_deref(),
+                    kind: b.kind.as_str(),
+                })
                 .collect();
+            let data = match format {
+                ListFormat::Nuon => nuon_table(&entries),
+                _ => serde_json::to_string_pretty(&entries),
+            };
+            data.map_err(|e| BmError::Serialize(e.to_string()))? + "\n"
+        }
+    };
+    io::stdout()
+        .write_all(data.as_bytes())
+        .map_err(|e| BmError::io(Path::new("<stdout>"), e))
+}
 
-            let list = List::new(items)
-                .block(Block::default().borders(Borders::ALL).title("Bookmarks"))
-                .highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black))
-                .highlight_symbol("→ ");
-
-            let mut state = ListState::default();
-            state.select(Some(selected));
-            f.render_stateful_widget(list, chunks[0], &mut state);
-
-            // Help message at bottom
-            let help_text = "j/k: move  u: add bookmark  !: delete  Enter: select  q: quit";
-            let help = Span::raw(help_text);
-            f.render_widget(
-                Block::default()
-                    .title(help)
-                    .borders(Borders::BOTTOM),
-                chunks[1],
-            );
-        })?;
-
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char('q') => break Ok(()),
-                    KeyCode::Char('j') | KeyCode::Down => {
-                        selected = (selected + 1).min(bookmarks.len().saturating_sub(1));
-                    }
-                    KeyCode::Char('k') | KeyCode::Up => {
-                        selected = selected.saturating_sub(1);
-                    }
-                    KeyCode::Char('u') => {
-                        if let Ok(cwd) = std::env::current_dir() {
-                            if let Some(cwd_str) = cwd.to_str() {
-                                let path = cwd_str.to_string();
-                                if !bookmarks.iter().any(|b| b.path == path) {
-                                    bookmarks.push(Bookmark {
-                                        name: format!("bookmark_{}", bookmarks.len() + 1),
-                                        path: path.clone(),
-                                    });
-                                    save_bookmarks(&bookmarks);
-                                    selected = bookmarks.len() - 1;
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Char('!') => {
-                        if !bookmarks.is_empty() {
-                            let mut show_confirm = true;
-                            while show_confirm {
-                                terminal.draw(|f| {
-                                    let size = f.area();
-                                    let chunks = Layout::default()
-                                        .direction(Direction::Vertical)
-                                        .constraints([
-                                            Constraint::Min(3),
-                                            Constraint::Length(3),
-                                        ])
-                                        .split(size);
-
-                                    // Bookmark list
-                                    let items: Vec<ListItem> = bookmarks
-                                        .iter()
-                                        .map(|b| ListItem::new(b.path.clone()))
-                                        .collect();
-
-                                    let list = List::new(items)
-                                        .block(Block::default().borders(Borders::ALL).title("Bookmarks"))
-                                        .highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black))
-                                        .highlight_symbol("→ ");
-
-                                    let mut state = ListState::default();
-                                    state.select(Some(selected));
-                                    f.render_stateful_widget(list, chunks[0], &mut state);
-
-                                    // Confirmation dialog
-                                    let confirm = Paragraph::new("Delete this bookmark? (y/n)")
-                                        .block(Block::default().borders(Borders::ALL).title("Confirm"))
-                                        .style(Style::default().fg(Color::Yellow));
-                                    f.render_widget(confirm, chunks[1]);
-                                })?;
-
-                                if let Event::Key(key) = event::read()? {
-                                    if key.kind == KeyEventKind::Press {
-                                        match key.code {
-                                            KeyCode::Char('y') | KeyCode::Char('Y') => {
-                                                bookmarks.remove(selected);
-                                                if selected >= bookmarks.len() && selected > 0 {
-                                                    selected -= 1;
-                                                }
-                                                save_bookmarks(&bookmarks);
-                                                show_confirm = false;
-                                            }
-                                            KeyCode::Char('n') | KeyCode::Char('N') => {
-                                                show_confirm = false;
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Enter => {
-                        if let Some(b) = bookmarks.get(selected) {
-                            println!("{}", b.path);
-                            break Ok(());
-                        }
-                    }
-                    _ => {}
-                }
-            }
+/// `entries` as a NUON table, `[[name, path, ...]; [...], ...]`: the
+/// column names once, then one row of values each. JSON strings, numbers,
+/// lists and `null` are valid NUON as they are.
+fn nuon_table(entries: &[ListEntry]) -> serde_json::Result<String> {
+    let mut columns: Vec<String> = Vec::new();
+    let mut rows = Vec::new();
+    for entry in entries {
+        let serde_json::Value::Object(fields) = serde_json::to_value(entry)? else {
+            continue;
+        };
+        if columns.is_empty() {
+            columns = fields.keys().cloned().collect();
+        }
+        let values: Vec<String> = fields.values().map(serde_json::to_string).collect::<serde_json::Result<_>>()?;
+        rows.push(format!("  [{}]", values.join(", ")));
+    }
+    if rows.is_empty() {
+        return Ok("[]".to_string());
+    }
+    Ok(format!("[[{}];\n{}\n]", columns.join(", "), rows.join(",\n")))
+}
+
+/// `bm expire`: archives the bookmarks unused for `days` days, listing them
+/// and asking first when `ask` is set.
+fn expire_bookmarks(store: &dyn BookmarkStore, days: u64, dry_run: bool, ask: bool) -> Result<()> {
+    let now = store::unix_now();
+    let list = |verb: &str, stale: &[Bookmark]| {
+        for b in stale {
+            let idle = archive::idle_days(b, now).unwrap_or(days);
+            eprintln!("{verb} {} -> {} (unused for {idle} days)", b.name, b.path);
         }
     };
+    if dry_run || ask {
+        let stale: Vec<Bookmark> = store.load()?.into_iter().filter(|b| archive::is_stale(b, days, now)).collect();
+        list("would archive", &stale);
+        if dry_run || stale.is_empty() {
+            note!("would archive {} bookmarks", stale.len());
+            return Ok(());
+        }
+        eprint!("Archive these {} bookmarks? [y/N] ", stale.len());
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).map_err(BmError::Terminal)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            note!("nothing was archived");
+            return Err(BmError::Cancelled);
+        }
+    }
+    let stale = archive::archive_stale(store, days, now)?;
+    log_changes(store.path(), &journal::changes(&stale, &[]));
+    if !ask && !QUIET.load(Ordering::Relaxed) {
+        list("archived", &stale);
+    }
+    note!("archived {} bookmarks", stale.len());
+    Ok(())
+}
+
+/// `bm get`: prints the directory of the bookmark called `name` and marks
+/// it as just used.
+fn get_bookmark(store: &dyn BookmarkStore, name: &str) -> Result<()> {
+    // A line picked from `bm fzf` carries the path after the name.
+    let name = name.split('\t').next().unwrap_or(name);
+    let (target, bookmark) = match visit_via_daemon(store, &format!("get\t{name}")) {
+        Some(visited) => visited?,
+        None => visit(store, name)?,
+    };
+    arrive(&target, &bookmark)
+}
+
+/// What the daemon serving the bookmarks of `store` answers to `request`,
+/// or `None` if none is running. Read-only, bm reads the file itself, as
+/// the daemon would count the visit.
+#[cfg(unix)]
+fn ask_daemon(store: &dyn BookmarkStore, request: &str) -> Option<Result<Vec<String>>> {
+    if store.is_read_only() {
+        return None;
+    }
+    let answer = bm::daemon::query(store.path(), request)?;
+    tracing::debug!("the daemon answered `{request}`");
+    Some(answer)
+}
+
+#[cfg(not(unix))]
+fn ask_daemon(_store: &dyn BookmarkStore, _request: &str) -> Option<Result<Vec<String>>> {
+    None
+}
+
+/// Like `visit_with`, but with the daemon finding the bookmark and
+/// recording the visit; `None` if no daemon is running.
+fn visit_via_daemon(store: &dyn BookmarkStore, request: &str) -> Option<Result<(String, Bookmark)>> {
+    let lines = match ask_daemon(store, request)? {
+        Ok(lines) => lines,
+        Err(e) => return Some(Err(e)),
+    };
+    let mut fields = lines.first()?.splitn(3, '\t');
+    let (name, target, kind) = (fields.next()?, fields.next()?, fields.next()?);
+    let mut bookmark = Bookmark::new(name.to_string(), target.to_string());
+    bookmark.kind = match kind {
+        "file" => Kind::File,
+        "remote" => Kind::Remote,
+        _ => Kind::Dir,
+    };
+    pre_jump(name, target);
+    Some(Ok((target.to_string(), bookmark)))
+}
+
+/// The directory of the bookmark called `name` and the bookmark itself,
+/// after recording the visit.
+fn visit(store: &dyn BookmarkStore, name: &str) -> Result<(String, Bookmark)> {
+    visit_with(store, |bookmarks| {
+        bookmarks
+            .iter()
+            .position(|b| b.name == name)
+            .ok_or_else(|| BmError::UnknownBookmark(name.to_string()))
+    })
+}
+
+/// The directory of the bookmark `find` picks and the bookmark itself,
+/// after recording the visit.
+fn visit_with(
+    store: &dyn BookmarkStore,
+    find: impl FnOnce(&[Bookmark]) -> Result<usize>,
+) -> Result<(String, Bookmark)> {
+    let base = store.load()?;
+    let mut bookmarks = base.clone();
+    let bookmark = &mut bookmarks[find(&base)?];
+    let target = bookmark
+        .target()
+        .map_err(|var| BmError::UnsetVariable { path: bookmark.path.clone(), var })?;
+    bookmark.touch();
+    let visited = bookmark.clone();
+    // Read-only, jumps still work; they just aren't counted.
+    if !store.is_read_only() {
+        save(store, &base, &bookmarks)?;
+    }
+    pre_jump(&visited.name, &target);
+    Ok((target, visited))
+}
+
+/// `bm pick`: jumps to the bookmark that matches `query` best.
+fn pick_bookmark(store: &dyn BookmarkStore, query: &str) -> Result<()> {
+    if let Some(visited) = visit_via_daemon(store, &format!("pick\t{query}")) {
+        let (target, bookmark) = visited?;
+        return arrive(&target, &bookmark);
+    }
+    let (target, bookmark) = visit_with(store, |bookmarks| {
+        fuzzy::best_match(query, bookmarks).ok_or_else(|| BmError::NoMatch(query.to_string()))
+    })?;
+    arrive(&target, &bookmark)
+}
+
+/// `bm pick --tui`: jumps to the bookmark picked in the picker, which
+/// starts out with `query` typed in.
+fn pick_interactively(store: &dyn BookmarkStore, query: &str, ranker: &Ranker) -> Result<()> {
+    let bookmarks = store.load()?;
+    let picked = tui::run_picker(&bookmarks, query, ranker)?.ok_or(BmError::NoSelection)?;
+    let path = &bookmarks[picked].path;
+    let (target, bookmark) = visit_with(store, |bookmarks| {
+        bookmarks.iter().position(|b| &b.path == path).ok_or_else(|| BmError::UnknownBookmark(path.clone()))
+    })?;
+    arrive(&target, &bookmark)
+}
+
+/// `bm menu`: lets an external menu pick a bookmark and jumps to it.
+/// Closing the menu without a choice prints nothing.
+fn menu_bookmark(store: &dyn BookmarkStore, command: &str) -> Result<()> {
+    let Some(choice) = menu::choose(command, &menu::entries(&store.load()?))? else {
+        return Err(BmError::NoSelection);
+    };
+    let (target, bookmark) = visit_with(store, |bookmarks| {
+        menu::resolve(&choice, bookmarks, &menu::entries(bookmarks)).ok_or_else(|| BmError::NoMatch(choice.clone()))
+    })?;
+    arrive(&target, &bookmark)
+}
+
+/// `bm track`: counts a visit to `dir`, made absolute and without `.`
+/// components, so it is counted under the path `bm add` would bookmark.
+fn track_directory(store: &dyn BookmarkStore, config: &Config, dir: &Path) -> Result<()> {
+    let dir = if dir.is_absolute() {
+        dir.to_path_buf()
+    } else {
+        std::env::current_dir().map_err(|e| BmError::io(Path::new("."), e))?.join(dir)
+    };
+    let dir: PathBuf = dir.components().collect();
+    if !dir.is_dir() {
+        return Err(BmError::Unsupported(format!("{} is not a directory", dir.display())));
+    }
+    let Some(dir) = dir.to_str() else {
+        return Err(BmError::Unsupported(format!("{} is not valid UTF-8", dir.display())));
+    };
+    if add::excluded(&config.exclude, dir).is_some() {
+        return Ok(());
+    }
+    // A bookmarks file that doesn't load only keeps its directories from
+    // being exempt from the limit; visits are still counted.
+    let bookmarks = store.load().unwrap_or_default();
+    visits::record(dir, config.max_visits, &bookmarks)
+}
+
+/// `bm push`: saves the current directory on the stack and prints the
+/// bookmarked one to change to.
+fn push_directory(store: &dyn BookmarkStore, name: &str) -> Result<()> {
+    let (target, bookmark) = visit(store, name)?;
+    if bookmark.kind == Kind::Remote {
+        return Err(BmError::Unsupported(format!("{name} is a remote bookmark; use bm get {name} to log into it")));
+    }
+    let cwd = std::env::current_dir().map_err(|e| BmError::io(Path::new("."), e))?;
+    stack::push(&cwd.display().to_string())?;
+    jumped_to(&target)
+}
+
+/// `bm -`: goes back `steps` entries in the jump history, recording the
+/// visit on the bookmark for that directory if there still is one.
+fn go_back(store: &dyn BookmarkStore, steps: usize) -> Result<()> {
+    let recent = history::load()?;
+    let target = recent.get(steps).ok_or_else(|| {
+        BmError::Unsupported("the jump history does not go back that far".to_string())
+    })?;
+    let base = store.load()?;
+    let mut bookmarks = base.clone();
+    let mut name = String::new();
+    if let Some(bookmark) = bookmarks.iter_mut().find(|b| b.target().is_ok_and(|t| same_path(&t, target))) {
+        bookmark.touch();
+        name = bookmark.name.clone();
+        if !store.is_read_only() {
+            save(store, &base, &bookmarks)?;
+        }
+    }
+    pre_jump(&name, target);
+    jumped_to(target)
+}
+
+/// Finishes a jump to `bookmark`, whose path is `target`: logs into the
+/// host of a remote bookmark, or prints the path of any other.
+fn arrive(target: &str, bookmark: &Bookmark) -> Result<()> {
+    if bookmark.kind == Kind::Remote {
+        return remote::connect(target);
+    }
+    jumped_to(target)
+}
+
+/// Prints the directory to change to and puts it on top of the jump history.
+fn jumped_to(dir: &str) -> Result<()> {
+    emit_path(dir)?;
+    if let Err(e) = history::record(dir) {
+        eprintln!("bm: could not record the jump: {e}");
+    }
+    Ok(())
+}
+
+/// Prints `dir` for the shell to change to, or writes it where `--output`
+/// or `--output-fd` says.
+fn emit_path(dir: &str) -> Result<()> {
+    let line = format!("{dir}\n");
+    match PATH_OUTPUT.get() {
+        None => print!("{line}"),
+        // Written in place rather than replaced, so a fifo or `/dev/stdout`
+        // stays what the caller passed.
+        Some(PathOutput::File(file)) => fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(file)
+            .and_then(|mut out| out.write_all(line.as_bytes()))
+            .map_err(|e| BmError::io(file, e))?,
+        #[cfg(unix)]
+        Some(PathOutput::Fd(fd)) => {
+            use std::os::fd::FromRawFd;
+            // SAFETY: the caller opened the descriptor for bm to write to. It
+            // stays theirs, so it is never closed here.
+            let mut out = std::mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(*fd) });
+            out.write_all(line.as_bytes()).map_err(|e| BmError::io(Path::new(&format!("fd {fd}")), e))?;
+        }
+        #[cfg(not(unix))]
+        Some(PathOutput::Fd(_)) => return Err(BmError::Unsupported("--output-fd needs a Unix system".to_string())),
+    }
+    Ok(())
+}
 
-    // Cleanup
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        crossterm::cursor::Show
-    )?;
+/// `bm dedup`: folds near-duplicate bookmarks into the first of each group.
+fn dedup_bookmarks(store: &dyn BookmarkStore) -> Result<()> {
+    let base = store.load()?;
+    let mut bookmarks = base.clone();
+    let removed = dedup::dedup(&mut bookmarks);
+    for (b, kept) in &removed {
+        note!("merged {} -> {} into {kept}", b.name, b.path);
+    }
+    if bookmarks != base {
+        save(store, &base, &bookmarks)?;
+    }
+    note!("removed {} duplicate bookmarks", removed.len());
+    Ok(())
+}
 
-    result
+/// `bm dedup --interactive`: lets the user merge each group of bookmarks
+/// that resolve to the same directory.
+fn dedup_interactively(store: &dyn BookmarkStore) -> Result<()> {
+    let base = store.load()?;
+    let groups = dedup::same_directory_groups(&base);
+    if groups.is_empty() {
+        note!("no duplicate bookmarks");
+        return Ok(());
+    }
+    let choices = tui::run_dedup(&base, &groups, "Duplicates")?;
+    let mut bookmarks = base.clone();
+    let removed = dedup::apply_choices(&mut bookmarks, &choices);
+    if bookmarks != base {
+        save(store, &base, &bookmarks)?;
+    }
+    note!("merged {} of {} groups ({removed} bookmarks removed)", choices.len(), groups.len());
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    run_tui()
+/// `bm merge`: folds `other`, a copy of the bookmarks that diverged, into
+/// `store`, letting the user pick for bookmarks edited in both.
+fn merge_file(store: &dyn BookmarkStore, other: &dyn BookmarkStore) -> Result<()> {
+    let base = store.load()?;
+    let (mut bookmarks, conflicts) = dedup::merge_copies(&base, &other.load()?);
+    if !conflicts.is_empty() {
+        let pairs: Vec<Bookmark> = conflicts.iter().flat_map(|c| [c.ours.clone(), c.theirs.clone()]).collect();
+        let groups: Vec<Vec<usize>> = (0..conflicts.len()).map(|i| vec![2 * i, 2 * i + 1]).collect();
+        let title = format!("Edited in both (#1 {}, #2 {})", store.path().display(), other.path().display());
+        for (group, choice) in tui::run_dedup(&pairs, &groups, &title)? {
+            let kept = &mut bookmarks[conflicts[group[0] / 2].index];
+            let mut merged = choice.merged(&pairs, &group);
+            // Both sides hold the same visits, which the choice would add up.
+            (merged.added, merged.last_used, merged.hits) = (kept.added, kept.last_used, kept.hits);
+            *kept = merged;
+        }
+    }
+    if bookmarks != base {
+        save(store, &base, &bookmarks)?;
+    }
+    note!(
+        "merged {}: {} bookmarks added, {} edited on both sides",
+        other.path().display(),
+        bookmarks.len() - base.len(),
+        conflicts.len()
+    );
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let path = match cli.file {
+        Some(file) => file,
+        None => profile::profile_path(cli.profile.as_deref())?,
+    };
+    match cli.command {
+        None => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            match tui::run_tui(store, cli.profile, cli.cwd, &config)? {
+                Some(tui::Picked::Dir(path)) => jumped_to(&path),
+                Some(tui::Picked::Paths(paths)) => emit_path(&paths.join("\n")),
+                None => Ok(()),
+            }
+        }
+        Some(Commands::Edit | Commands::Sync { .. }) if cli.read_only => Err(BmError::ReadOnly(path)),
+        Some(Commands::Edit) if store::is_sqlite(&path) => Err(BmError::Unsupported(format!(
+            "{}: bm edit does not work on SQLite databases",
+            path.display()
+        ))),
+        Some(Commands::Edit) if store::is_encrypted(&path) => Err(BmError::Unsupported(format!(
+            "{}: bm edit does not work on encrypted files",
+            path.display()
+        ))),
+        Some(Commands::Edit) => edit_bookmark_file(&path),
+        Some(Commands::Export { output, format }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            export_bookmarks(store.as_ref(), output.as_deref(), format)
+        }
+        Some(Commands::Import { from: Some(list), .. }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            let content = read_input(&list)?;
+            // A list is imported whole, so clashing names get a suffix.
+            let options = AddOptions {
+                canonicalize: config.canonicalize,
+                suffix_names: true,
+                exclude: config.exclude.clone(),
+                ..AddOptions::default()
+            };
+            add_directories(store.as_ref(), add::parse_path_list(&content).map(|p| (None, p)), &options)
+        }
+        Some(Commands::Import { bashmarks: Some(file), .. }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            let content = read_input(Path::new(&paths::expand_tilde(&file)))?;
+            let named = add::parse_named_list(&content);
+            let options = AddOptions {
+                canonicalize: config.canonicalize,
+                suffix_names: true,
+                exclude: config.exclude.clone(),
+                ..AddOptions::default()
+            };
+            add_directories(store.as_ref(), named.iter().map(|(name, p)| (Some(name.as_str()), p.as_str())), &options)
+        }
+        Some(Commands::Import { input, format, from: None, update, .. }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            let input = input.expect("clap requires FILE without --from");
+            import_bookmarks(store.as_ref(), &input, format, update)
+        }
+        Some(Commands::Add { paths, stdin, tags, suffix_names }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            let options = AddOptions { tags, canonicalize: config.canonicalize, suffix_names, exclude: config.exclude.clone() };
+            if stdin {
+                let content = read_input(Path::new("-"))?;
+                add_directories(store.as_ref(), add::parse_path_list(&content).map(|p| (None, p)), &options)
+            } else {
+                let cwd = cli.cwd.map_or_else(|| ".".to_string(), |dir| dir.to_string_lossy().into_owned());
+                let paths = if paths.is_empty() { vec![cwd] } else { paths };
+                add_directories(store.as_ref(), paths.iter().map(|p| (None, p.as_str())), &options)
+            }
+        }
+        Some(Commands::List { format, project_type }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            list_bookmarks(store.as_ref(), format, project_type)
+        }
+        Some(Commands::Doctor) => diagnose(path, cli.canonicalize, cli.read_only),
+        Some(Commands::Status { prompt }) => {
+            // A prompt shouldn't show errors, so a broken config falls back
+            // to the defaults, as for `bm doctor`.
+            let config = match load_config(cli.canonicalize, cli.read_only) {
+                Ok(config) => config,
+                Err(_) if prompt => Config { read_only: cli.read_only, ..Config::default() },
+                Err(e) => return Err(e),
+            };
+            let store = match store::open(path, &config) {
+                Ok(store) => store,
+                Err(_) if prompt => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let dir = match cli.cwd {
+                Some(dir) => dir,
+                // $PWD keeps the symlinks the shell went through, as the prompt shows them.
+                None => std::env::var_os("PWD").map_or_else(|| std::env::current_dir().unwrap_or_default(), PathBuf::from),
+            };
+            show_status(store.as_ref(), &dir.to_string_lossy(), prompt.then_some(config.prompt_format.as_str()))
+        }
+        Some(Commands::Serve { .. }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            let options = AddOptions { canonicalize: config.canonicalize, exclude: config.exclude.clone(), ..AddOptions::default() };
+            rpc::serve(store.as_ref(), &options, &config.hooks, io::stdin().lock(), io::stdout().lock())
+        }
+        Some(Commands::Daemon { status }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            run_daemon(store, &config, status)
+        }
+        Some(Commands::Stats) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            let bookmarks = store.load()?;
+            let stats = Stats::new(&bookmarks, &probe::check_all(&bookmarks, probe::TIMEOUT), store::unix_now());
+            for line in stats.report() {
+                println!("{line}");
+            }
+            Ok(())
+        }
+        Some(Commands::Expire { days, dry_run, yes }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let days = days.or(config.stale_after_days).ok_or_else(|| {
+                BmError::Unsupported("pass --days or set stale_after_days in config.toml".to_string())
+            })?;
+            let store = store::open(path, &config)?;
+            expire_bookmarks(store.as_ref(), days, dry_run, config.confirm.prune && !yes)
+        }
+        Some(Commands::Get { name }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            get_bookmark(store.as_ref(), &name)
+        }
+        Some(Commands::Push { name }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            push_directory(store.as_ref(), &name)
+        }
+        Some(Commands::Pop) => {
+            let dir = stack::pop()?.ok_or_else(|| BmError::Unsupported("the directory stack is empty".to_string()))?;
+            emit_path(&dir)
+        }
+        Some(Commands::Init { shell }) => {
+            print!("{}", shell::init_script(shell));
+            Ok(())
+        }
+        Some(Commands::Hook { shell }) => {
+            print!("{}", shell::hook_script(shell));
+            Ok(())
+        }
+        Some(Commands::Track { dir }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            track_directory(store.as_ref(), &config, &dir)
+        }
+        Some(Commands::Pick { query, tui: true }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            pick_interactively(store.as_ref(), &query.join(" "), &Ranker::new(&config))
+        }
+        Some(Commands::Pick { query, tui: false }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            pick_bookmark(store.as_ref(), &query.join(" "))
+        }
+        Some(Commands::Menu { dmenu }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            menu_bookmark(store.as_ref(), &dmenu)
+        }
+        Some(Commands::Tmux { name, session }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            let (target, bookmark) = visit(store.as_ref(), &name)?;
+            tmux::open(&name, &target, &bookmark.workspace, if session { tmux::Target::Session } else { tmux::Target::Window })
+        }
+        Some(Commands::Run { name }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            let (target, bookmark) = visit_with(store.as_ref(), |bookmarks| {
+                let b = bookmarks.iter().position(|b| b.name == name);
+                match b.map(|i| (i, &bookmarks[i])) {
+                    Some((i, b)) if b.command.is_some() => Ok(i),
+                    Some(_) => Err(BmError::NoCommand(name.clone())),
+                    None => Err(BmError::UnknownBookmark(name.clone())),
+                }
+            })?;
+            launch::run_command(&bookmark, &target)
+        }
+        Some(Commands::Fzf) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            fzf_lines(store.as_ref(), &Ranker::new(&config))
+        }
+        Some(Commands::Back { steps }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            go_back(store.as_ref(), steps)
+        }
+        Some(Commands::Dedup { interactive }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            if interactive {
+                dedup_interactively(store.as_ref())
+            } else {
+                dedup_bookmarks(store.as_ref())
+            }
+        }
+        Some(Commands::Merge { other }) => {
+            let config = load_config(cli.canonicalize, cli.read_only)?;
+            let store = store::open(path, &config)?;
+            merge_file(store.as_ref(), store::open(other, &config)?.as_ref())
+        }
+        Some(Commands::Completions { shell }) => write_completions(shell),
+        Some(Commands::Log { limit }) => {
+            for line in journal::read(limit)? {
+                println!("{}", line.replace('\t', "  "));
+            }
+            Ok(())
+        }
+        Some(Commands::Sync { init, remote }) => {
+            let store = store::open(path, &load_config(cli.canonicalize, cli.read_only)?)?;
+            if init {
+                sync::init(store.as_ref(), remote.as_deref())?;
+                note!("bm: {} is a git repository now; saves are committed", store.path().parent().unwrap_or(Path::new(".")).display());
+                if remote.is_none() {
+                    return Ok(());
+                }
+            }
+            let synced = sync::sync(store.as_ref())?;
+            let pulled = if synced.pulled { "merged changes from" } else { "nothing new from" };
+            note!("bm: synced with {} ({pulled} it)", synced.remote);
+            for name in &synced.conflicts {
+                eprintln!("bm: {name} was changed on both machines; kept this one's version");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    CompleteEnv::with_factory(Cli::command).var(COMPLETE_VAR).complete();
+    let cli = Cli::parse();
+    logging::init(cli.verbose);
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    if let Some(output) = cli.output.clone().map(PathOutput::File).or(cli.output_fd.map(PathOutput::Fd)) {
+        let _ = PATH_OUTPUT.set(output);
+    }
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is_silent() => ExitCode::from(e.exit_code()),
+        Err(e) => {
+            tracing::error!("{e}");
+            eprintln!("bm: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
@@ -0,0 +1,51 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{BmError, Result};
+use crate::fuzzy;
+use crate::store::Bookmark;
+use crate::width;
+
+/// Menu run by `bm menu` when no command is given.
+pub const DEFAULT_MENU: &str = "dmenu";
+
+/// One `name  path` line per bookmark, names padded to the same width.
+pub fn entries(bookmarks: &[Bookmark]) -> Vec<String> {
+    let width = bookmarks.iter().map(|b| width::columns(&b.name)).max().unwrap_or(0);
+    bookmarks.iter().map(|b| format!("{}  {}", width::pad(&b.name, width), b.path)).collect()
+}
+
+/// Runs a dmenu-style `command` (split on whitespace, like `$EDITOR`) with
+/// `entries` on its stdin and returns the line it prints. `None` means the
+/// menu was closed without a choice.
+pub fn choose(command: &str, entries: &[String]) -> Result<Option<String>> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().unwrap_or(DEFAULT_MENU);
+    let err = |e| BmError::io(Path::new(program), e);
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(err)?;
+    let input: String = entries.iter().map(|e| format!("{e}\n")).collect();
+    // A menu that exits before reading everything closes the pipe; its
+    // output still says what was chosen.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(err)?;
+    let choice = String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("").trim_end().to_string();
+    Ok((output.status.success() && !choice.is_empty()).then_some(choice))
+}
+
+/// The bookmark a menu's `choice` stands for: one of the `entries`, a
+/// bookmark name, or else what typing it as a `bm pick` query finds.
+pub fn resolve(choice: &str, bookmarks: &[Bookmark], entries: &[String]) -> Option<usize> {
+    entries
+        .iter()
+        .position(|e| e.trim_end() == choice)
+        .or_else(|| bookmarks.iter().position(|b| b.name == choice))
+        .or_else(|| fuzzy::best_match(choice, bookmarks))
+}
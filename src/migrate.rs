@@ -0,0 +1,46 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Layout version written to the `version` key of every bookmarks file.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// One step of the migration pipeline: rewrites a file at `from` so that it
+/// matches the layout of version `from + 1`.
+struct Migration {
+    from: u32,
+    apply: fn(&mut Map<String, Value>) -> Result<(), String>,
+}
+
+/// Every layout change since version 1, oldest first. Bumping
+/// `SCHEMA_VERSION` requires adding the step that upgrades the previous
+/// version here.
+const MIGRATIONS: &[Migration] = &[];
+
+/// Files written before the `version` key existed are version 1.
+pub(crate) fn implicit_version() -> u32 {
+    1
+}
+
+/// Just the version of a bookmarks file, read before deciding how to parse it.
+#[derive(Deserialize)]
+pub(crate) struct VersionProbe {
+    #[serde(default = "implicit_version")]
+    pub(crate) version: u32,
+}
+
+/// Upgrades the parsed contents of a file at version `from` to
+/// `SCHEMA_VERSION`.
+pub(crate) fn migrate(value: &mut Value, from: u32) -> Result<(), String> {
+    let Value::Object(table) = value else {
+        return Err("expected a table at the top level".to_string());
+    };
+    for version in from..SCHEMA_VERSION {
+        let step = MIGRATIONS
+            .iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| format!("no migration from schema version {version}"))?;
+        (step.apply)(table)?;
+    }
+    table.insert("version".to_string(), Value::from(SCHEMA_VERSION));
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dirs::home_dir;
+use serde::Deserialize;
+
+use crate::error::{BmError, Result};
+
+/// Name of the bookmarks file inside the data directory.
+const BOOKMARKS_FILE: &str = "bookmarks.toml";
+
+/// Reads a directory from `var`, ignoring empty and relative values as the
+/// XDG spec requires.
+fn dir_from_env(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|v: &OsString| !v.is_empty())
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
+fn home() -> Result<PathBuf> {
+    home_dir().ok_or(BmError::NoHome)
+}
+
+/// Whether `c` separates path components: `/`, and `\` on Windows.
+fn is_separator(c: char) -> bool {
+    c == '/' || c == std::path::MAIN_SEPARATOR
+}
+
+/// Replaces a leading `~` in `path` with the home directory, for paths that
+/// did not go through the shell.
+pub fn expand_tilde(path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => rest,
+        _ => return path.to_string(),
+    };
+    match home_dir() {
+        Some(home) => format!("{}{rest}", home.display()),
+        None => path.to_string(),
+    }
+}
+
+/// A working directory passed to bm: a path, or a `file://host/path` URL
+/// as shells report it to the terminal with OSC 7, `%XX` escapes decoded.
+/// The host part is ignored.
+pub fn parse_cwd(value: &str) -> std::result::Result<PathBuf, String> {
+    let Some(rest) = value.strip_prefix("file://") else {
+        return Ok(PathBuf::from(expand_tilde(value)));
+    };
+    let path = &rest[rest.find('/').ok_or("the file URL has no path")?..];
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut input = path.bytes();
+    while let Some(byte) = input.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex: Vec<u8> = input.by_ref().take(2).collect();
+        let decoded = std::str::from_utf8(&hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        bytes.push(decoded.ok_or_else(|| format!("invalid escape in {value}"))?);
+    }
+    let path = String::from_utf8(bytes).map_err(|_| format!("{value} is not valid UTF-8"))?;
+    // `file:///C:/src` names a Windows drive path.
+    match path.strip_prefix('/') {
+        Some(drive) if drive.as_bytes().get(1) == Some(&b':') => Ok(PathBuf::from(drive)),
+        _ => Ok(PathBuf::from(path)),
+    }
+}
+
+/// A `file://` URL for the absolute `path`, the reverse of [`parse_cwd`].
+/// Bytes other than letters, digits and `/-._~:` are `%XX` escaped.
+pub fn file_url(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut url = String::from("file://");
+    // Windows paths start with their drive, which URLs put after a slash.
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' | b':' => url.push(byte as char),
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    url
+}
+
+/// Expands `$NAME` and `${NAME}` in `path` from the environment. Fails
+/// with the name of the first variable that is unset or empty, since the
+/// result would point somewhere else.
+pub fn expand_vars(path: &str) -> Result<String, String> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, remainder) = match after.strip_prefix('{').and_then(|a| a.split_once('}')) {
+            Some((name, remainder)) => (name, remainder),
+            None => {
+                let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+                after.split_at(end)
+            }
+        };
+        if name.is_empty() || !name.chars().all(is_name) {
+            // A lone `$` is part of the path.
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        match env::var(name) {
+            Ok(value) if !value.is_empty() => out.push_str(&value),
+            _ => return Err(name.to_string()),
+        }
+        rest = remainder;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Replaces the home directory at the start of `path` with `~`, the
+/// reverse of [`expand_tilde`].
+pub fn contract_tilde(path: &str) -> String {
+    let Some(home) = home_dir().and_then(|h| h.into_os_string().into_string().ok()) else {
+        return path.to_string();
+    };
+    match path.strip_prefix(home.trim_end_matches(is_separator)) {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => format!("~{rest}"),
+        _ => path.to_string(),
+    }
+}
+
+/// `path` with the directory `from` at its start replaced by `to`, or
+/// `None` if it isn't in `from`.
+fn replace_prefix(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(trim_trailing_separator(from))?;
+    if !rest.is_empty() && !rest.starts_with(is_separator) {
+        return None;
+    }
+    Some(format!("{}{rest}", trim_trailing_separator(to)))
+}
+
+/// The `[remap]` table of `config.toml`: directories as the bookmarks file
+/// has them, mapped to where they are on this machine, such as
+/// `"/home/alice" = "/Users/alice"`.
+#[derive(Deserialize, Clone, Default)]
+#[serde(transparent)]
+pub struct Remap(BTreeMap<String, String>);
+
+impl Remap {
+    /// `path` after the longest of `pairs`' prefixes it starts with is replaced.
+    fn apply<'a>(path: &str, pairs: impl Iterator<Item = (&'a String, &'a String)>) -> Option<String> {
+        pairs
+            .filter_map(|(from, to)| Some((from.len(), replace_prefix(path, from, to)?)))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, mapped)| mapped)
+    }
+
+    /// `path` from the bookmarks file as it is on this machine.
+    pub fn to_local(&self, path: &str) -> String {
+        Remap::apply(path, self.0.iter()).unwrap_or_else(|| path.to_string())
+    }
+
+    /// `path` on this machine as the bookmarks file has it.
+    pub fn to_shared(&self, path: &str) -> String {
+        Remap::apply(path, self.0.iter().map(|(from, to)| (to, from))).unwrap_or_else(|| path.to_string())
+    }
+}
+
+/// `path` without trailing separators, keeping a lone root such as `/`,
+/// `C:\` or `\\`.
+fn trim_trailing_separator(path: &str) -> &str {
+    let trimmed = path.trim_end_matches(is_separator);
+    if trimmed.is_empty() || trimmed.ends_with(':') { path } else { trimmed }
+}
+
+/// The form paths are compared in when looking for duplicates: without a
+/// trailing separator and, on macOS and Windows where filesystems ignore
+/// case by default, lowercased. On Windows `/` and `\` are the same
+/// separator, so `C:/src` and `C:\src` compare equal.
+pub fn normalize(path: &str) -> String {
+    let path = trim_trailing_separator(path);
+    if cfg!(windows) {
+        path.replace('/', "\\").to_lowercase()
+    } else if cfg!(target_os = "macos") {
+        path.to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+/// `fs::canonicalize`, without the `\\?\` prefix Windows puts in front of
+/// the result: `\\?\C:\src` becomes `C:\src` and `\\?\UNC\server\share`
+/// becomes `\\server\share`, as users write them and other programs accept.
+pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    let canonical = fs::canonicalize(path)?;
+    let Some(text) = canonical.to_str() else {
+        return Ok(canonical);
+    };
+    if let Some(share) = text.strip_prefix(r"\\?\UNC\") {
+        return Ok(PathBuf::from(format!(r"\\{share}")));
+    }
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => Ok(PathBuf::from(rest)),
+        _ => Ok(canonical),
+    }
+}
+
+/// Whether `path` is `root` or somewhere below it, compared in the form
+/// [`normalize`] gives.
+pub fn is_within(path: &str, root: &str) -> bool {
+    let (path, root) = (normalize(path), normalize(root));
+    match path.strip_prefix(&root) {
+        Some(rest) => rest.is_empty() || rest.starts_with(is_separator) || root.ends_with(is_separator),
+        None => false,
+    }
+}
+
+/// Whether `a` and `b` are the same bookmark path once normalized.
+pub fn same_path(a: &str, b: &str) -> bool {
+    a == b || normalize(a) == normalize(b)
+}
+
+/// Where bookmarks and backups live: `$BM_DATA_DIR`, else
+/// `$XDG_DATA_HOME/bm`, else `~/.local/share/bm`.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(dir) = dir_from_env("BM_DATA_DIR") {
+        return Ok(dir);
+    }
+    if let Some(dir) = dir_from_env("XDG_DATA_HOME") {
+        return Ok(dir.join("bm"));
+    }
+    if cfg!(windows) {
+        return dirs::data_dir().map(|d| d.join("bm")).ok_or(BmError::NoHome);
+    }
+    Ok(home()?.join(".local/share/bm"))
+}
+
+/// Where `config.toml` lives: `$BM_CONFIG_DIR`, else `$XDG_CONFIG_HOME/bm`,
+/// else `~/.config/bm`.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = dir_from_env("BM_CONFIG_DIR") {
+        return Ok(dir);
+    }
+    if let Some(dir) = dir_from_env("XDG_CONFIG_HOME") {
+        return Ok(dir.join("bm"));
+    }
+    if cfg!(windows) {
+        return dirs::config_dir().map(|d| d.join("bm")).ok_or(BmError::NoHome);
+    }
+    Ok(home()?.join(".config/bm"))
+}
+
+/// The pre-XDG location, `~/.bm/`.
+fn legacy_dir() -> Result<PathBuf> {
+    Ok(home()?.join(".bm"))
+}
+
+/// Resolves the bookmarks file, moving a legacy `~/.bm/bookmarks.toml` (and
+/// its backups) into the data directory the first time it is found. If the
+/// move fails the legacy file keeps being used in place.
+pub fn get_bookmark_path() -> Result<PathBuf> {
+    let path = data_dir()?.join(BOOKMARKS_FILE);
+    if path.exists() {
+        return Ok(path);
+    }
+    let legacy = legacy_dir()?;
+    let legacy_path = legacy.join(BOOKMARKS_FILE);
+    if !legacy_path.exists() {
+        return Ok(path);
+    }
+    match migrate_legacy(&legacy, &path) {
+        Ok(()) => {
+            eprintln!(
+                "bm: moved {} to {}",
+                legacy_path.display(),
+                path.display()
+            );
+            Ok(path)
+        }
+        Err(e) => {
+            eprintln!("bm: could not migrate {}: {e}", legacy_path.display());
+            Ok(legacy_path)
+        }
+    }
+}
+
+fn migrate_legacy(legacy: &Path, path: &Path) -> Result<()> {
+    let data = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(data).map_err(|e| BmError::io(data, e))?;
+    move_path(&legacy.join(BOOKMARKS_FILE), path)?;
+    let backups = legacy.join("backups");
+    if backups.is_dir() && !data.join("backups").exists() {
+        move_path(&backups, &data.join("backups"))?;
+    }
+    let _ = fs::remove_file(legacy.join(format!("{BOOKMARKS_FILE}.lock")));
+    // Leaves ~/.bm alone if anything else was put there.
+    let _ = fs::remove_dir(legacy);
+    Ok(())
+}
+
+/// Renames `from` to `to`, copying instead when they are on different filesystems.
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        fs::create_dir_all(to).map_err(|e| BmError::io(to, e))?;
+        for entry in fs::read_dir(from).map_err(|e| BmError::io(from, e))? {
+            let entry = entry.map_err(|e| BmError::io(from, e))?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from).map_err(|e| BmError::io(from, e))
+    } else {
+        fs::copy(from, to).map_err(|e| BmError::io(to, e))?;
+        fs::remove_file(from).map_err(|e| BmError::io(from, e))
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::icons::{self, ProjectType};
+use crate::store::{Bookmark, Kind};
+
+/// How long checking whether bookmarked paths exist may take in total.
+/// A stat on an unreachable NFS or SMB mount can block for minutes.
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What checking a bookmark's path found.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+    /// There is a directory, or a file for file bookmarks. Remote paths
+    /// always count as present, as checking would need a connection.
+    Present,
+    /// Nothing of the bookmark's kind is there, or its variable is unset.
+    Missing,
+    /// The check did not finish in time.
+    Unknown,
+}
+
+/// Starts checking `b` on a thread of its own, so a stat that never
+/// returns only blocks that thread. Directories that are there are also
+/// looked into for their project type.
+fn start(b: &Bookmark) -> Receiver<(Status, Option<ProjectType>)> {
+    let (tx, rx) = mpsc::channel();
+    let kind = b.kind;
+    match b.target() {
+        _ if kind == Kind::Remote => {
+            let _ = tx.send((Status::Present, None));
+        }
+        Ok(path) => {
+            thread::spawn(move || {
+                let path = Path::new(&path);
+                let found = if Kind::of(path) != Some(kind) {
+                    (Status::Missing, None)
+                } else if kind == Kind::Dir {
+                    (Status::Present, icons::detect(path))
+                } else {
+                    (Status::Present, None)
+                };
+                let _ = tx.send(found);
+            });
+        }
+        Err(_) => {
+            let _ = tx.send((Status::Missing, None));
+        }
+    }
+    rx
+}
+
+/// The status and project type of every bookmark, in order, checked in
+/// parallel. Paths that haven't answered once `timeout` is up are `Unknown`.
+pub fn probe_all(bookmarks: &[Bookmark], timeout: Duration) -> Vec<(Status, Option<ProjectType>)> {
+    let pending: Vec<_> = bookmarks.iter().map(start).collect();
+    let deadline = Instant::now() + timeout;
+    pending
+        .iter()
+        .map(|rx| {
+            rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .unwrap_or((Status::Unknown, None))
+        })
+        .collect()
+}
+
+/// The status of every bookmark, in order, as `probe_all` finds it.
+pub fn check_all(bookmarks: &[Bookmark], timeout: Duration) -> Vec<Status> {
+    probe_all(bookmarks, timeout).into_iter().map(|(status, _)| status).collect()
+}
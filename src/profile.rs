@@ -0,0 +1,58 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::error::{BmError, Result};
+use crate::paths::{data_dir, get_bookmark_path};
+
+/// Directory holding one `<name>.toml` bookmarks file per named profile.
+pub fn profiles_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join("profiles"))
+}
+
+/// Bookmarks file for `profile`; `None` is the default `bookmarks.toml`.
+pub fn profile_path(profile: Option<&str>) -> Result<PathBuf> {
+    let Some(name) = profile else {
+        return get_bookmark_path();
+    };
+    // The name becomes a file name, so it must not be able to escape the
+    // directory, nor name the archive of another profile.
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) || is_archive(name) {
+        return Err(BmError::InvalidProfile(name.to_string()));
+    }
+    Ok(profiles_dir()?.join(format!("{name}.toml")))
+}
+
+/// Whether `stem` is that of a profile's archive, `<name>.archive.toml`, see
+/// [`crate::archive::archive_path`].
+fn is_archive(stem: &str) -> bool {
+    stem.ends_with(".archive")
+}
+
+/// Names of the existing profiles, sorted.
+pub fn list_profiles() -> Result<Vec<String>> {
+    let dir = profiles_dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(BmError::io(&dir, e)),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|p| p.file_stem()?.to_str().map(str::to_string))
+        .filter(|name| !name.starts_with('.') && !is_archive(name))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// The profile after `current` in the cycle default → each named profile →
+/// default.
+pub fn next_profile(current: Option<&str>, names: &[String]) -> Option<String> {
+    let next = match current {
+        None => 0,
+        Some(name) => names.iter().position(|n| n == name)? + 1,
+    };
+    names.get(next).cloned()
+}
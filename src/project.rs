@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{BmError, Result};
+use crate::store::{parse_bookmarks, Bookmark, Kind};
+
+/// Name of the per-project bookmarks file looked up from the working directory.
+pub const PROJECT_FILE: &str = ".bm.toml";
+
+/// Shared bookmarks committed into a repository as `.bm.toml`.
+pub struct Project {
+    pub file: PathBuf,
+    /// Bookmarks with relative paths already resolved against the file's directory.
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// Finds the nearest `.bm.toml` in `start` or one of its parents, the same
+/// way git looks for `.git`.
+pub fn discover(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|file| file.is_file())
+}
+
+impl Project {
+    pub fn load(file: &Path) -> Result<Project> {
+        let content = fs::read_to_string(file).map_err(|e| BmError::io(file, e))?;
+        let root = file.parent().unwrap_or(Path::new("/"));
+        let bookmarks = parse_bookmarks(file, &content)?
+            .bookmarks
+            .into_iter()
+            .map(|mut b| {
+                // Paths are usually relative so the file works in every checkout.
+                if Path::new(&b.path).is_relative() && b.kind != Kind::Remote {
+                    b.path = root.join(&b.path).to_string_lossy().into_owned();
+                }
+                b
+            })
+            .collect();
+        Ok(Project { file: file.to_path_buf(), bookmarks })
+    }
+}
@@ -0,0 +1,79 @@
+use std::cmp::Ordering;
+
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::store::{Bookmark, unix_now};
+
+/// What makes a bookmark or visited directory rank above another.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Ranking {
+    /// The most recently used first.
+    #[default]
+    Recency,
+    /// The most often used first.
+    Frequency,
+    /// Uses that count for half as much every half-life, so a directory
+    /// used a lot last year ends up below one used a few times this week.
+    Frecency,
+}
+
+impl Ranking {
+    pub fn label(self) -> &'static str {
+        match self {
+            Ranking::Recency => "recency",
+            Ranking::Frequency => "frequency",
+            Ranking::Frecency => "frecency",
+        }
+    }
+}
+
+/// Orders by `ranking`, with the half-life frecency decays by.
+#[derive(Clone, Copy)]
+pub struct Ranker {
+    pub ranking: Ranking,
+    pub half_life_days: u64,
+}
+
+impl Default for Ranker {
+    fn default() -> Self {
+        Ranker::new(&Config::default())
+    }
+}
+
+impl Ranker {
+    pub fn new(config: &Config) -> Self {
+        Ranker { ranking: config.ranking, half_life_days: config.half_life_days }
+    }
+
+    /// How highly something used `hits` times, last at `last_used`, ranks at
+    /// `now`. Only scores of the same ranking compare.
+    pub fn score(&self, hits: u64, last_used: Option<u64>, now: u64) -> f64 {
+        match self.ranking {
+            Ranking::Recency => last_used.map_or(0.0, |t| t as f64),
+            Ranking::Frequency => hits as f64,
+            Ranking::Frecency => {
+                let Some(last) = last_used else {
+                    return 0.0;
+                };
+                let half_life = (self.half_life_days.max(1) * 24 * 60 * 60) as f64;
+                let age = now.saturating_sub(last) as f64;
+                hits.max(1) as f64 * (-age / half_life).exp2()
+            }
+        }
+    }
+
+    /// Whether `a` ranks before `b`, both given as hits and last use. Ties
+    /// go to the more recently used.
+    pub fn compare(&self, a: (u64, Option<u64>), b: (u64, Option<u64>), now: u64) -> Ordering {
+        let (a_score, b_score) = (self.score(a.0, a.1, now), self.score(b.0, b.1, now));
+        b_score.total_cmp(&a_score).then(b.1.cmp(&a.1))
+    }
+
+    /// Sorts `bookmarks` best ranked first.
+    pub fn sort(&self, bookmarks: &mut [Bookmark]) {
+        let now = unix_now();
+        bookmarks.sort_by(|a, b| self.compare((a.hits, a.last_used), (b.hits, b.last_used), now));
+    }
+}
@@ -0,0 +1,49 @@
+use std::process::Command;
+
+use crate::error::{BmError, Result};
+use crate::launch;
+
+/// The host and directory of a remote path written as `[user@]host:/dir`,
+/// the way `scp` takes it. A single letter before the colon is a Windows
+/// drive, not a host.
+pub fn split(path: &str) -> Option<(&str, &str)> {
+    let (host, dir) = path.split_once(':')?;
+    let is_host = host.len() > 1 && !host.contains(['/', '\\']) && !host.starts_with('@');
+    (is_host && !dir.is_empty()).then_some((host, dir))
+}
+
+/// `dir` in single quotes for the remote shell; a `~` at the start is left
+/// outside so the remote home directory is used.
+fn quote(dir: &str) -> String {
+    let (home, rest) = match dir.strip_prefix('~') {
+        Some(rest) => ("~", rest.strip_prefix('/').unwrap_or(rest)),
+        None => ("", dir),
+    };
+    let quoted = format!("'{}'", rest.replace('\'', "'\\''"));
+    match home {
+        "" => quoted,
+        _ if rest.is_empty() => home.to_string(),
+        _ => format!("{home}/{quoted}"),
+    }
+}
+
+/// Opens an interactive `ssh` session on the host of the remote `path`
+/// that starts the login shell in its directory, on this terminal, and
+/// waits for it to end.
+pub fn connect(path: &str) -> Result<()> {
+    let (host, dir) = split(path)
+        .ok_or_else(|| BmError::Unsupported(format!("{path} is not a remote path of the form [user@]host:/dir")))?;
+    let remote = format!("cd {} && exec $SHELL -l", quote(dir));
+    let err = |message: String| BmError::Launch { command: format!("ssh -t {host} \"{remote}\""), message };
+    let status = Command::new("ssh")
+        .args(["-t", host, &remote])
+        .stdout(launch::terminal_stdout())
+        .status()
+        .map_err(|e| err(e.to_string()))?;
+    // ssh exits with the status of the remote shell's last command, so only
+    // its own failures (255) are reported.
+    if status.code() == Some(255) {
+        return Err(err(format!("failed ({status})")));
+    }
+    Ok(())
+}
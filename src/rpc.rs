@@ -0,0 +1,225 @@
+use std::io::{BufRead, Write};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::add::{self, AddOptions};
+use crate::error::{BmError, Result};
+use crate::fuzzy;
+use crate::hooks::{Event, Hooks, Output};
+use crate::journal::{self, Source};
+use crate::store::{Bookmark, BookmarkStore};
+
+/// Error codes JSON-RPC defines.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// No bookmark has the name or matches the query.
+const NOT_FOUND: i64 = -32001;
+/// Anything else bm failed at, such as saving the file.
+const FAILED: i64 = -32000;
+
+/// A failed request: the JSON-RPC error code and message.
+struct Failure(i64, String);
+
+impl From<BmError> for Failure {
+    fn from(e: BmError) -> Self {
+        let code = match e {
+            BmError::UnknownBookmark(_) | BmError::NoMatch(_) => NOT_FOUND,
+            _ => FAILED,
+        };
+        Failure(code, e.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct AddParams {
+    path: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoveParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default = "visit_by_default")]
+    visit: bool,
+}
+
+fn visit_by_default() -> bool {
+    true
+}
+
+/// `b` as plugins see it: every field present, and the directory its path
+/// leads to, `null` if it names an unset variable.
+fn entry(b: &Bookmark) -> Value {
+    json!({
+        "name": b.name,
+        "path": b.path,
+        "target": b.target().ok(),
+        "kind": b.kind.as_str(),
+        "note": b.note,
+        "tags": b.tags,
+        "added": b.added,
+        "last_used": b.last_used,
+        "hits": b.hits,
+        "command": b.command,
+    })
+}
+
+/// Answers the requests of an editor plugin on the bookmarks of `store`.
+struct Server<'a> {
+    store: &'a dyn BookmarkStore,
+    options: &'a AddOptions,
+    hooks: &'a Hooks,
+}
+
+impl Server<'_> {
+    /// Saves `bookmarks`, logs how they differ from `base` and runs the
+    /// hooks for it, like the commands do.
+    fn save(&self, base: &[Bookmark], bookmarks: &[Bookmark]) -> Result<()> {
+        self.store.save_merged(base, bookmarks)?;
+        self.log(&journal::changes(base, bookmarks));
+        Ok(())
+    }
+
+    fn log(&self, changes: &[journal::Change]) {
+        if let Err(e) = journal::record(Source::Rpc, self.store.path(), changes) {
+            tracing::warn!("could not write the change log: {e}");
+        }
+        if let Err(e) = self.hooks.run_for_changes(changes, Output::Stderr) {
+            tracing::warn!("{e}");
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> std::result::Result<Value, Failure> {
+        match method {
+            "list" => Ok(Value::Array(self.store.load()?.iter().map(entry).collect())),
+            "add" => self.add(parse(params)?),
+            "remove" => self.remove(parse(params)?),
+            "resolve" => self.resolve(parse(params)?),
+            _ => Err(Failure(METHOD_NOT_FOUND, format!("no method `{method}`"))),
+        }
+    }
+
+    /// `add`: bookmarks `path`, under `name` if given, as `bm add` does.
+    /// Answers what was added, what was already bookmarked and what was
+    /// skipped and why.
+    fn add(&self, params: AddParams) -> std::result::Result<Value, Failure> {
+        let options = AddOptions {
+            tags: [self.options.tags.clone(), params.tags].concat(),
+            canonicalize: self.options.canonicalize,
+            suffix_names: self.options.suffix_names,
+            exclude: self.options.exclude.clone(),
+        };
+        let summary = add::add_named_paths(self.store, [(params.name.as_deref(), params.path.as_str())], &options)?;
+        self.log(&journal::changes(&[], &summary.added));
+        let skipped: Vec<Value> = summary.invalid.iter().map(|(path, reason)| json!({"path": path, "reason": reason})).collect();
+        Ok(json!({
+            "added": summary.added.iter().map(entry).collect::<Vec<_>>(),
+            "duplicates": summary.duplicates,
+            "skipped": skipped,
+        }))
+    }
+
+    /// `remove`: deletes the bookmark called `name` and answers it.
+    fn remove(&self, params: RemoveParams) -> std::result::Result<Value, Failure> {
+        let base = self.store.load()?;
+        let index = base.iter().position(|b| b.name == params.name);
+        let index = index.ok_or_else(|| BmError::UnknownBookmark(params.name.clone()))?;
+        let mut bookmarks = base.clone();
+        let removed = bookmarks.remove(index);
+        self.save(&base, &bookmarks)?;
+        Ok(entry(&removed))
+    }
+
+    /// `resolve`: the bookmark called `name`, or the one matching `query`
+    /// best as for `bm pick`. Unless `visit` is false the jump is counted
+    /// and the `pre_jump` hook runs, as for `bm get`.
+    fn resolve(&self, params: ResolveParams) -> std::result::Result<Value, Failure> {
+        let base = self.store.load()?;
+        let index = match (&params.name, &params.query) {
+            (Some(name), _) => base.iter().position(|b| &b.name == name).ok_or_else(|| BmError::UnknownBookmark(name.clone()))?,
+            (None, Some(query)) => fuzzy::best_match(query, &base).ok_or_else(|| BmError::NoMatch(query.clone()))?,
+            (None, None) => return Err(Failure(INVALID_PARAMS, "pass `name` or `query`".to_string())),
+        };
+        let mut bookmarks = base.clone();
+        let bookmark = &mut bookmarks[index];
+        if !params.visit {
+            return Ok(entry(bookmark));
+        }
+        let target = bookmark
+            .target()
+            .map_err(|var| BmError::UnsetVariable { path: bookmark.path.clone(), var })?;
+        bookmark.touch();
+        let visited = entry(bookmark);
+        if !self.store.is_read_only() {
+            self.save(&base, &bookmarks)?;
+        }
+        if let Err(e) = self.hooks.run(Event::PreJump, &bookmarks[index].name, &target, Output::Stderr) {
+            tracing::warn!("{e}");
+        }
+        Ok(visited)
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(params: Value) -> std::result::Result<T, Failure> {
+    // Methods whose parameters are all optional may be called without any.
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| Failure(INVALID_PARAMS, e.to_string()))
+}
+
+/// The response to one line of input, `None` for a notification.
+fn respond(server: &Server, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(failure(Value::Null, Failure(PARSE_ERROR, e.to_string()))),
+    };
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        let message = "a request is an object with a `method`; batches are not supported";
+        return Some(failure(id.unwrap_or(Value::Null), Failure(INVALID_REQUEST, message.to_string())));
+    };
+    tracing::debug!("rpc call `{method}`");
+    let result = server.call(method, request.get("params").cloned().unwrap_or(Value::Null));
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => failure(id, e),
+    })
+}
+
+fn failure(id: Value, Failure(code, message): Failure) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// Serves JSON-RPC 2.0 on the bookmarks of `store`, one request per line
+/// of `input` and one response per line of `output`, until `input` ends.
+/// The methods are `list`, `add`, `remove` and `resolve`; changes are
+/// logged and run the hooks in `hooks` as the commands do.
+pub fn serve(store: &dyn BookmarkStore, options: &AddOptions, hooks: &Hooks, input: impl BufRead, mut output: impl Write) -> Result<()> {
+    let server = Server { store, options, hooks };
+    for line in input.lines() {
+        let line = line.map_err(BmError::Terminal)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(response) = respond(&server, &line) else {
+            continue;
+        };
+        writeln!(output, "{response}").and_then(|_| output.flush()).map_err(BmError::Terminal)?;
+    }
+    Ok(())
+}
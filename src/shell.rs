@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::store::{Bookmark, Kind};
+
+/// Shells `bm init` can write wrapper functions for.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Nu,
+}
+
+const POSIX_INIT: &str = r#"# Pick a bookmark in the UI and change to it; file paths are printed
+bmgo() {
+  local dir
+  dir="$(command bm "$@")" && [ -n "$dir" ] || return
+  if [ -d "$dir" ]; then cd "$dir"; else printf '%s\n' "$dir"; fi
+}
+
+# Change to a bookmark, remembering the current directory
+bmpush() {
+  local dir
+  dir="$(command bm push "$@")" && cd "$dir"
+}
+
+# Go back to the directory saved by the last bmpush
+bmpop() {
+  local dir
+  dir="$(command bm pop)" && cd "$dir"
+}
+"#;
+
+const FISH_INIT: &str = r#"# Pick a bookmark in the UI and change to it; file paths are printed
+function bmgo
+    set -l dir (command bm $argv); and test -n "$dir"; or return
+    if test -d $dir; cd $dir; else; echo $dir; end
+end
+
+# Change to a bookmark, remembering the current directory
+function bmpush
+    set -l dir (command bm push $argv); and cd $dir
+end
+
+# Go back to the directory saved by the last bmpush
+function bmpop
+    set -l dir (command bm pop); and cd $dir
+end
+"#;
+
+const POWERSHELL_INIT: &str = r#"# Pick a bookmark in the UI and change to it; file paths are printed
+function bmgo {
+    $dir = bm @args
+    if ($LASTEXITCODE -ne 0 -or -not $dir) { return }
+    if (Test-Path -LiteralPath $dir -PathType Container) { Set-Location -LiteralPath $dir } else { $dir }
+}
+
+# Change to a bookmark, remembering the current directory
+function bmpush {
+    $dir = bm push @args
+    if ($LASTEXITCODE -eq 0) { Set-Location -LiteralPath $dir }
+}
+
+# Go back to the directory saved by the last bmpush
+function bmpop {
+    $dir = bm pop
+    if ($LASTEXITCODE -eq 0) { Set-Location -LiteralPath $dir }
+}
+"#;
+
+// `def --env` lets a custom command change the caller's directory, which a
+// plain `def` can't.
+const NU_INIT: &str = r#"# Pick a bookmark in the UI and change to it; file paths are printed
+def --env bmgo [...args: string] {
+    let dir = (^bm ...$args | str trim)
+    if ($dir | is-empty) { return }
+    if ($dir | path type) == "dir" { cd $dir } else { $dir }
+}
+
+# Change to a bookmark, remembering the current directory
+def --env bmpush [name: string] {
+    cd (^bm push $name | str trim)
+}
+
+# Go back to the directory saved by the last bmpush
+def --env bmpop [] {
+    cd (^bm pop | str trim)
+}
+
+# The bookmarks as a table
+def bmls [] {
+    ^bm list --format nuon | from nuon
+}
+"#;
+
+// The directory is compared with the last one seen, as PROMPT_COMMAND runs
+// before every prompt rather than on each `cd`.
+const BASH_HOOK: &str = r#"# Count every directory changed to with `bm track`
+__bm_track() {
+  [ "$PWD" = "${__bm_last_dir-}" ] && return
+  __bm_last_dir="$PWD"
+  command bm track -- "$PWD" >/dev/null 2>&1
+}
+case ";${PROMPT_COMMAND-};" in
+  *";__bm_track;"*) ;;
+  *) PROMPT_COMMAND="__bm_track${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+"#;
+
+const ZSH_HOOK: &str = r#"# Count every directory changed to with `bm track`
+__bm_track() {
+  command bm track -- "$PWD" >/dev/null 2>&1
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd __bm_track
+"#;
+
+const FISH_HOOK: &str = r#"# Count every directory changed to with `bm track`
+function __bm_track --on-variable PWD
+    command bm track -- "$PWD" >/dev/null 2>&1
+end
+"#;
+
+const POWERSHELL_HOOK: &str = r#"# Count every directory changed to with `bm track`
+$global:BmPrompt = $function:prompt
+$global:BmLastDir = $null
+function global:prompt {
+    $dir = (Get-Location -PSProvider FileSystem).ProviderPath
+    if ($dir -ne $global:BmLastDir) {
+        $global:BmLastDir = $dir
+        bm track -- $dir *> $null
+    }
+    & $global:BmPrompt
+}
+"#;
+
+const NU_HOOK: &str = r#"# Count every directory changed to with `bm track`
+$env.config.hooks.env_change.PWD = ($env.config.hooks.env_change.PWD? | default [] | append {|before, after|
+    ^bm track -- $after | complete | ignore
+})
+"#;
+
+/// A hook that runs `bm track` whenever the shell changes directory, for
+/// the visit counts behind the UI's suggestions.
+pub fn hook_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH_HOOK,
+        Shell::Zsh => ZSH_HOOK,
+        Shell::Fish => FISH_HOOK,
+        Shell::Powershell => POWERSHELL_HOOK,
+        Shell::Nu => NU_HOOK,
+    }
+}
+
+/// Shell functions that change to what bm prints, meant to be evaluated
+/// from the shell's startup file.
+pub fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash | Shell::Zsh => POSIX_INIT,
+        Shell::Fish => FISH_INIT,
+        Shell::Powershell => POWERSHELL_INIT,
+        Shell::Nu => NU_INIT,
+    }
+}
+
+/// `path` in double quotes for a POSIX shell. Only the `$NAME` and
+/// `${NAME}` variables bm itself expands are left for the shell to expand;
+/// every other `$`, as in `$(...)`, is escaped so it can't run anything.
+fn quote(path: &str) -> String {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut quoted = String::from("\"");
+    for (i, c) in path.char_indices() {
+        if c == '$' {
+            let rest = &path[i + 1..];
+            let braced = rest
+                .strip_prefix('{')
+                .and_then(|r| r.split_once('}'))
+                .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(is_name));
+            if !braced && !rest.starts_with(is_name) {
+                quoted.push('\\');
+            }
+        } else if matches!(c, '"' | '\\' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// An `export CDPATH=...` line listing `.` and the parent directory of
+/// every local bookmark, so `cd NAME` works from anywhere when NAME is the
+/// last component of a bookmarked path.
+pub fn cdpath_export(bookmarks: &[Bookmark]) -> String {
+    let mut parents: Vec<&str> = vec!["."];
+    for b in bookmarks.iter().filter(|b| b.kind != Kind::Remote) {
+        let parent = Path::new(&b.path).parent().and_then(Path::to_str).filter(|p| !p.is_empty());
+        if let Some(parent) = parent
+            && !parents.contains(&parent)
+        {
+            parents.push(parent);
+        }
+    }
+    format!("export CDPATH={}\n", quote(&parents.join(":")))
+}
+
+/// The environment variable `bm export --format env` uses for a bookmark:
+/// `BM_` and the name in upper case, with anything but letters and digits
+/// replaced by `_`.
+pub fn env_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("BM_{name}")
+}
+
+/// One `export BM_NAME="path"` line per bookmark.
+pub fn env_exports(bookmarks: &[Bookmark]) -> String {
+    definitions(bookmarks, env_name, |var, path| format!("export {var}={}", quote(path)))
+}
+
+/// The alias `bm export --format aliases` defines for a bookmark: `cd`
+/// and the name, with anything a shell doesn't allow in an alias replaced
+/// by `_`.
+pub fn alias_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') { c } else { '_' })
+        .collect();
+    format!("cd{name}")
+}
+
+/// One `alias cdNAME='cd "path"'` line per local bookmark. The path is
+/// quoted as for `env_exports`, so using the alias can't run a command
+/// hidden in a directory name.
+pub fn alias_definitions(bookmarks: &[Bookmark]) -> String {
+    let local: Vec<Bookmark> = bookmarks.iter().filter(|b| b.kind != Kind::Remote).cloned().collect();
+    definitions(&local, alias_name, |alias, path| {
+        let command = format!("cd {}", quote(path)).replace('\'', "'\\''");
+        format!("alias {alias}='{command}'")
+    })
+}
+
+/// A line from `define` for every bookmark, under the name `name` gives
+/// it. Bookmarks whose name maps to one already defined by an earlier
+/// bookmark are left out with a comment.
+fn definitions(
+    bookmarks: &[Bookmark],
+    name: impl Fn(&str) -> String,
+    define: impl Fn(&str, &str) -> String,
+) -> String {
+    let mut used = Vec::new();
+    let mut out = String::new();
+    for b in bookmarks {
+        let defined = name(&b.name);
+        if used.contains(&defined) {
+            out.push_str(&format!("# {}: {defined} is already defined by another bookmark\n", b.name));
+            continue;
+        }
+        out.push_str(&define(&defined, &b.path));
+        out.push('\n');
+        used.push(defined);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_escapes_what_the_shell_would_run_or_end_on() {
+        assert_eq!(quote("/srv/$(touch pwned)"), r#""/srv/\$(touch pwned)""#);
+        assert_eq!(quote("/srv/`touch pwned`"), r#""/srv/\`touch pwned\`""#);
+        assert_eq!(quote(r#"/srv/say "hi""#), r#""/srv/say \"hi\"""#);
+        assert_eq!(quote(r"/srv/back\slash"), r#""/srv/back\\slash""#);
+        assert_eq!(quote("/srv/costs $"), r#""/srv/costs \$""#);
+    }
+
+    #[test]
+    fn quote_leaves_plain_variables_to_the_shell() {
+        assert_eq!(quote("$HOME/src"), r#""$HOME/src""#);
+        assert_eq!(quote("${HOME}/src"), r#""${HOME}/src""#);
+        assert_eq!(quote("/a/$WORK_DIR2/b"), r#""/a/$WORK_DIR2/b""#);
+    }
+
+    #[test]
+    fn quote_escapes_expansions_hidden_in_braces() {
+        assert_eq!(quote("${X:-$(touch pwned)}"), r#""\${X:-\$(touch pwned)}""#);
+        assert_eq!(quote("${}"), r#""\${}""#);
+    }
+
+    #[test]
+    fn aliases_quote_the_path_inside_single_quotes() {
+        let bookmarks = [
+            Bookmark::new("work".to_string(), "$HOME/it's $(touch pwned)".to_string()),
+            Bookmark::new("plain".to_string(), "/srv".to_string()),
+        ];
+        assert_eq!(
+            alias_definitions(&bookmarks),
+            concat!(
+                r#"alias cdwork='cd "$HOME/it'\''s \$(touch pwned)"'"#,
+                "\n",
+                r#"alias cdplain='cd "/srv"'"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn env_and_cdpath_exports_use_the_same_quoting() {
+        let bookmarks = [Bookmark::new("my app".to_string(), "/srv/`id`/app".to_string())];
+        assert_eq!(env_exports(&bookmarks), "export BM_MY_APP=\"/srv/\\`id\\`/app\"\n");
+        assert_eq!(cdpath_export(&bookmarks), "export CDPATH=\".:/srv/\\`id\\`\"\n");
+    }
+}
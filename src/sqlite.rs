@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::{Connection, TransactionBehavior, params};
+
+use crate::config::Config;
+use crate::error::{BmError, Result};
+use crate::paths::{Remap, expand_tilde};
+use crate::store::{Bookmark, BookmarkStore, Kind, for_disk, from_disk, make_names_unique, merge_bookmarks};
+
+/// How long to wait for another instance's write transaction before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bookmarks kept in an SQLite database, for collections large enough that
+/// parsing TOML on every start becomes noticeable.
+pub struct SqliteStore {
+    path: PathBuf,
+    /// Write paths under the home directory as `~/...`.
+    home_relative: bool,
+    /// Translate Windows drive paths between WSL and Windows.
+    wsl_paths: bool,
+    /// Directories that are elsewhere on this machine than in the database.
+    remap: Remap,
+}
+
+impl SqliteStore {
+    pub fn new(path: PathBuf, config: &Config) -> Self {
+        SqliteStore {
+            path,
+            home_relative: config.home_relative,
+            wsl_paths: config.wsl_paths,
+            remap: config.remap.clone(),
+        }
+    }
+
+    fn db_err(&self, e: rusqlite::Error) -> BmError {
+        BmError::Database { path: self.path.clone(), message: e.to_string() }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| BmError::io(dir, e))?;
+        }
+        let conn = Connection::open(&self.path).map_err(|e| self.db_err(e))?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| self.db_err(e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS bookmarks (
+                position INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                path TEXT NOT NULL,
+                note TEXT NOT NULL DEFAULT '',
+                tags TEXT NOT NULL DEFAULT '[]',
+                added INTEGER,
+                last_used INTEGER,
+                hits INTEGER NOT NULL DEFAULT 0,
+                workspace TEXT NOT NULL DEFAULT '[]',
+                command TEXT,
+                kind TEXT NOT NULL DEFAULT 'dir'
+            )",
+        )
+        .map_err(|e| self.db_err(e))?;
+        // Databases created by older versions lack the newer columns.
+        let columns = [
+            ("tags", "TEXT NOT NULL DEFAULT '[]'"),
+            ("added", "INTEGER"),
+            ("last_used", "INTEGER"),
+            ("hits", "INTEGER NOT NULL DEFAULT 0"),
+            ("workspace", "TEXT NOT NULL DEFAULT '[]'"),
+            ("command", "TEXT"),
+            ("kind", "TEXT NOT NULL DEFAULT 'dir'"),
+        ];
+        for (column, definition) in columns {
+            let exists: bool = conn
+                .query_row(
+                    "SELECT COUNT(*) > 0 FROM pragma_table_info('bookmarks') WHERE name = ?1",
+                    [column],
+                    |row| row.get(0),
+                )
+                .map_err(|e| self.db_err(e))?;
+            if !exists {
+                conn.execute(&format!("ALTER TABLE bookmarks ADD COLUMN {column} {definition}"), [])
+                    .map_err(|e| self.db_err(e))?;
+            }
+        }
+        Ok(conn)
+    }
+}
+
+fn read_all(conn: &Connection) -> rusqlite::Result<Vec<Bookmark>> {
+    let mut stmt =
+        conn.prepare("SELECT name, path, note, tags, added, last_used, hits, workspace, command, kind FROM bookmarks ORDER BY position")?;
+    stmt.query_map([], |row| {
+        // Lists are stored as JSON arrays.
+        let list = |column: usize| -> rusqlite::Result<Vec<String>> {
+            let json: String = row.get(column)?;
+            serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, e.into()))
+        };
+        let tags = list(3)?;
+        let path: String = row.get(1)?;
+        let added: Option<i64> = row.get(4)?;
+        let last_used: Option<i64> = row.get(5)?;
+        let hits: i64 = row.get(6)?;
+        Ok(Bookmark {
+            name: row.get(0)?,
+            path: expand_tilde(&path),
+            note: row.get(2)?,
+            tags,
+            added: added.map(|t| t as u64),
+            last_used: last_used.map(|t| t as u64),
+            hits: hits as u64,
+            workspace: list(7)?,
+            command: row.get(8)?,
+            kind: match row.get::<_, String>(9)?.as_str() {
+                "file" => Kind::File,
+                "remote" => Kind::Remote,
+                _ => Kind::Dir,
+            },
+        })
+    })?
+    .collect()
+}
+
+fn write_all(conn: &Connection, bookmarks: &[Bookmark]) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM bookmarks", [])?;
+    let mut stmt = conn.prepare(
+        "INSERT INTO bookmarks (position, name, path, note, tags, added, last_used, hits, workspace, command, kind)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+    )?;
+    for (position, b) in bookmarks.iter().enumerate() {
+        let list = |values: &[String]| serde_json::to_string(values).unwrap_or_else(|_| "[]".to_string());
+        stmt.execute(params![
+            position as i64,
+            b.name,
+            b.path,
+            b.note,
+            list(&b.tags),
+            b.added.map(|t| t as i64),
+            b.last_used.map(|t| t as i64),
+            b.hits as i64,
+            list(&b.workspace),
+            b.command,
+            b.kind.as_str()
+        ])?;
+    }
+    Ok(())
+}
+
+impl BookmarkStore for SqliteStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> Result<Vec<Bookmark>> {
+        let bookmarks = read_all(&self.connect()?).map_err(|e| self.db_err(e))?;
+        Ok(from_disk(bookmarks, self.wsl_paths, &self.remap))
+    }
+
+    /// The immediate transaction takes the database's write lock, which plays
+    /// the role of the lock file used for TOML.
+    fn save_merged(&self, base: &[Bookmark], ours: &[Bookmark]) -> Result<(Vec<Bookmark>, bool)> {
+        let mut conn = self.connect()?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|e| self.db_err(e))?;
+        let theirs = from_disk(read_all(&tx).map_err(|e| self.db_err(e))?, self.wsl_paths, &self.remap);
+        let (mut merged, was_merged) = if theirs == base {
+            (ours.to_vec(), false)
+        } else {
+            (merge_bookmarks(base, ours, &theirs), true)
+        };
+        make_names_unique(&mut merged);
+        write_all(&tx, &for_disk(&merged, self.home_relative, self.wsl_paths, &self.remap)).map_err(|e| self.db_err(e))?;
+        tx.commit().map_err(|e| self.db_err(e))?;
+        Ok((merged, was_merged))
+    }
+
+    fn with_path(&self, path: PathBuf) -> Box<dyn BookmarkStore> {
+        Box::new(SqliteStore { path, remap: self.remap.clone(), ..*self })
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::history::{read_lines, write_lines};
+use crate::paths::data_dir;
+
+/// Name of the directory stack inside the data directory.
+const STACK_FILE: &str = "stack";
+
+/// The directory stack used by `bm push` and `bm pop`, one directory per
+/// line with the top last.
+pub fn stack_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join(STACK_FILE))
+}
+
+/// The stack from bottom to top.
+pub fn load() -> Result<Vec<String>> {
+    read_lines(&stack_path()?)
+}
+
+pub fn push(dir: &str) -> Result<()> {
+    let mut dirs = load()?;
+    dirs.push(dir.to_string());
+    write_lines(&stack_path()?, &dirs)
+}
+
+/// Removes and returns the top of the stack, `None` when it is empty.
+pub fn pop() -> Result<Option<String>> {
+    let mut dirs = load()?;
+    let top = dirs.pop();
+    if top.is_some() {
+        write_lines(&stack_path()?, &dirs)?;
+    }
+    Ok(top)
+}
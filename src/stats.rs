@@ -0,0 +1,92 @@
+use crate::probe::Status;
+use crate::store::Bookmark;
+
+/// How many entries the most and least used lists show.
+const RANKED: usize = 5;
+/// How many weeks back the activity histogram goes.
+const WEEKS: usize = 8;
+const WEEK: u64 = 7 * 86_400;
+
+/// Summary of a bookmark list, shown by `bm stats` and the `S` screen.
+pub struct Stats {
+    pub total: usize,
+    /// Paths that are not an existing directory, or use an unset variable.
+    pub dead: Vec<String>,
+    /// Paths whose check timed out, e.g. on a network mount that is down.
+    pub unreachable: Vec<String>,
+    /// Name and hits of the most jumped-to bookmarks, busiest first.
+    pub most_used: Vec<(String, u64)>,
+    /// Name and hits of the least jumped-to bookmarks, quietest first.
+    pub least_used: Vec<(String, u64)>,
+    /// Every tag with the number of bookmarks carrying it, most common first.
+    pub tags: Vec<(String, usize)>,
+    /// Bookmarks whose last jump fell into each of the past weeks, this
+    /// week first. Only the latest jump of each bookmark is recorded.
+    pub weekly: Vec<usize>,
+}
+
+impl Stats {
+    /// `status` holds what [`crate::probe::check_all`] found for each of
+    /// the `bookmarks`.
+    pub fn new(bookmarks: &[Bookmark], status: &[Status], now: u64) -> Self {
+        let with_status = |wanted: Status| -> Vec<String> {
+            bookmarks
+                .iter()
+                .zip(status)
+                .filter(|&(_, &s)| s == wanted)
+                .map(|(b, _)| b.path.clone())
+                .collect()
+        };
+        let dead = with_status(Status::Missing);
+        let unreachable = with_status(Status::Unknown);
+
+        let mut by_hits: Vec<&Bookmark> = bookmarks.iter().collect();
+        by_hits.sort_by_key(|b| std::cmp::Reverse(b.hits));
+        let ranked = |b: &&Bookmark| (b.name.clone(), b.hits);
+        let most_used = by_hits.iter().filter(|b| b.hits > 0).take(RANKED).map(ranked).collect();
+        let least_used = by_hits.iter().rev().take(RANKED).map(ranked).collect();
+
+        let mut tags: Vec<(String, usize)> = Vec::new();
+        for tag in bookmarks.iter().flat_map(|b| &b.tags) {
+            match tags.iter_mut().find(|(t, _)| t == tag) {
+                Some((_, count)) => *count += 1,
+                None => tags.push((tag.clone(), 1)),
+            }
+        }
+        tags.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+        let mut weekly = vec![0; WEEKS];
+        for used in bookmarks.iter().filter_map(|b| b.last_used) {
+            if let Some(week) = weekly.get_mut((now.saturating_sub(used) / WEEK) as usize) {
+                *week += 1;
+            }
+        }
+
+        Stats { total: bookmarks.len(), dead, unreachable, most_used, least_used, tags, weekly }
+    }
+
+    /// The summary as plain text lines.
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec![format!("bookmarks: {}", self.total), format!("dead paths: {}", self.dead.len())];
+        lines.extend(self.dead.iter().map(|path| format!("  {path}")));
+        if !self.unreachable.is_empty() {
+            lines.push(format!("unknown (check timed out): {}", self.unreachable.len()));
+            lines.extend(self.unreachable.iter().map(|path| format!("  {path}")));
+        }
+        let ranked = |(name, hits): &(String, u64)| format!("  {hits:>5}  {name}");
+        lines.push("most used:".to_string());
+        lines.extend(self.most_used.iter().map(ranked));
+        lines.push("least used:".to_string());
+        lines.extend(self.least_used.iter().map(ranked));
+        lines.push("tags:".to_string());
+        lines.extend(self.tags.iter().map(|(tag, count)| format!("  {count:>5}  #{tag}")));
+        lines.push("last jumps per week:".to_string());
+        let busiest = self.weekly.iter().copied().max().unwrap_or(0).max(1);
+        lines.extend(self.weekly.iter().enumerate().map(|(week, &count)| {
+            let label = if week == 0 { "this week".to_string() } else { format!("{week}w ago") };
+            // Bars are scaled to the busiest week, at most 40 wide.
+            format!("  {label:>9}  {count:>3} {}", "#".repeat(count * 40 / busiest)).trim_end().to_string()
+        }));
+        lines
+    }
+}
@@ -0,0 +1,764 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "encryption")]
+use crate::age::{decrypt, encrypt};
+use crate::config::Config;
+use crate::error::{BmError, Result};
+use crate::format::{self, Format};
+use crate::migrate::{self, SCHEMA_VERSION};
+use crate::paths::{Remap, contract_tilde, expand_vars};
+use crate::sync;
+use crate::wsl;
+
+/// What a bookmark points at.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    #[default]
+    Dir,
+    File,
+    /// A directory on another machine, written `[user@]host:/dir`.
+    Remote,
+}
+
+impl Kind {
+    fn is_dir(&self) -> bool {
+        *self == Kind::Dir
+    }
+
+    /// The kind of what is at `path` now, `None` when nothing is.
+    pub fn of(path: &Path) -> Option<Kind> {
+        let meta = fs::metadata(path).ok()?;
+        Some(if meta.is_dir() { Kind::Dir } else { Kind::File })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Dir => "dir",
+            Kind::File => "file",
+            Kind::Remote => "remote",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub path: String,
+    /// Directories are jumped to, files are printed or opened, and remote
+    /// directories are logged into with `ssh`.
+    #[serde(default, skip_serializing_if = "Kind::is_dir")]
+    pub kind: Kind,
+    /// Free-form reminder of why the bookmark exists.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// When the bookmark was created, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added: Option<u64>,
+    /// When the bookmark was last jumped to, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+    /// How many times the bookmark was jumped to.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hits: u64,
+    /// Commands started in their own tmux pane, in the bookmark's directory,
+    /// when it is opened in tmux.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workspace: Vec<String>,
+    /// Shell command `x` runs instead of printing the path, with `{path}`
+    /// replaced by the directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl Bookmark {
+    /// A bookmark created just now.
+    pub fn new(name: String, path: String) -> Self {
+        Bookmark {
+            name,
+            path,
+            kind: Kind::Dir,
+            note: String::new(),
+            tags: Vec::new(),
+            added: Some(unix_now()),
+            last_used: None,
+            hits: 0,
+            workspace: Vec::new(),
+            command: None,
+        }
+    }
+
+    /// Records that the bookmark was just used.
+    pub fn touch(&mut self) {
+        self.last_used = Some(unix_now());
+        self.hits += 1;
+    }
+
+    /// The directory to jump to: `path` with environment variables such as
+    /// `$PROJECTS` expanded. Fails with the name of an unset variable.
+    pub fn target(&self) -> Result<String, String> {
+        expand_vars(&self.path)
+    }
+
+    /// The bookmark's command with `{path}` replaced by `target`, as is.
+    pub fn command_line(&self, target: &str) -> Option<String> {
+        Some(self.command.as_ref()?.replace("{path}", target))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BookmarkFile {
+    /// Layout version; older files are migrated when they are read.
+    #[serde(default = "migrate::implicit_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    /// Version the file had on disk when it had to be migrated while reading.
+    #[serde(skip)]
+    pub migrated_from: Option<u32>,
+}
+
+impl BookmarkFile {
+    pub fn new(bookmarks: Vec<Bookmark>) -> Self {
+        BookmarkFile { version: SCHEMA_VERSION, bookmarks, migrated_from: None }
+    }
+}
+
+/// Parses the contents of the bookmarks file at `path` in the format its
+/// extension names; the error message carries the line and column of the problem.
+pub fn parse_bookmarks(path: &Path, content: &str) -> Result<BookmarkFile> {
+    Format::from_path(path).parse(path, content)
+}
+
+/// Advisory lock on the bookmarks file shared by every bm process; dropping it
+/// releases the lock.
+pub struct FileLock {
+    _file: fs::File,
+}
+
+/// Where bookmarks are kept. The UI only goes through this trait, so it works
+/// the same on every backend.
+pub trait BookmarkStore {
+    /// The file backing the store, shown in messages and watched for changes.
+    fn path(&self) -> &Path;
+
+    fn load(&self) -> Result<Vec<Bookmark>>;
+
+    /// Saves `ours`, merging in whatever changed since `base` was loaded if
+    /// another instance saved in the meantime. Returns the list that was
+    /// written and whether a merge took place.
+    fn save_merged(&self, base: &[Bookmark], ours: &[Bookmark]) -> Result<(Vec<Bookmark>, bool)>;
+
+    /// Replaces a store that fails to load with its newest usable backup.
+    /// Returns the backup that was used, or `None` if there is none.
+    fn restore_from_backup(&self) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    /// Signals whenever the backing file changes on disk.
+    fn watch(&self) -> notify::Result<(RecommendedWatcher, Receiver<()>)> {
+        watch_file(self.path())
+    }
+
+    /// A store of the same kind and settings for another file.
+    fn with_path(&self, path: PathBuf) -> Box<dyn BookmarkStore>;
+
+    /// Whether every write is refused, see [`ReadOnlyStore`].
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Another store with its writes refused, for `--read-only`: loading and
+/// watching work as usual, saving fails with [`BmError::ReadOnly`].
+pub struct ReadOnlyStore(pub Box<dyn BookmarkStore>);
+
+impl BookmarkStore for ReadOnlyStore {
+    fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    fn load(&self) -> Result<Vec<Bookmark>> {
+        self.0.load()
+    }
+
+    fn save_merged(&self, _base: &[Bookmark], _ours: &[Bookmark]) -> Result<(Vec<Bookmark>, bool)> {
+        Err(BmError::ReadOnly(self.path().to_path_buf()))
+    }
+
+    fn restore_from_backup(&self) -> Result<Option<PathBuf>> {
+        Err(BmError::ReadOnly(self.path().to_path_buf()))
+    }
+
+    fn watch(&self) -> notify::Result<(RecommendedWatcher, Receiver<()>)> {
+        self.0.watch()
+    }
+
+    fn with_path(&self, path: PathBuf) -> Box<dyn BookmarkStore> {
+        Box::new(ReadOnlyStore(self.0.with_path(path)))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+/// `name`, or the first of `name-2`, `name-3`, ... that no bookmark in
+/// `bookmarks` uses yet.
+pub fn unique_name(bookmarks: &[Bookmark], name: &str) -> String {
+    let taken = |candidate: &str| bookmarks.iter().any(|b| b.name == candidate);
+    if !taken(name) {
+        return name.to_string();
+    }
+    (2..).map(|n| format!("{name}-{n}")).find(|candidate| !taken(candidate)).expect("unbounded range")
+}
+
+/// Renames every bookmark whose name an earlier one already uses, so names
+/// work as jump keys. Run before every save, since a merge with another
+/// instance's changes can bring in a second bookmark of the same name.
+pub fn make_names_unique(bookmarks: &mut [Bookmark]) {
+    for i in 1..bookmarks.len() {
+        if bookmarks[..i].iter().any(|b| b.name == bookmarks[i].name) {
+            // Later entries may hold suffixed names too, so check them all.
+            let name = unique_name(bookmarks, &bookmarks[i].name);
+            bookmarks[i].name = name;
+        }
+    }
+}
+
+/// `bookmarks` as they are written to disk: directories in `remap` are put
+/// back the way the file has them, with `home_relative` paths under the
+/// home directory become `~/...`, and with `wsl_paths` Windows drive mounts
+/// become `C:\...`. Loading undoes all three, see [`from_disk`].
+pub(crate) fn for_disk(bookmarks: &[Bookmark], home_relative: bool, wsl_paths: bool, remap: &Remap) -> Vec<Bookmark> {
+    let mut bookmarks = bookmarks.to_vec();
+    for b in bookmarks.iter_mut().filter(|b| b.kind != Kind::Remote) {
+        b.path = remap.to_shared(&b.path);
+        if home_relative {
+            b.path = contract_tilde(&b.path);
+        }
+        if wsl_paths {
+            b.path = wsl::portable(&b.path);
+        }
+    }
+    bookmarks
+}
+
+/// Turns paths read from disk into this system's form: with `wsl_paths`
+/// `C:\...` inside WSL and `/mnt/c/...` on Windows, and directories in
+/// `remap` where this machine has them. `~` is expanded by the parser.
+pub(crate) fn from_disk(mut bookmarks: Vec<Bookmark>, wsl_paths: bool, remap: &Remap) -> Vec<Bookmark> {
+    for b in bookmarks.iter_mut().filter(|b| b.kind != Kind::Remote) {
+        if wsl_paths {
+            b.path = wsl::native(&b.path);
+        }
+        b.path = remap.to_local(&b.path);
+    }
+    bookmarks
+}
+
+/// Whether `path` names an SQLite database rather than a bookmarks file.
+pub fn is_sqlite(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext, "db" | "sqlite" | "sqlite3"))
+}
+
+/// Whether `path` names an age-encrypted bookmarks file, such as
+/// `bookmarks.toml.age`.
+pub fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "age")
+}
+
+/// The format of the bookmarks file at `path`; for an encrypted file, the
+/// one of the extension in front of `.age`.
+fn format_of(path: &Path) -> Format {
+    if is_encrypted(path) {
+        Format::from_path(&path.with_extension(""))
+    } else {
+        Format::from_path(path)
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encryption_unsupported(path: &Path) -> BmError {
+    BmError::Unsupported(format!(
+        "{}: bm was built without encryption support (enable the `encryption` feature)",
+        path.display()
+    ))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn decrypt(path: &Path) -> Result<String> {
+    Err(encryption_unsupported(path))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encrypt(path: &Path, _content: &str) -> Result<Vec<u8>> {
+    Err(encryption_unsupported(path))
+}
+
+/// Opens the store for `path`, picking the backend from the file extension.
+pub fn open(path: PathBuf, config: &Config) -> Result<Box<dyn BookmarkStore>> {
+    let store = open_writable(path, config)?;
+    Ok(if config.read_only { Box::new(ReadOnlyStore(store)) } else { store })
+}
+
+fn open_writable(path: PathBuf, config: &Config) -> Result<Box<dyn BookmarkStore>> {
+    if is_sqlite(&path) {
+        #[cfg(feature = "sqlite")]
+        return Ok(Box::new(crate::sqlite::SqliteStore::new(path, config)));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(BmError::Unsupported(format!(
+            "{}: bm was built without SQLite support (enable the `sqlite` feature)",
+            path.display()
+        )));
+    }
+    #[cfg(not(feature = "encryption"))]
+    if is_encrypted(&path) {
+        return Err(encryption_unsupported(&path));
+    }
+    Ok(Box::new(FileStore::new(path, config)))
+}
+
+/// A TOML, JSON or YAML bookmarks file on disk together with the settings
+/// used to save it.
+pub struct FileStore {
+    path: PathBuf,
+    format: Format,
+    /// The file is encrypted with age, see [`is_encrypted`].
+    encrypted: bool,
+    /// Number of backups kept in the `backups/` directory next to the file;
+    /// older ones are deleted on save.
+    max_backups: usize,
+    /// Write paths under the home directory as `~/...`.
+    home_relative: bool,
+    /// Translate Windows drive paths between WSL and Windows.
+    wsl_paths: bool,
+    /// Directories that are elsewhere on this machine than in the file.
+    remap: Remap,
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf, config: &Config) -> Self {
+        FileStore {
+            format: format_of(&path),
+            encrypted: is_encrypted(&path),
+            path,
+            max_backups: config.backups,
+            home_relative: config.home_relative,
+            wsl_paths: config.wsl_paths,
+            remap: config.remap.clone(),
+        }
+    }
+
+    /// Blocks until no other bm instance is reading or writing the bookmarks file.
+    pub fn lock(&self) -> Result<FileLock> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| BmError::io(dir, e))?;
+        }
+        let lock_path = with_suffix(&self.path, ".lock");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| BmError::io(&lock_path, e))?;
+        file.lock().map_err(|e| BmError::io(&lock_path, e))?;
+        Ok(FileLock { _file: file })
+    }
+
+    /// The text of the bookmarks file at `path`, decrypted if the store is
+    /// encrypted, or `None` if there is no such file.
+    fn read_text(&self, path: &Path) -> Result<Option<String>> {
+        match fs::read(path) {
+            Ok(_) if self.encrypted => decrypt(path).map(Some),
+            Ok(data) => String::from_utf8(data)
+                .map(Some)
+                .map_err(|e| BmError::io(path, io::Error::new(io::ErrorKind::InvalidData, e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(BmError::io(path, e)),
+        }
+    }
+
+    /// Reads the bookmarks file; a missing file is an empty list, but a file that
+    /// fails to parse is an error so that it never gets overwritten by a save.
+    fn read(&self) -> Result<BookmarkFile> {
+        match self.read_text(&self.path)? {
+            Some(content) => self.format.parse(&self.path, &content).map(|mut file| {
+                file.bookmarks = from_disk(file.bookmarks, self.wsl_paths, &self.remap);
+                file
+            }),
+            None => Ok(BookmarkFile::new(Vec::new())),
+        }
+    }
+
+    /// Keeps a copy of a file in an older schema version as
+    /// `backups/<stem>.v<version>.<ext>` before it is first saved in the new
+    /// layout. Unlike the regular backups it is never pruned.
+    fn backup_before_migration(&self, version: u32) -> Result<()> {
+        let dir = self.backup_dir();
+        fs::create_dir_all(&dir).map_err(|e| BmError::io(&dir, e))?;
+        let stem = self.path.file_stem().and_then(|n| n.to_str()).unwrap_or("bookmarks");
+        let ext = self.path.extension().and_then(|n| n.to_str()).unwrap_or("toml");
+        let backup = dir.join(format!("{stem}.v{version}.{ext}"));
+        if backup.exists() {
+            return Ok(());
+        }
+        fs::copy(&self.path, &backup).map_err(|e| BmError::io(&backup, e))?;
+        Ok(())
+    }
+
+    fn write(&self, bookmarks: &[Bookmark]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| BmError::io(dir, e))?;
+        }
+        // Hand-written comments and layout survive saves of TOML files.
+        // Encrypted files are written afresh rather than decrypted again.
+        let previous = match self.format {
+            Format::Toml if !self.encrypted => fs::read_to_string(&self.path).ok(),
+            _ => None,
+        };
+        let bookmarks = for_disk(bookmarks, self.home_relative, self.wsl_paths, &self.remap);
+        let data = match previous.and_then(|previous| format::update_toml(&previous, &bookmarks)) {
+            Some(data) => data,
+            None => self.format.serialize(&BookmarkFile::new(bookmarks))?,
+        };
+        let data = if self.encrypted { encrypt(&self.path, &data)? } else { data.into_bytes() };
+        self.backup()?;
+        write_atomic(&self.path, &data)?;
+        sync::commit_change(&self.path);
+        Ok(())
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        self.path.with_file_name("backups")
+    }
+
+    /// The stem and extension backups of this file are named with, as in
+    /// `bookmarks-<timestamp>.toml`.
+    fn backup_name_parts(&self) -> (&str, &str) {
+        let stem = self.path.file_stem().and_then(|n| n.to_str()).unwrap_or("bookmarks");
+        let ext = self.path.extension().and_then(|n| n.to_str()).unwrap_or("toml");
+        (stem, ext)
+    }
+
+    /// This file's backups, oldest first. The backup directory is shared with
+    /// the archive, other profiles and pre-migration copies, so only names of
+    /// exactly the form `backup` writes count.
+    fn backups(&self) -> io::Result<Vec<PathBuf>> {
+        let (stem, ext) = self.backup_name_parts();
+        let mut backups: Vec<PathBuf> = fs::read_dir(self.backup_dir())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| is_backup_name(n, stem, ext)))
+            .collect();
+        backups.sort();
+        Ok(backups)
+    }
+
+    /// Copies the current contents of the file into the backup directory under a
+    /// timestamped name and prunes all but the newest `max_backups` copies.
+    fn backup(&self) -> Result<()> {
+        if !self.path.exists() || self.max_backups == 0 {
+            return Ok(());
+        }
+        let dir = self.backup_dir();
+        let io_err = |e| BmError::io(&dir, e);
+        fs::create_dir_all(&dir).map_err(io_err)?;
+        let (stem, ext) = self.backup_name_parts();
+        let name = format!("{stem}-{}.{ext}", backup_timestamp(SystemTime::now()));
+        fs::copy(&self.path, dir.join(name)).map_err(io_err)?;
+
+        let backups = self.backups().map_err(io_err)?;
+        let excess = backups.len().saturating_sub(self.max_backups);
+        for old in &backups[..excess] {
+            fs::remove_file(old).map_err(io_err)?;
+        }
+        Ok(())
+    }
+}
+
+impl BookmarkStore for FileStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> Result<Vec<Bookmark>> {
+        // Reading still works when the lock file cannot be created (e.g. read-only home).
+        let _lock = self.lock().ok();
+        Ok(self.read()?.bookmarks)
+    }
+
+    /// Saves `ours` under the file lock. If another instance has written the file
+    /// since `base` was loaded, both sets of changes are merged first. Returns the
+    /// list that was written and whether a merge took place. Nothing is written
+    /// when the file on disk cannot be parsed.
+    fn save_merged(&self, base: &[Bookmark], ours: &[Bookmark]) -> Result<(Vec<Bookmark>, bool)> {
+        let _lock = self.lock()?;
+        let theirs = self.read()?;
+        if let Some(version) = theirs.migrated_from {
+            self.backup_before_migration(version)?;
+        }
+        let theirs = theirs.bookmarks;
+        let (mut merged, was_merged) = if theirs == base {
+            (ours.to_vec(), false)
+        } else {
+            (merge_bookmarks(base, ours, &theirs), true)
+        };
+        make_names_unique(&mut merged);
+        self.write(&merged)?;
+        tracing::debug!(path = %self.path.display(), merged = was_merged, "saved {} bookmarks", merged.len());
+        Ok((merged, was_merged))
+    }
+
+    /// Replaces a bookmarks file that fails to parse with the newest backup that
+    /// parses. The broken file is kept next to it as `bookmarks.toml.corrupt`.
+    /// Returns the backup that was used, or `None` if no usable backup exists.
+    fn restore_from_backup(&self) -> Result<Option<PathBuf>> {
+        let _lock = self.lock()?;
+        let path = &self.path;
+        let dir = self.backup_dir();
+        let backups = match self.backups() {
+            Ok(backups) => backups,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(BmError::io(&dir, e)),
+        };
+        for backup in backups.iter().rev() {
+            let Ok(Some(content)) = self.read_text(backup) else {
+                continue;
+            };
+            if self.format.parse(backup, &content).is_ok() {
+                if path.exists() {
+                    let corrupt = with_suffix(path, ".corrupt");
+                    fs::rename(path, &corrupt).map_err(|e| BmError::io(&corrupt, e))?;
+                }
+                // Copied as it is, so an encrypted backup stays encrypted.
+                let data = fs::read(backup).map_err(|e| BmError::io(backup, e))?;
+                write_atomic(path, &data)?;
+                return Ok(Some(backup.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn with_path(&self, path: PathBuf) -> Box<dyn BookmarkStore> {
+        Box::new(FileStore {
+            format: format_of(&path),
+            encrypted: is_encrypted(&path),
+            path,
+            remap: self.remap.clone(),
+            ..*self
+        })
+    }
+}
+
+/// Watches the directory holding `path` (the file itself is replaced on every
+/// atomic save) and signals whenever the file is touched.
+pub fn watch_file(path: &Path) -> notify::Result<(RecommendedWatcher, Receiver<()>)> {
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    fs::create_dir_all(&dir)?;
+    let file_name = path.file_name().map(|n| n.to_os_string());
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // Reads (including our own) show up as access events; only changes matter.
+        if let Ok(event) = event
+            && !matches!(event.kind, EventKind::Access(_))
+            && event.paths.iter().any(|p| p.file_name() == file_name.as_deref())
+        {
+            let _ = tx.send(());
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok((watcher, rx))
+}
+
+/// Whether `name` is `{stem}-<timestamp>.{ext}`, with the timestamp as
+/// `backup_timestamp` writes it.
+fn is_backup_name(name: &str, stem: &str, ext: &str) -> bool {
+    let Some(timestamp) = name.strip_prefix(stem).and_then(|n| n.strip_prefix('-')) else {
+        return false;
+    };
+    let Some(timestamp) = timestamp.strip_suffix(ext).and_then(|t| t.strip_suffix('.')) else {
+        return false;
+    };
+    let fields: Vec<&str> = timestamp.split('-').collect();
+    fields.iter().map(|f| f.len()).eq([8, 6, 3]) && fields.iter().all(|f| f.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `bookmarks.toml.lock`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Three-way merge keyed on the bookmark path. Additions and deletions from
+/// either side are kept; when both sides touched the same entry, our version
+/// wins, and an entry one side deleted survives if the other side edited it.
+pub fn merge_bookmarks(base: &[Bookmark], ours: &[Bookmark], theirs: &[Bookmark]) -> Vec<Bookmark> {
+    let find = |list: &[Bookmark], path: &str| list.iter().position(|b| b.path == path);
+    let mut merged = Vec::new();
+    for t in theirs {
+        let in_base = find(base, &t.path).map(|i| &base[i]);
+        match find(ours, &t.path).map(|i| &ours[i]) {
+            Some(o) if in_base != Some(o) => merged.push(o.clone()),
+            Some(_) => merged.push(t.clone()),
+            // We deleted it; keep it only if they changed it meanwhile.
+            None if in_base.is_some_and(|b| b != t) => merged.push(t.clone()),
+            None if in_base.is_some() => {}
+            None => merged.push(t.clone()),
+        }
+    }
+    for o in ours {
+        if find(theirs, &o.path).is_some() {
+            continue;
+        }
+        match find(base, &o.path).map(|i| &base[i]) {
+            // They deleted it; keep it only if we changed it meanwhile.
+            Some(b) if b == o => {}
+            _ => merged.push(o.clone()),
+        }
+    }
+    merged
+}
+
+/// Writes `data` to a temp file next to `path` and renames it over the original,
+/// so readers see either the old or the new contents but never a partial file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("bookmarks");
+    let tmp = path.with_file_name(format!(".{file_name}.tmp{}", std::process::id()));
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        // Keep what the user set, such as `chmod 600`, instead of the umask.
+        match fs::metadata(path) {
+            Ok(meta) => file.set_permissions(meta.permissions())?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        io::Write::write_all(&mut file, data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map_err(|e| BmError::io(path, e))
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Formats `secs` since the Unix epoch as a UTC time, `YYYY-MM-DD HH:MM:SS`.
+pub(crate) fn utc_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}", rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// Formats a UTC time as `YYYYMMDD-HHMMSS-mmm`, which sorts chronologically.
+fn backup_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}-{:03}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, path: &str) -> Bookmark {
+        Bookmark::new(name.to_string(), path.to_string())
+    }
+
+    /// Name and path of each bookmark, as `Bookmark` has no `Debug`.
+    fn entries(bookmarks: &[Bookmark]) -> Vec<(&str, &str)> {
+        bookmarks.iter().map(|b| (b.name.as_str(), b.path.as_str())).collect()
+    }
+
+    #[test]
+    fn merge_keeps_an_entry_we_edited_that_they_deleted() {
+        let base = [bookmark("src", "/src"), bookmark("tmp", "/tmp")];
+        let ours = [bookmark("code", "/src"), bookmark("tmp", "/tmp")];
+        let theirs = [bookmark("tmp", "/tmp")];
+        assert_eq!(entries(&merge_bookmarks(&base, &ours, &theirs)), [("tmp", "/tmp"), ("code", "/src")]);
+    }
+
+    #[test]
+    fn merge_keeps_an_entry_they_edited_that_we_deleted() {
+        let base = [bookmark("src", "/src"), bookmark("tmp", "/tmp")];
+        let ours = [bookmark("tmp", "/tmp")];
+        let theirs = [bookmark("code", "/src"), bookmark("tmp", "/tmp")];
+        assert_eq!(entries(&merge_bookmarks(&base, &ours, &theirs)), [("code", "/src"), ("tmp", "/tmp")]);
+    }
+
+    #[test]
+    fn merge_drops_an_entry_deleted_on_one_side_and_untouched_on_the_other() {
+        let base = [bookmark("src", "/src"), bookmark("tmp", "/tmp")];
+        let without_src = [bookmark("tmp", "/tmp")];
+        assert_eq!(entries(&merge_bookmarks(&base, &without_src, &base)), [("tmp", "/tmp")]);
+        assert_eq!(entries(&merge_bookmarks(&base, &base, &without_src)), [("tmp", "/tmp")]);
+    }
+
+    #[test]
+    fn merge_keeps_one_entry_for_a_path_added_on_both_sides() {
+        let base = [bookmark("tmp", "/tmp")];
+        let ours = [bookmark("tmp", "/tmp"), bookmark("mine", "/srv")];
+        let theirs = [bookmark("tmp", "/tmp"), bookmark("theirs", "/srv")];
+        assert_eq!(entries(&merge_bookmarks(&base, &ours, &theirs)), [("tmp", "/tmp"), ("mine", "/srv")]);
+    }
+
+    #[test]
+    fn merge_without_changes_gives_the_base_back() {
+        let base = [bookmark("src", "/src"), bookmark("tmp", "/tmp")];
+        assert!(merge_bookmarks(&base, &base, &base) == base);
+    }
+
+    #[test]
+    fn backups_are_only_this_files_timestamped_copies() {
+        assert!(is_backup_name("bookmarks-20261014-095932-856.toml", "bookmarks", "toml"));
+        assert!(is_backup_name(&format!("work-{}.toml", backup_timestamp(SystemTime::now())), "work", "toml"));
+        // The archive, a pre-migration copy and another profile's backups.
+        assert!(!is_backup_name("bookmarks.archive-20261014-095932-856.toml", "bookmarks", "toml"));
+        assert!(!is_backup_name("bookmarks.v1.toml", "bookmarks", "toml"));
+        assert!(!is_backup_name("work-2-20261014-095932-856.toml", "work", "toml"));
+        assert!(!is_backup_name("bookmarks-20261014-095932-856.toml.age", "bookmarks", "toml"));
+        assert!(!is_backup_name("bookmarks-latest.toml", "bookmarks", "toml"));
+    }
+}
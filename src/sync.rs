@@ -0,0 +1,231 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::archive;
+use crate::error::{BmError, Result};
+use crate::store::{self, Bookmark, BookmarkStore, is_encrypted, merge_bookmarks};
+
+/// Bookmarks a sync changed on both machines, left in the repository for the
+/// UI to report.
+const CONFLICTS_FILE: &str = "bm-sync-conflicts";
+
+/// What `sync` did.
+pub struct Synced {
+    /// The remote that was pulled from and pushed to.
+    pub remote: String,
+    /// Whether the remote had changes that were merged in.
+    pub pulled: bool,
+    /// Names of bookmarks both machines changed; this machine's version was kept.
+    pub conflicts: Vec<String>,
+}
+
+/// Runs git in `dir` and returns what it printed, or its error message if it failed.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| BmError::io(Path::new("git"), e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(BmError::Git(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The directory holding the bookmarks file at `path`, which is the repository.
+fn repo_dir(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Whether the directory of the bookmarks file at `path` is a git repository
+/// of its own, set up by `bm sync --init` or by hand.
+pub fn is_repo(path: &Path) -> bool {
+    repo_dir(path).join(".git").exists()
+}
+
+/// Whether a merge is in progress, which `sync` finishes with a commit of its own.
+fn merging(dir: &Path) -> bool {
+    dir.join(".git/MERGE_HEAD").exists()
+}
+
+/// Stages the files of `store` that are synced: the bookmarks file and its archive.
+fn stage(store: &dyn BookmarkStore) -> Result<()> {
+    let dir = repo_dir(store.path());
+    for file in [store.path().to_path_buf(), archive::archive_path(store.path())] {
+        if file.exists() {
+            git(&dir, &["add", "--", &file.to_string_lossy()])?;
+        }
+    }
+    Ok(())
+}
+
+/// Commits the staged files, if anything changed. Returns whether it did.
+fn commit_staged(dir: &Path, message: &str) -> Result<bool> {
+    if git(dir, &["diff", "--cached", "--quiet"]).is_ok() {
+        return Ok(false);
+    }
+    git(dir, &["commit", "--quiet", "-m", message])?;
+    Ok(true)
+}
+
+/// Commits the bookmarks file at `path` after it was saved, if its directory
+/// is a repository. `sync` commits whatever this leaves behind, so a failure
+/// here only delays the commit.
+pub fn commit_change(path: &Path) {
+    let dir = repo_dir(path);
+    if !is_repo(path) || merging(&dir) {
+        return;
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if git(&dir, &["add", "--", &path.to_string_lossy()]).is_ok() {
+        let _ = commit_staged(&dir, &format!("Update {name}"));
+    }
+}
+
+/// Makes the directory of `store` a git repository holding its bookmarks,
+/// and adds `remote` as `origin` to sync with.
+pub fn init(store: &dyn BookmarkStore, remote: Option<&str>) -> Result<()> {
+    let dir = repo_dir(store.path());
+    fs::create_dir_all(&dir).map_err(|e| BmError::io(&dir, e))?;
+    if !is_repo(store.path()) {
+        git(&dir, &["init", "--quiet"])?;
+    }
+    if let Some(remote) = remote {
+        git(&dir, &["remote", "add", "origin", remote])?;
+    }
+    // Backups, lock files and the jump history stay local to each machine.
+    let ignore = dir.join(".gitignore");
+    if !ignore.exists() {
+        let mut content = String::from("*\n!.gitignore\n");
+        for file in [store.path().to_path_buf(), archive::archive_path(store.path())] {
+            if let Some(name) = file.file_name() {
+                content.push_str(&format!("!{}\n", name.to_string_lossy()));
+            }
+        }
+        fs::write(&ignore, content).map_err(|e| BmError::io(&ignore, e))?;
+        git(&dir, &["add", "--", ".gitignore"])?;
+    }
+    stage(store)?;
+    commit_staged(&dir, "Start syncing bookmarks")?;
+    Ok(())
+}
+
+/// The bookmarks in the file at `path` as the merge's `stage` has it: 1 for
+/// the common ancestor, 2 for this machine and 3 for the remote. A file that
+/// a side doesn't have is empty.
+fn staged_bookmarks(dir: &Path, rel: &str, stage: u8, path: &Path) -> Result<Vec<Bookmark>> {
+    match git(dir, &["show", &format!(":{stage}:{rel}")]) {
+        Ok(content) => Ok(store::parse_bookmarks(path, &content)?.bookmarks),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Names of the bookmarks both sides changed, each in its own way.
+fn both_changed(base: &[Bookmark], ours: &[Bookmark], theirs: &[Bookmark]) -> Vec<String> {
+    ours.iter()
+        .filter(|o| {
+            let b = base.iter().find(|b| b.path == o.path);
+            theirs.iter().any(|t| t.path == o.path && t != *o && b.is_none_or(|b| b != t && b != *o))
+        })
+        .map(|o| o.name.clone())
+        .collect()
+}
+
+/// Resolves a merge conflict in the file of `store` with the same three-way
+/// merge bm instances use on each other's changes. Returns the names of the
+/// bookmarks changed on both sides.
+fn resolve(store: &dyn BookmarkStore, dir: &Path, rel: &str) -> Result<Vec<String>> {
+    let path = store.path();
+    if is_encrypted(path) {
+        return Err(BmError::Git(format!("{rel} changed on both sides; encrypted files can't be merged")));
+    }
+    let base = staged_bookmarks(dir, rel, 1, path)?;
+    let ours = staged_bookmarks(dir, rel, 2, path)?;
+    let theirs = staged_bookmarks(dir, rel, 3, path)?;
+    // Saving over our side keeps the file's comments and layout.
+    let ours_file = git(dir, &["show", &format!(":2:{rel}")]).unwrap_or_default();
+    store::write_atomic(path, ours_file.as_bytes())?;
+    let current = store.load()?;
+    store.save_merged(&current, &merge_bookmarks(&base, &ours, &theirs))?;
+    git(dir, &["add", "--", rel])?;
+    Ok(both_changed(&base, &ours, &theirs))
+}
+
+/// Commits the bookmarks of `store`, merges in what the remote has and
+/// pushes the result. Conflicts in the bookmarks file or its archive are
+/// merged bookmark by bookmark; anything else aborts the merge.
+pub fn sync(store: &dyn BookmarkStore) -> Result<Synced> {
+    let dir = repo_dir(store.path());
+    if !is_repo(store.path()) {
+        return Err(BmError::Git(format!("{} is not a git repository; run `bm sync --init`", dir.display())));
+    }
+    stage(store)?;
+    commit_staged(&dir, "Update bookmarks")?;
+    let remotes = git(&dir, &["remote"])?;
+    let remote = match remotes.lines().find(|r| *r == "origin").or(remotes.lines().next()) {
+        Some(remote) => remote.to_string(),
+        None => return Err(BmError::Git("no remote to sync with; run `bm sync --init --remote URL`".to_string())),
+    };
+    let branch = git(&dir, &["symbolic-ref", "--short", "HEAD"])?.trim().to_string();
+    git(&dir, &["fetch", "--quiet", &remote])?;
+    let upstream = format!("{remote}/{branch}");
+    let mut synced = Synced { remote: remote.clone(), pulled: false, conflicts: Vec::new() };
+    if git(&dir, &["rev-parse", "--verify", "--quiet", &format!("refs/remotes/{upstream}")]).is_ok() {
+        // A machine that had no bookmarks yet has no commit to compare with.
+        synced.pulled = match git(&dir, &["rev-list", "--count", &format!("HEAD..{upstream}")]) {
+            Ok(behind) => behind.trim() != "0",
+            Err(_) => true,
+        };
+        let merge = ["merge", "--quiet", "--no-edit", "--allow-unrelated-histories", &upstream];
+        if synced.pulled && git(&dir, &merge).is_err() {
+            synced.conflicts = resolve_all(store, &dir)?;
+            git(&dir, &["commit", "--quiet", "--no-edit"])?;
+        }
+    }
+    git(&dir, &["push", "--quiet", "--set-upstream", &remote, &branch])?;
+    if !synced.conflicts.is_empty() {
+        let file = dir.join(".git").join(CONFLICTS_FILE);
+        fs::write(&file, synced.conflicts.join("\n")).map_err(|e| BmError::io(&file, e))?;
+    }
+    Ok(synced)
+}
+
+/// Resolves every conflict of a failed merge, or aborts it if one is in a
+/// file bm doesn't know how to merge.
+fn resolve_all(store: &dyn BookmarkStore, dir: &Path) -> Result<Vec<String>> {
+    let unmerged = git(dir, &["diff", "--name-only", "--diff-filter=U"])?;
+    let archive = archive::open_archive(store);
+    let mut conflicts = Vec::new();
+    for rel in unmerged.lines() {
+        let file = dir.join(rel);
+        let resolved = if file == store.path() {
+            resolve(store, dir, rel)
+        } else if file == archive.path() {
+            resolve(archive.as_ref(), dir, rel)
+        } else {
+            Err(BmError::Git(format!("{rel} changed on both sides; merge it by hand in {}", dir.display())))
+        };
+        match resolved {
+            Ok(names) => conflicts.extend(names),
+            Err(e) => {
+                let _ = git(dir, &["merge", "--abort"]);
+                return Err(e);
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Names of the bookmarks the last sync kept this machine's version of, as
+/// both machines changed them. They are reported once.
+pub fn take_conflicts(path: &Path) -> Vec<String> {
+    let file = repo_dir(path).join(".git").join(CONFLICTS_FILE);
+    let Ok(content) = fs::read_to_string(&file) else {
+        return Vec::new();
+    };
+    let _ = fs::remove_file(&file);
+    content.lines().filter(|l| !l.is_empty()).map(str::to_string).collect()
+}
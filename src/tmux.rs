@@ -0,0 +1,109 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{BmError, Result};
+use crate::launch;
+
+/// What `open` creates for a bookmark, or switches to if it exists.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    /// A window in the current session; outside tmux a session is used.
+    Window,
+    Session,
+}
+
+/// Whether bm runs inside a tmux client.
+pub fn inside() -> bool {
+    std::env::var_os("TMUX").is_some_and(|v| !v.is_empty())
+}
+
+/// `name` as tmux accepts it for a window or session: `.` and `:` separate
+/// target parts, so they are replaced by `_` like tmux itself does.
+pub fn target_name(name: &str) -> String {
+    name.replace(['.', ':'], "_")
+}
+
+/// Runs tmux and returns what it printed, or its error message if it failed.
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .map_err(|e| BmError::io(Path::new("tmux"), e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(BmError::Tmux(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The title of the pane bm runs in, as the shell or the user left it.
+pub fn pane_title() -> Option<String> {
+    let pane = std::env::var("TMUX_PANE").ok()?;
+    let title = tmux(&["display-message", "-p", "-t", &pane, "#{pane_title}"]).ok()?;
+    Some(title.trim_end_matches('\n').to_string())
+}
+
+/// Sets the title of the pane bm runs in.
+pub fn set_pane_title(title: &str) -> Result<()> {
+    let pane = std::env::var("TMUX_PANE").map_err(|_| BmError::Tmux("not inside a tmux pane".into()))?;
+    tmux(&["select-pane", "-t", &pane, "-T", title]).map(drop)
+}
+
+/// Types each of `commands` into a pane of its own in `dir`, the first one
+/// into `pane`, and tiles the panes.
+fn start_workspace(pane: &str, dir: &str, commands: &[String]) -> Result<()> {
+    for (i, command) in commands.iter().enumerate() {
+        let target = if i == 0 {
+            pane.to_string()
+        } else {
+            let split = tmux(&["split-window", "-t", pane, "-c", dir, "-P", "-F", "#{pane_id}"])?;
+            // Tiling after every split leaves room for the next one.
+            tmux(&["select-layout", "-t", pane, "tiled"])?;
+            split.trim().to_string()
+        };
+        // Typed rather than run directly, so the pane keeps its shell once
+        // the command exits.
+        tmux(&["send-keys", "-t", &target, command, "Enter"])?;
+    }
+    if commands.len() > 1 {
+        tmux(&["select-pane", "-t", pane])?;
+    }
+    Ok(())
+}
+
+/// Opens a tmux window or session called after the bookmark `name` and
+/// rooted at `dir`, or switches to the one that already exists. A new one
+/// starts the bookmark's `workspace` commands, each in its own pane.
+/// Outside tmux the session is attached to in this terminal, which returns
+/// once the client detaches.
+pub fn open(name: &str, dir: &str, workspace: &[String], target: Target) -> Result<()> {
+    let name = target_name(name);
+    if target == Target::Window && inside() {
+        let windows = tmux(&["list-windows", "-F", "#{window_name}"])?;
+        if windows.lines().any(|w| w == name) {
+            tmux(&["select-window", "-t", &format!(":={name}")])?;
+        } else {
+            let pane = tmux(&["new-window", "-n", &name, "-c", dir, "-P", "-F", "#{pane_id}"])?;
+            start_workspace(pane.trim(), dir, workspace)?;
+        }
+        return Ok(());
+    }
+    let session = format!("={name}");
+    if tmux(&["has-session", "-t", &session]).is_err() {
+        let pane = tmux(&["new-session", "-d", "-s", &name, "-c", dir, "-P", "-F", "#{pane_id}"])?;
+        start_workspace(pane.trim(), dir, workspace)?;
+    }
+    if inside() {
+        tmux(&["switch-client", "-t", &session])?;
+        return Ok(());
+    }
+    let status = Command::new("tmux")
+        .args(["attach-session", "-t", &session])
+        .stdout(launch::terminal_stdout())
+        .status()
+        .map_err(|e| BmError::io(Path::new("tmux"), e))?;
+    if !status.success() {
+        return Err(BmError::Tmux(format!("attaching to session {name} failed ({status})")));
+    }
+    Ok(())
+}
@@ -0,0 +1,3237 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Once, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::Print,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+    execute,
+};
+use notify::RecommendedWatcher;
+use serde::{Deserialize, Serialize};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::*,
+    style::*,
+    text::{Line, Span},
+    widgets::*,
+    Frame,
+    Terminal,
+};
+// The list's own `Row` shadows the table row widget.
+use ratatui::widgets::Row as TableRow;
+
+use crate::add;
+use crate::archive;
+use crate::clipboard;
+use crate::complete;
+use crate::config::{Column, Config, Confirm, Icons};
+use crate::dedup::Choice;
+use crate::editor::{self, edit_text_externally};
+use crate::error::{BmError, Result};
+use crate::journal;
+use crate::launch;
+use crate::logging;
+use crate::history;
+use crate::hooks::{self, Hooks, Output};
+use crate::i18n::{self, Text};
+use crate::fuzzy;
+use crate::icons::{self, ProjectType};
+use crate::paths::{self, expand_tilde, same_path};
+use crate::probe::{self, Status};
+use crate::profile;
+use crate::rank::Ranker;
+use crate::project::{self, Project};
+use crate::remote;
+use crate::stats::Stats;
+use crate::tmux;
+use crate::visits;
+use crate::width;
+use crate::sync;
+use crate::store::{Bookmark, BookmarkStore, Kind, unique_name, unix_now, write_atomic};
+
+/// How long after the last change the bookmarks file is written.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on how long stopping the input thread takes.
+const INPUT_STOP_LATENCY: Duration = Duration::from_millis(100);
+
+/// Keys used for hint labels, home row first so the common jumps stay cheap.
+const HINT_KEYS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// Builds `count` distinct labels: single letters while they suffice,
+/// otherwise two letters each so every label has the same length.
+fn hint_labels(count: usize) -> Vec<String> {
+    let keys: Vec<char> = HINT_KEYS.chars().collect();
+    if count <= keys.len() {
+        keys.iter().take(count).map(|c| c.to_string()).collect()
+    } else {
+        keys.iter()
+            .flat_map(|a| keys.iter().map(move |b| format!("{a}{b}")))
+            .take(count)
+            .collect()
+    }
+}
+
+enum Mode {
+    Normal,
+    /// The bookmarks file failed to parse; offers restoring a backup or
+    /// continuing read-only.
+    LoadFailed { message: String },
+    /// Asks before archiving `count` bookmarks from the selected one down.
+    ConfirmDelete { count: usize },
+    /// Asks before quitting with changes that aren't written yet.
+    ConfirmQuit,
+    /// Editing the note of the selected bookmark; `input` holds the text typed so far.
+    EditNote { input: String },
+    /// Typing the path of a directory to bookmark; `candidates` lists the
+    /// matches of an ambiguous Tab completion. With `create` set the
+    /// directory is created without asking first.
+    AddPath { input: String, candidates: Vec<String>, create: bool },
+    /// The typed directory does not exist; asks whether to create it.
+    ConfirmCreate { path: String },
+    /// A file bookmark was picked; asks whether to print, open or edit `path`.
+    FileAction { path: String },
+    /// Browsing the filesystem for directories to bookmark.
+    Browse(Browser),
+    /// Looking through the archive for bookmarks to restore.
+    Archive(ArchiveView),
+    /// The `bm stats` summary of the bookmarks, scrolled down `scroll` lines.
+    Stats { lines: Vec<String>, scroll: u16 },
+    /// A failed operation, shown as a popup over the list.
+    Error { message: String, retry: Option<Retry> },
+    /// Labels are shown next to the visible rows in `targets`; `typed` holds
+    /// the prefix entered so far.
+    Hint { labels: Vec<String>, targets: Vec<usize>, typed: String },
+    /// Typing an ex-style command after `:`, run by [`App::run_command`].
+    Command { input: String },
+    /// Searching the command palette opened by Ctrl-P.
+    Palette(Palette),
+    /// Typing a `/` search; the first match from row `origin` on is
+    /// selected as it is typed. `previous` is the search Esc goes back to.
+    Search { input: String, origin: usize, previous: Option<String> },
+}
+
+/// Directory navigator shown in place of the list by `b`.
+struct Browser {
+    dir: PathBuf,
+    /// Names of the subdirectories of `dir`, sorted, hidden ones left out.
+    entries: Vec<String>,
+    selected: usize,
+    list_state: ListState,
+}
+
+/// How a command palette entry is carried out.
+#[derive(Clone, Copy)]
+enum Invoke {
+    /// As if its key was pressed in the list.
+    Key(KeyCode),
+    /// The command line, with the start of a command that needs an argument.
+    Prompt(&'static str),
+    /// A `:` command that needs nothing more.
+    Run(&'static str),
+}
+
+/// Everything the list can do, as the command palette offers it: what it's
+/// called, the key or command that does it directly, and how it's done.
+const ACTIONS: &[(&str, &str, Invoke)] = &[
+    ("Add the current directory", "u", Invoke::Key(KeyCode::Char('u'))),
+    ("Add the git repository root", "U", Invoke::Key(KeyCode::Char('U'))),
+    ("Add a path", "a", Invoke::Key(KeyCode::Char('a'))),
+    ("Add the directory on the clipboard", "P", Invoke::Key(KeyCode::Char('P'))),
+    ("Create a directory and add it", "c", Invoke::Key(KeyCode::Char('c'))),
+    ("Browse the filesystem", "b", Invoke::Key(KeyCode::Char('b'))),
+    ("Bookmark the selected suggestion", "+", Invoke::Key(KeyCode::Char('+'))),
+    ("Fold or unfold the suggestions", "z", Invoke::Key(KeyCode::Char('z'))),
+    ("Rename the selected bookmark", ":rename", Invoke::Prompt("rename ")),
+    ("Edit the note", "e", Invoke::Key(KeyCode::Char('e'))),
+    ("Edit the note in $EDITOR", "E", Invoke::Key(KeyCode::Char('E'))),
+    ("Tag the selected bookmark", ":tag", Invoke::Prompt("tag ")),
+    ("Untag the selected bookmark", ":untag", Invoke::Prompt("untag ")),
+    ("Sort: next order", "s", Invoke::Key(KeyCode::Char('s'))),
+    ("Sort in file order", ":sort file", Invoke::Run("sort file")),
+    ("Sort by use", ":sort used", Invoke::Run("sort used")),
+    ("Sort by name", ":sort name", Invoke::Run("sort name")),
+    ("Sort by path", ":sort path", Invoke::Run("sort path")),
+    ("Sort by last use", ":sort last_used", Invoke::Run("sort last_used")),
+    ("Sort by hits", ":sort hits", Invoke::Run("sort hits")),
+    ("Group by project type", ":sort type", Invoke::Run("sort type")),
+    ("Filter by project type", "F", Invoke::Key(KeyCode::Char('F'))),
+    ("Show statistics", "S", Invoke::Key(KeyCode::Char('S'))),
+    ("Jump with labels", "f", Invoke::Key(KeyCode::Char('f'))),
+    ("Mark or unmark the selected bookmark", "Space", Invoke::Key(KeyCode::Char(' '))),
+    ("Copy the marked paths", "y", Invoke::Key(KeyCode::Char('y'))),
+    ("Print the marked paths and exit", "Y", Invoke::Key(KeyCode::Char('Y'))),
+    ("Archive the selected bookmark", "!", Invoke::Key(KeyCode::Char('!'))),
+    ("Show the archive", "A", Invoke::Key(KeyCode::Char('A'))),
+    ("Switch to the next profile", "p", Invoke::Key(KeyCode::Char('p'))),
+    ("Select and exit", "Enter", Invoke::Key(KeyCode::Enter)),
+    ("Run the bookmark's command", "x", Invoke::Key(KeyCode::Char('x'))),
+    ("Open in tmux", "T", Invoke::Key(KeyCode::Char('T'))),
+    ("Type a command", ":", Invoke::Key(KeyCode::Char(':'))),
+    ("Search", "/", Invoke::Key(KeyCode::Char('/'))),
+    ("Next match", "n", Invoke::Key(KeyCode::Char('n'))),
+    ("Previous match", "N", Invoke::Key(KeyCode::Char('N'))),
+    ("Save now", ":w", Invoke::Run("w")),
+    ("Quit", "q", Invoke::Key(KeyCode::Char('q'))),
+];
+
+/// The command palette: `ACTIONS` matching what was typed, best first.
+struct Palette {
+    input: String,
+    /// Indices into `ACTIONS`.
+    matches: Vec<usize>,
+    selected: usize,
+    list_state: ListState,
+}
+
+impl Palette {
+    fn new() -> Self {
+        let mut palette = Palette { input: String::new(), matches: Vec::new(), selected: 0, list_state: ListState::default() };
+        palette.search();
+        palette
+    }
+
+    /// Finds the actions matching `input` again, like `bm pick` matches names.
+    fn search(&mut self) {
+        let mut scored: Vec<(i64, usize)> = ACTIONS
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (title, _, _))| fuzzy::score(&self.input, title).map(|score| (score, i)))
+            .collect();
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+/// The archived bookmarks, listed for restoring.
+struct ArchiveView {
+    bookmarks: Vec<Bookmark>,
+    selected: usize,
+    list_state: TableState,
+}
+
+impl Browser {
+    fn open(dir: PathBuf) -> io::Result<Self> {
+        let mut entries: Vec<String> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        entries.sort();
+        Ok(Browser { dir, entries, selected: 0, list_state: ListState::default() })
+    }
+
+    fn highlighted(&self) -> Option<PathBuf> {
+        self.entries.get(self.selected).map(|name| self.dir.join(name))
+    }
+}
+
+/// Operation that failed and can be attempted again from the error popup.
+enum Retry {
+    Save,
+    EditNote,
+}
+
+/// What the event loop should do after a key has been handled.
+enum Action {
+    Continue,
+    Quit,
+    /// Quit as Ctrl-C does, which tells scripts the user backed out.
+    Cancel,
+    Select(String),
+    /// Quit, printing these paths one per line.
+    PrintPaths(Vec<String>),
+    /// Open the selected bookmark's note in `$EDITOR`.
+    EditNoteExternally,
+    /// Run the bookmark's command in `path`.
+    Run { bookmark: Bookmark, path: String },
+    /// Open the file at `path` with the desktop's default application.
+    OpenFile(String),
+    /// Open the file at `path` in `$EDITOR`.
+    EditFile(String),
+    /// Log into the host of the remote `path` and change to its directory.
+    Ssh(String),
+    /// Open a tmux window or session for the bookmark `name` in `path`,
+    /// starting its workspace commands.
+    Tmux { name: String, path: String, workspace: Vec<String> },
+    /// The store now points at another profile's file and needs a new watcher.
+    ProfileSwitched,
+}
+
+/// Order of the user's bookmarks in the list; the file keeps its own order.
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Sort {
+    /// As stored in the file.
+    #[default]
+    File,
+    /// By a column: names, paths and tags alphabetically, the most recently
+    /// or most often used first.
+    By(Column),
+    /// By use, as the `ranking` setting ranks it.
+    Used,
+    /// Grouped by project type, in file order within each group.
+    ProjectType,
+}
+
+/// Name of the file in the data directory that keeps the UI's state.
+const STATE_FILE: &str = "ui-state.toml";
+
+/// What the UI was left with, restored when it is opened again.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SavedState {
+    /// Path of the bookmark that was selected.
+    selected: Option<String>,
+    sort: Sort,
+    type_filter: Option<ProjectType>,
+    /// Whether the suggestions were folded away with `z`.
+    suggestions_collapsed: bool,
+}
+
+impl SavedState {
+    fn path() -> Result<PathBuf> {
+        Ok(paths::data_dir()?.join(STATE_FILE))
+    }
+
+    /// The state saved last. A missing or unreadable file is no state, as
+    /// it's only a convenience.
+    fn load() -> Option<SavedState> {
+        let content = fs::read_to_string(SavedState::path().ok()?).ok()?;
+        toml::from_str(&content).ok()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = SavedState::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| BmError::io(parent, e))?;
+        }
+        let content = toml::to_string(self).map_err(|e| BmError::Serialize(e.to_string()))?;
+        write_atomic(&path, content.as_bytes())
+    }
+}
+
+/// Which list a bookmark row comes from.
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    /// The user's own bookmarks file.
+    Main,
+    /// The `.bm.toml` found from the working directory; read-only in the UI.
+    Project,
+    /// Often visited directories that aren't bookmarked, promoted with `+`.
+    Suggested,
+}
+
+/// Number of recently jumped-to bookmarks listed above the others.
+const RECENT_SHOWN: usize = 3;
+
+/// Number of often visited directories suggested below the bookmarks.
+const SUGGESTIONS_SHOWN: usize = 5;
+
+/// One line of the list: a section header or a bookmark.
+enum Row {
+    /// A section title, and what it is about, shown in the path column.
+    Header(String, Option<String>),
+    Bookmark(Source, usize),
+    /// A bookmark repeated in the "recent" section.
+    Recent(Source, usize),
+}
+
+impl Row {
+    fn entry(&self) -> Option<(Source, usize)> {
+        match *self {
+            Row::Bookmark(source, index) | Row::Recent(source, index) => Some((source, index)),
+            Row::Header(..) => None,
+        }
+    }
+}
+
+struct App {
+    /// The bookmarks file being shown.
+    store: Box<dyn BookmarkStore>,
+    bookmarks: Vec<Bookmark>,
+    /// The bookmarks as last read from or written to disk, used to merge
+    /// concurrent edits from other instances.
+    base: Vec<Bookmark>,
+    /// Shared bookmarks from a `.bm.toml` above the working directory.
+    project: Option<Project>,
+    /// What the list shows, rebuilt whenever the bookmarks change.
+    rows: Vec<Row>,
+    /// One-off message shown in the footer until the next key press.
+    status: Option<String>,
+    /// Set when the file on disk could not be parsed, so the broken file is
+    /// never overwritten, and throughout with `--read-only`; saving is disabled.
+    read_only: bool,
+    /// Set when the list has changes that are not on disk yet.
+    dirty: bool,
+    /// When the pending changes get written if nothing else changes first.
+    save_due: Option<Instant>,
+    /// Index into `rows`; always a bookmark row when there is one.
+    selected: usize,
+    mode: Mode,
+    list_state: TableState,
+    /// Number of list rows that fit on screen, updated on every draw.
+    list_height: usize,
+    /// Named profile being shown, `None` for the default bookmarks file.
+    profile: Option<String>,
+    /// Resolve symlinks in newly bookmarked paths.
+    canonicalize: bool,
+    /// Directory `u` bookmarks, given with `--cwd`; else the working directory.
+    cwd: Option<PathBuf>,
+    sort: Sort,
+    columns: Vec<Column>,
+    column_widths: HashMap<Column, u16>,
+    /// Bookmarks unused for this many days are flagged.
+    stale_after: Option<u64>,
+    /// How `s` orders bookmarks by use.
+    ranker: Ranker,
+    /// Which actions ask first.
+    confirm: Confirm,
+    /// Glob patterns of paths never to bookmark, see [`add::excluded`].
+    exclude: Vec<String>,
+    /// Directories jumped to, most recent first.
+    recent: Vec<String>,
+    hooks: Hooks,
+    /// The terminal title last set, so it is only written when it changes.
+    title: Option<String>,
+    /// Show paths as OSC 8 hyperlinks.
+    hyperlinks: bool,
+    /// The paths on screen, to be rewritten as hyperlinks after each draw.
+    links: Vec<Link>,
+    /// Whether each bookmarked path exists, by path as written. Filled in
+    /// from a background check; paths not checked yet have no entry.
+    liveness: HashMap<String, Status>,
+    /// The project type of bookmarked directories, filled in with `liveness`.
+    project_types: HashMap<String, ProjectType>,
+    icons: Icons,
+    /// Only bookmarks of this project type are listed.
+    type_filter: Option<ProjectType>,
+    /// The bookmark to select once project types are known, as the type
+    /// filter hides every bookmark until then.
+    pending_selection: Option<String>,
+    /// The last `/` search, in lower case, whose matches are highlighted and
+    /// visited with `n` and `N`.
+    search: Option<String>,
+    /// The count typed so far before a move or `!`.
+    count: Option<usize>,
+    /// Whether `o` was pressed, so the next digit picks the column to sort by.
+    sort_prefix: bool,
+    /// Often visited directories that aren't bookmarked, as bookmarks to be.
+    suggestions: Vec<Bookmark>,
+    /// The suggestions are folded into their section header.
+    suggestions_collapsed: bool,
+    /// Paths of the bookmarks marked with `Space`, for `y` and `Y`.
+    marked: HashSet<String>,
+}
+
+impl App {
+    fn new(store: Box<dyn BookmarkStore>, bookmarks: Vec<Bookmark>, project: Option<Project>) -> Self {
+        let read_only = store.is_read_only();
+        let mut app = App {
+            store,
+            base: bookmarks.clone(),
+            bookmarks,
+            project,
+            rows: Vec::new(),
+            status: None,
+            read_only,
+            dirty: false,
+            save_due: None,
+            selected: 0,
+            mode: Mode::Normal,
+            list_state: TableState::default(),
+            list_height: 0,
+            profile: None,
+            canonicalize: false,
+            cwd: None,
+            sort: Sort::File,
+            columns: Vec::new(),
+            column_widths: HashMap::new(),
+            stale_after: None,
+            ranker: Ranker::default(),
+            confirm: Confirm::default(),
+            exclude: Vec::new(),
+            recent: Vec::new(),
+            hooks: Hooks::default(),
+            liveness: HashMap::new(),
+            project_types: HashMap::new(),
+            icons: Icons::Off,
+            type_filter: None,
+            pending_selection: None,
+            search: None,
+            count: None,
+            sort_prefix: false,
+            suggestions: Vec::new(),
+            suggestions_collapsed: false,
+            marked: HashSet::new(),
+            hyperlinks: false,
+            links: Vec::new(),
+            title: None,
+        };
+        app.rebuild_rows();
+        app
+    }
+
+    /// Starts with an error screen for a bookmarks file that failed to load.
+    fn load_failed(store: Box<dyn BookmarkStore>, error: &BmError) -> Self {
+        let mut app = App::new(store, Vec::new(), None);
+        app.read_only = true;
+        app.mode = Mode::LoadFailed { message: error.to_string() };
+        app
+    }
+
+    /// Recomputes `rows`. Section headers only appear when there is more than
+    /// one section to tell apart.
+    fn rebuild_rows(&mut self) {
+        let selected = self.selected_entry();
+        self.rows.clear();
+        let recent = self.recent_entries();
+        if !recent.is_empty() {
+            self.rows.push(Row::Header("recent".to_string(), None));
+            self.rows.extend(recent.into_iter().map(|(source, i)| Row::Recent(source, i)));
+        }
+        if let Some(project) = &self.project {
+            let dir = project.file.parent().unwrap_or(&project.file);
+            self.rows.push(Row::Header("project".to_string(), Some(dir.display().to_string())));
+            let shown: Vec<Row> = (0..project.bookmarks.len())
+                .filter(|&i| self.shown(&project.bookmarks[i]))
+                .map(|i| Row::Bookmark(Source::Project, i))
+                .collect();
+            self.rows.extend(shown);
+        }
+        let mut order: Vec<usize> = (0..self.bookmarks.len()).filter(|&i| self.shown(&self.bookmarks[i])).collect();
+        match self.sort {
+            Sort::File => {}
+            Sort::By(column) => order.sort_by(|&a, &b| compare_by(column, &self.bookmarks[a], &self.bookmarks[b])),
+            Sort::Used => {
+                let now = unix_now();
+                let used = |b: &Bookmark| (b.hits, b.last_used);
+                order.sort_by(|&a, &b| self.ranker.compare(used(&self.bookmarks[a]), used(&self.bookmarks[b]), now));
+            }
+            Sort::ProjectType => {
+                // Directories of no known type go last.
+                order.sort_by_key(|&i| self.project_type(&self.bookmarks[i]).map_or(ProjectType::ALL.len(), |t| t as usize));
+                let mut group = None;
+                for i in order {
+                    let project = self.project_type(&self.bookmarks[i]);
+                    if group != Some(project) {
+                        group = Some(project);
+                        let title = project.map_or("other bookmarks", ProjectType::label);
+                        self.rows.push(Row::Header(title.to_string(), None));
+                    }
+                    self.rows.push(Row::Bookmark(Source::Main, i));
+                }
+                self.push_suggestions();
+                return self.reselect(selected);
+            }
+        }
+        if !self.rows.is_empty() {
+            self.rows.push(Row::Header("bookmarks".to_string(), None));
+        }
+        self.rows.extend(order.into_iter().map(|i| Row::Bookmark(Source::Main, i)));
+        self.push_suggestions();
+        self.reselect(selected);
+    }
+
+    /// Adds the section of suggestions that still aren't bookmarked, or just
+    /// its header while it is collapsed.
+    fn push_suggestions(&mut self) {
+        let shown: Vec<usize> = (0..self.suggestions.len())
+            .filter(|&i| {
+                let s = &self.suggestions[i];
+                self.shown(s) && !self.bookmarks.iter().any(|b| add::same_dir(&b.path, &s.path))
+            })
+            .collect();
+        if shown.is_empty() {
+            return;
+        }
+        if self.suggestions_collapsed {
+            let detail = format!("{} hidden; z shows them", shown.len());
+            self.rows.push(Row::Header("suggested".to_string(), Some(detail)));
+            return;
+        }
+        self.rows.push(Row::Header("suggested".to_string(), Some("often visited; + bookmarks one".to_string())));
+        self.rows.extend(shown.into_iter().map(|i| Row::Bookmark(Source::Suggested, i)));
+    }
+
+    /// Selects the `selected` entry again after the rows were rebuilt.
+    fn reselect(&mut self, selected: Option<(Source, usize)>) {
+        match selected {
+            Some((source, index)) => self.select_entry(source, index),
+            None => self.clamp_selection(),
+        }
+    }
+
+    /// The bookmarks for the most recent entries of the jump history,
+    /// project ones first when both lists have the directory.
+    fn recent_entries(&self) -> Vec<(Source, usize)> {
+        let project = self.project.iter().flat_map(|p| p.bookmarks.iter().enumerate().map(|(i, b)| (Source::Project, i, b)));
+        let main = self.bookmarks.iter().enumerate().map(|(i, b)| (Source::Main, i, b));
+        let all: Vec<_> = project.chain(main).collect();
+        self.recent
+            .iter()
+            .filter_map(|dir| {
+                all.iter()
+                    .find(|(_, _, b)| b.target().is_ok_and(|target| same_path(&target, dir)))
+                    .map(|&(source, i, _)| (source, i))
+            })
+            .filter(|&(source, i)| self.bookmark(source, i).is_some_and(|b| self.shown(b)))
+            .take(RECENT_SHOWN)
+            .collect()
+    }
+
+    fn project_type(&self, b: &Bookmark) -> Option<ProjectType> {
+        self.project_types.get(&b.path).copied()
+    }
+
+    /// Whether `b` passes the project type filter.
+    fn shown(&self, b: &Bookmark) -> bool {
+        self.type_filter.is_none_or(|wanted| self.project_type(b) == Some(wanted))
+    }
+
+    fn bookmark(&self, source: Source, index: usize) -> Option<&Bookmark> {
+        match source {
+            Source::Main => self.bookmarks.get(index),
+            Source::Project => self.project.as_ref()?.bookmarks.get(index),
+            Source::Suggested => self.suggestions.get(index),
+        }
+    }
+
+    fn selected_entry(&self) -> Option<(Source, usize)> {
+        self.rows.get(self.selected)?.entry()
+    }
+
+    fn selected_bookmark(&self) -> Option<&Bookmark> {
+        let (source, index) = self.selected_entry()?;
+        self.bookmark(source, index)
+    }
+
+    /// Index of the selected bookmark in the user's own list. Project
+    /// bookmarks can't be changed from here, which is reported in the status line.
+    fn selected_main(&mut self) -> Option<usize> {
+        match self.selected_entry()? {
+            (Source::Main, index) => Some(index),
+            (Source::Project, _) => {
+                if let Some(project) = &self.project {
+                    self.status = Some(format!(
+                        "Project bookmarks are read-only; edit {}",
+                        project.file.display()
+                    ));
+                }
+                None
+            }
+            (Source::Suggested, _) => {
+                self.status = Some("Not bookmarked yet; + adds it".to_string());
+                None
+            }
+        }
+    }
+
+    fn select_entry(&mut self, source: Source, index: usize) {
+        let found = self
+            .rows
+            .iter()
+            .position(|r| matches!(r, Row::Bookmark(s, i) if *s == source && *i == index));
+        if let Some(row) = found {
+            self.selected = row;
+        }
+        self.clamp_selection();
+    }
+
+    /// Selects the user's bookmark for `path`, if it is still in the list.
+    fn select_path(&mut self, path: &str) {
+        if let Some(index) = self.bookmarks.iter().position(|b| b.path == path) {
+            self.select_entry(Source::Main, index);
+        }
+    }
+
+    /// Moves the selection onto the nearest bookmark row, preferring the one below.
+    fn clamp_selection(&mut self) {
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+        if self.selected_entry().is_some() {
+            return;
+        }
+        let is_bookmark = |r: &Row| r.entry().is_some();
+        if let Some(offset) = self.rows[self.selected..].iter().position(is_bookmark) {
+            self.selected += offset;
+        } else if let Some(row) = self.rows[..self.selected].iter().rposition(is_bookmark) {
+            self.selected = row;
+        }
+    }
+
+    /// Moves the selection by one bookmark, skipping headers.
+    fn move_selection(&mut self, down: bool) {
+        let is_bookmark = |r: &Row| r.entry().is_some();
+        let next = if down {
+            self.rows
+                .iter()
+                .enumerate()
+                .skip(self.selected + 1)
+                .find(|(_, r)| is_bookmark(r))
+        } else {
+            self.rows[..self.selected].iter().enumerate().rfind(|(_, r)| is_bookmark(r))
+        };
+        if let Some((row, _)) = next {
+            self.selected = row;
+        }
+    }
+
+    /// Whether `b` has the last search in its name, path (also as shown,
+    /// with `~`) or tags.
+    fn matches_search(&self, b: &Bookmark) -> bool {
+        let Some(query) = self.search.as_deref() else {
+            return false;
+        };
+        let shown = paths::contract_tilde(&b.path);
+        [b.name.as_str(), b.path.as_str(), shown.as_str()]
+            .into_iter()
+            .chain(b.tags.iter().map(String::as_str))
+            .any(|text| text.to_lowercase().contains(query))
+    }
+
+    /// Selects the next row matching the search (or the previous one),
+    /// wrapping around the list like vi does. The selected row itself comes
+    /// last unless `here` is set, as when the search is being typed.
+    fn find_match(&mut self, forward: bool, here: bool) {
+        let Some(query) = self.search.clone() else {
+            self.status = Some("No search yet; / starts one".to_string());
+            return;
+        };
+        if self.rows.is_empty() {
+            self.status = Some(format!("No match for {query}"));
+            return;
+        }
+        let (len, at) = (self.rows.len(), self.selected);
+        let mut order: Vec<usize> = if forward {
+            (at..len).chain(0..at.min(len)).collect()
+        } else {
+            (0..=at.min(len.saturating_sub(1))).rev().chain((at + 1..len).rev()).collect()
+        };
+        if !here && !order.is_empty() {
+            order.rotate_left(1);
+        }
+        let found = order.into_iter().find(|&row| {
+            self.rows[row].entry().and_then(|(source, index)| self.bookmark(source, index)).is_some_and(|b| self.matches_search(b))
+        });
+        match found {
+            Some(row) => {
+                if forward && row < at {
+                    self.status = Some("Search wrapped to the top".to_string());
+                } else if !forward && row > at {
+                    self.status = Some("Search wrapped to the bottom".to_string());
+                }
+                self.selected = row;
+            }
+            None => self.status = Some(format!("No match for {query}")),
+        }
+    }
+
+    /// Whether changes are refused because the store is read-only, in which
+    /// case the status line says so. Checked before anything is changed, so
+    /// the list on screen never shows edits that won't be saved.
+    fn refuses_changes(&mut self) -> bool {
+        if self.read_only {
+            self.status = Some("Read-only: changes are not saved".to_string());
+        }
+        self.read_only
+    }
+
+    /// Records that the list was changed. Writes are batched: the file is
+    /// saved once no change has been made for `SAVE_DELAY`, and on exit.
+    fn changed(&mut self) {
+        if self.read_only {
+            self.status = Some("Read-only: changes are not saved".to_string());
+            return;
+        }
+        self.dirty = true;
+        self.save_due = Some(Instant::now() + SAVE_DELAY);
+    }
+
+    /// Writes pending changes, merging them with whatever another instance
+    /// saved meanwhile. Returns whether a merge took place.
+    fn flush(&mut self) -> Result<bool> {
+        if !self.dirty || self.read_only {
+            return Ok(false);
+        }
+        self.save_due = None;
+        // On failure the in-memory list and `base` stay as they are, so a
+        // retry still merges correctly with whatever is on disk.
+        let (merged, was_merged) = self.store.save_merged(&self.base, &self.bookmarks)?;
+        let changes = journal::changes(&self.base, &self.bookmarks);
+        if let Err(e) = journal::record(journal::Source::Tui, self.store.path(), &changes) {
+            self.status = Some(format!("Could not write the change log: {e}"));
+        }
+        if let Err(e) = self.hooks.run_for_changes(&changes, Output::Discard) {
+            self.status = Some(e.to_string());
+        }
+        self.dirty = false;
+        self.bookmarks = merged;
+        self.base = self.bookmarks.clone();
+        self.rebuild_rows();
+        Ok(was_merged)
+    }
+
+    /// Flushes pending changes, reporting failures in a popup.
+    fn save(&mut self) {
+        let selected_path = self.selected_bookmark().map(|b| b.path.clone());
+        let was_merged = match self.flush() {
+            Ok(was_merged) => was_merged,
+            Err(e) => {
+                self.show_error(format!("Could not save bookmarks: {e}"), Some(Retry::Save));
+                return;
+            }
+        };
+        if was_merged {
+            self.status = Some("Merged changes from another bm instance".to_string());
+            if let Some(path) = selected_path {
+                self.select_path(&path);
+            }
+        }
+    }
+
+    fn show_error(&mut self, message: String, retry: Option<Retry>) {
+        tracing::warn!("{message}");
+        self.mode = Mode::Error { message, retry };
+    }
+
+    /// Replaces the list with the file's current contents if another process
+    /// changed it, keeping the same bookmark selected where possible.
+    fn reload(&mut self) {
+        let disk = match self.store.load() {
+            Ok(disk) => disk,
+            Err(e) => {
+                self.status = Some(format!("Bookmarks file changed but is invalid: {e}"));
+                return;
+            }
+        };
+        if disk == self.base {
+            return;
+        }
+        let selected_path = self.selected_bookmark().map(|b| b.path.clone());
+        self.bookmarks = disk;
+        self.base = self.bookmarks.clone();
+        self.read_only = self.store.is_read_only();
+        self.rebuild_rows();
+        if let Some(path) = selected_path {
+            self.select_path(&path);
+        }
+        self.status = Some("Reloaded bookmarks changed on disk".to_string());
+    }
+
+    /// Bookmarks the directory, file or remote `host:/dir` typed into the
+    /// add box. A local path that does not exist yet is created as a
+    /// directory, right away with `create` and after asking otherwise.
+    fn add_typed_path(&mut self, input: &str, create: bool) {
+        if input.is_empty() {
+            return;
+        }
+        if remote::split(input).is_some() {
+            self.add_bookmark(input.to_string(), Kind::Remote);
+            return;
+        }
+        let expanded = expand_tilde(input);
+        match add::resolve_path(&expanded, self.canonicalize) {
+            Ok((path, kind)) => self.add_bookmark(path, kind),
+            Err(_) if !Path::new(&expanded).exists() => {
+                let cwd = std::env::current_dir().unwrap_or_default();
+                let path: PathBuf = cwd.join(&expanded).components().collect();
+                let path = path.to_string_lossy().into_owned();
+                if create {
+                    self.create_and_add(path);
+                } else {
+                    self.mode = Mode::ConfirmCreate { path };
+                }
+            }
+            Err(reason) => self.status = Some(format!("Can't bookmark {input}: {reason}")),
+        }
+    }
+
+    /// Marks or unmarks the selected bookmark and moves on to the next.
+    fn toggle_mark(&mut self) {
+        let Some(index) = self.selected_main() else {
+            return;
+        };
+        let path = self.bookmarks[index].path.clone();
+        if !self.marked.remove(&path) {
+            self.marked.insert(path);
+        }
+        self.move_selection(true);
+    }
+
+    /// The directories `y` and `Y` act on: those of the marked bookmarks in
+    /// list order, or the selected one's if none are marked.
+    fn marked_paths(&self) -> Vec<String> {
+        let target = |b: &Bookmark| b.target().unwrap_or_else(|_| b.path.clone());
+        if self.marked.is_empty() {
+            return self.selected_bookmark().map(target).into_iter().collect();
+        }
+        // The recent section's repeats would come first.
+        self.rows
+            .iter()
+            .filter_map(|row| match *row {
+                Row::Bookmark(Source::Main, index) => Some(&self.bookmarks[index]),
+                _ => None,
+            })
+            .filter(|b| self.marked.contains(&b.path))
+            .map(target)
+            .collect()
+    }
+
+    /// Copies `marked_paths` to the clipboard, one per line.
+    fn yank(&mut self) {
+        let paths = self.marked_paths();
+        if paths.is_empty() {
+            return;
+        }
+        self.status = Some(match clipboard::copy(&paths.join("\n")) {
+            Ok(()) if paths.len() == 1 => format!("Copied {}", paths[0]),
+            Ok(()) => format!("Copied {} paths", paths.len()),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// Bookmarks the directory whose path is on the clipboard, as a file
+    /// manager copies it.
+    fn add_pasted_path(&mut self) {
+        let text = match clipboard::paste() {
+            Ok(text) => text,
+            Err(e) => {
+                self.status = Some(e.to_string());
+                return;
+            }
+        };
+        match clipboard::pasted_path(&text) {
+            Ok(path) if !path.is_dir() => self.status = Some(format!("Can't bookmark {}: not a directory", path.display())),
+            Ok(path) => match path.to_str() {
+                Some(path) => self.add_bookmark(path.to_string(), Kind::Dir),
+                None => self.status = Some("Can't bookmark the clipboard: path is not valid UTF-8".to_string()),
+            },
+            Err(reason) => self.status = Some(format!("Can't bookmark the clipboard: {reason}")),
+        }
+    }
+
+    /// Creates `path` with any missing parents and bookmarks it.
+    fn create_and_add(&mut self, path: String) {
+        if self.refuses_changes() {
+            return;
+        }
+        match fs::create_dir_all(&path) {
+            Ok(()) => {
+                self.add_bookmark(path.clone(), Kind::Dir);
+                if self.status.is_none() {
+                    self.status = Some(format!("Created {path}"));
+                }
+            }
+            Err(e) => self.status = Some(format!("Could not create {path}: {e}")),
+        }
+    }
+
+    /// Appends a bookmark named after the directory or file and selects it;
+    /// an existing bookmark for the same path, also through a symlink, is
+    /// selected instead. See [`add::default_name`] for the name.
+    fn add_bookmark(&mut self, path: String, kind: Kind) {
+        if self.refuses_changes() {
+            return;
+        }
+        let path = if self.canonicalize { add::canonical(&path).unwrap_or(path) } else { path };
+        if let Some(pattern) = add::excluded(&self.exclude, &path) {
+            self.status = Some(format!("Can't bookmark {path}: excluded by `{pattern}`"));
+            return;
+        }
+        if let Some(index) = self.bookmarks.iter().position(|b| add::same_dir(&b.path, &path)) {
+            self.select_entry(Source::Main, index);
+            self.status = Some(format!("{path} is already bookmarked"));
+            return;
+        }
+        let wanted = add::name_from_path(&path);
+        let name = add::default_name(&self.bookmarks, &path);
+        if name != wanted {
+            self.status = Some(format!("Named it {name}: {wanted} is already taken"));
+        }
+        self.liveness.insert(path.clone(), Status::Present);
+        if kind == Kind::Dir
+            && let Some(project) = icons::detect(Path::new(&path))
+        {
+            self.project_types.insert(path.clone(), project);
+        }
+        let mut bookmark = Bookmark::new(name, path);
+        bookmark.kind = kind;
+        self.bookmarks.push(bookmark);
+        self.rebuild_rows();
+        self.select_entry(Source::Main, self.bookmarks.len() - 1);
+        self.changed();
+    }
+
+    /// Runs a command typed after `:`, for what has no key of its own or
+    /// needs an argument: `add PATH`, `rename NAME`, `note TEXT`, `tag TAG...`,
+    /// `untag TAG...`, `sort ORDER`, `w`, `q` and `wq`.
+    fn run_command(&mut self, line: &str) -> Action {
+        let (command, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arg = arg.trim();
+        match command {
+            "" => {}
+            "add" | "a" if arg.is_empty() => self.status = Some("Usage: :add PATH".to_string()),
+            "add" | "a" => self.add_typed_path(arg, false),
+            "rename" | "note" | "tag" | "untag" if self.refuses_changes() => {}
+            "rename" if arg.is_empty() => self.status = Some("Usage: :rename NAME".to_string()),
+            "rename" => {
+                if let Some(index) = self.selected_main() {
+                    if let Some(other) = self.bookmarks.iter().position(|b| b.name == arg).filter(|&i| i != index) {
+                        self.status = Some(format!("{arg} is already the name of {}", self.bookmarks[other].path));
+                    } else {
+                        self.bookmarks[index].name = arg.to_string();
+                        self.rebuild_rows();
+                        self.changed();
+                    }
+                }
+            }
+            "note" => {
+                if let Some(index) = self.selected_main() {
+                    self.bookmarks[index].note = arg.to_string();
+                    self.changed();
+                }
+            }
+            "tag" | "untag" if arg.is_empty() => self.status = Some(format!("Usage: :{command} TAG...")),
+            "tag" | "untag" => {
+                if let Some(index) = self.selected_main() {
+                    let tags = &mut self.bookmarks[index].tags;
+                    for tag in arg.split_whitespace() {
+                        if command == "untag" {
+                            tags.retain(|t| t != tag);
+                        } else if !tags.iter().any(|t| t == tag) {
+                            tags.push(tag.to_string());
+                        }
+                    }
+                    self.changed();
+                }
+            }
+            "sort" => {
+                let sort = match arg {
+                    "file" => Some(Sort::File),
+                    "use" | "used" => Some(Sort::Used),
+                    "type" => Some(Sort::ProjectType),
+                    column => toml::Value::String(column.to_string()).try_into().ok().map(Sort::By),
+                };
+                match sort {
+                    Some(sort) => {
+                        self.sort = sort;
+                        self.rebuild_rows();
+                    }
+                    None => {
+                        self.status = Some(format!(
+                            "Can't sort by `{arg}`: use file, used, type, name, path, tags, last_used or hits"
+                        ));
+                    }
+                }
+            }
+            "w" | "write" if self.refuses_changes() => {}
+            "w" | "write" => {
+                self.save();
+                if !matches!(self.mode, Mode::Error { .. }) && self.status.is_none() {
+                    self.status = Some(format!("Saved {}", self.store.path().display()));
+                }
+            }
+            "q" | "quit" => return self.quit(),
+            "wq" | "x" => {
+                self.save();
+                if !matches!(self.mode, Mode::Error { .. }) {
+                    return Action::Quit;
+                }
+            }
+            _ => self.status = Some(format!("Unknown command :{command}")),
+        }
+        Action::Continue
+    }
+
+    /// Picks the selected bookmark as the jump target, unless its path uses
+    /// a variable that is not set, and records the use.
+    fn select_target(&mut self) -> Action {
+        let Some(b) = self.selected_bookmark() else {
+            return Action::Continue;
+        };
+        match b.target() {
+            Ok(path) => {
+                if let Some((Source::Main, index)) = self.selected_entry()
+                    && !self.read_only
+                {
+                    self.bookmarks[index].touch();
+                    self.changed();
+                }
+                Action::Select(path)
+            }
+            Err(var) => {
+                self.status = Some(format!("Can't jump to {}: ${var} is not set", b.path));
+                Action::Continue
+            }
+        }
+    }
+
+    /// Like [`App::select_target`], but a file bookmark asks what to do
+    /// with the file first and a remote one logs into its host.
+    fn pick_target(&mut self) -> Action {
+        let kind = self.selected_bookmark().map(|b| b.kind);
+        match (kind, self.select_target()) {
+            (Some(Kind::File), Action::Select(path)) => {
+                self.mode = Mode::FileAction { path };
+                Action::Continue
+            }
+            (Some(Kind::Remote), Action::Select(path)) => Action::Ssh(path),
+            (_, action) => action,
+        }
+    }
+
+    /// Quits, asking first if `confirm.quit` is set and there are changes
+    /// still to be written.
+    fn quit(&mut self) -> Action {
+        if self.confirm.quit && self.dirty {
+            self.mode = Mode::ConfirmQuit;
+            return Action::Continue;
+        }
+        Action::Quit
+    }
+
+    /// Archives `count` bookmarks from the selected one down, asking first
+    /// if `confirm.delete` is set.
+    fn delete(&mut self, count: usize) {
+        if self.selected_main().is_none() {
+            return;
+        }
+        if self.confirm.delete {
+            let count = self.main_entries_from_selection(count).len();
+            self.mode = Mode::ConfirmDelete { count };
+        } else {
+            self.archive_bookmarks(self.main_entries_from_selection(count));
+        }
+    }
+
+    /// Indices of up to `count` of the user's own bookmarks, as listed from
+    /// the selected one down; the recent section's repeats count once.
+    fn main_entries_from_selection(&self, count: usize) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for row in &self.rows[self.selected.min(self.rows.len())..] {
+            if let Some((Source::Main, index)) = row.entry()
+                && !indices.contains(&index)
+            {
+                indices.push(index);
+            }
+        }
+        indices.truncate(count);
+        indices
+    }
+
+    /// Moves the bookmarks at `indices` into the archive. They are written
+    /// there right away, so they are never only in memory.
+    fn archive_bookmarks(&mut self, mut indices: Vec<usize>) {
+        if self.refuses_changes() {
+            return;
+        }
+        let archived: Vec<Bookmark> = indices.iter().map(|&i| self.bookmarks[i].clone()).collect();
+        match archive::archive(self.store.as_ref(), &archived) {
+            Ok(()) => {
+                indices.sort_unstable_by(|a, b| b.cmp(a));
+                for index in indices {
+                    self.bookmarks.remove(index);
+                }
+                self.rebuild_rows();
+                self.changed();
+                self.status = Some(match archived.as_slice() {
+                    [b] => format!("Archived {}; A shows the archive", b.name),
+                    _ => format!("Archived {} bookmarks; A shows the archive", archived.len()),
+                });
+            }
+            Err(e) => {
+                let what = match archived.as_slice() {
+                    [b] => b.path.clone(),
+                    _ => format!("{} bookmarks", archived.len()),
+                };
+                self.show_error(format!("Could not archive {what}: {e}"), None);
+            }
+        }
+    }
+
+    /// Takes `b` out of the archive and back into the list.
+    fn restore_bookmark(&mut self, b: Bookmark) {
+        if self.refuses_changes() {
+            return;
+        }
+        if self.bookmarks.iter().any(|existing| existing.path == b.path) {
+            self.status = Some(format!("{} is bookmarked already; it stays archived", b.path));
+            return;
+        }
+        let archive = archive::open_archive(self.store.as_ref());
+        let restored = archive.load().and_then(|base| {
+            let remaining: Vec<Bookmark> = base.iter().filter(|a| a.path != b.path).cloned().collect();
+            archive.save_merged(&base, &remaining)
+        });
+        if let Err(e) = restored {
+            self.status = Some(format!("Could not restore {}: {e}", b.path));
+            return;
+        }
+        if let Mode::Archive(view) = &mut self.mode {
+            view.bookmarks.retain(|a| a.path != b.path);
+            view.selected = view.selected.min(view.bookmarks.len().saturating_sub(1));
+        }
+        let mut b = b;
+        b.name = unique_name(&self.bookmarks, &b.name);
+        self.status = Some(format!("Restored {}", b.name));
+        self.bookmarks.push(b);
+        self.rebuild_rows();
+        self.changed();
+    }
+
+    /// Switches to the next profile in the cycle. Does nothing when `--file`
+    /// points somewhere outside the profiles.
+    fn cycle_profile(&mut self) -> Action {
+        match self.load_next_profile() {
+            Ok(true) => {
+                let name = self.profile.as_deref().unwrap_or("default");
+                self.status = Some(format!("Profile: {name}"));
+                Action::ProfileSwitched
+            }
+            Ok(false) => {
+                self.status = Some("Profiles can't be switched while --file is in use".to_string());
+                Action::Continue
+            }
+            Err(e) => {
+                self.status = Some(format!("Could not switch profile: {e}"));
+                Action::Continue
+            }
+        }
+    }
+
+    /// Replaces the list with the profile after the current one. Returns
+    /// `false` when the current file is not a profile.
+    fn load_next_profile(&mut self) -> Result<bool> {
+        if profile::profile_path(self.profile.as_deref())? != self.store.path() {
+            return Ok(false);
+        }
+        self.flush()?;
+        let next = profile::next_profile(self.profile.as_deref(), &profile::list_profiles()?);
+        let store = self.store.with_path(profile::profile_path(next.as_deref())?);
+        let bookmarks = store.load()?;
+        self.store = store;
+        self.base = bookmarks.clone();
+        self.bookmarks = bookmarks;
+        self.profile = next;
+        self.read_only = self.store.is_read_only();
+        self.selected = 0;
+        self.rebuild_rows();
+        Ok(true)
+    }
+
+    /// Range of row indices currently visible in the list.
+    fn visible_range(&self) -> std::ops::Range<usize> {
+        let start = self.list_state.offset().min(self.rows.len());
+        let end = (start + self.list_height).min(self.rows.len());
+        start..end
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        self.status = None;
+        // Raw mode turns Ctrl-C into a key press instead of SIGINT.
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Cancel;
+        }
+        match &mut self.mode {
+            Mode::Normal => self.handle_normal_key(key),
+            Mode::LoadFailed { .. } => {
+                match key.code {
+                    KeyCode::Char('r') => match self.store.restore_from_backup() {
+                        Ok(Some(backup)) => match self.store.load() {
+                            Ok(bookmarks) => {
+                                self.base = bookmarks.clone();
+                                self.bookmarks = bookmarks;
+                                self.read_only = self.store.is_read_only();
+                                self.mode = Mode::Normal;
+                                self.rebuild_rows();
+                                self.status = Some(format!("Restored from {}", backup.display()));
+                            }
+                            Err(e) => self.mode = Mode::LoadFailed { message: e.to_string() },
+                        },
+                        Ok(None) => self.status = Some("No usable backup found".to_string()),
+                        Err(e) => self.status = Some(format!("Restore failed: {e}")),
+                    },
+                    KeyCode::Char('o') => {
+                        self.mode = Mode::Normal;
+                        self.status = Some("Read-only: the bookmarks file was left untouched".to_string());
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::ConfirmDelete { count } => {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        let count = *count;
+                        self.mode = Mode::Normal;
+                        if self.selected_main().is_some() {
+                            self.archive_bookmarks(self.main_entries_from_selection(count));
+                        }
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') => {
+                        self.mode = Mode::Normal;
+                    }
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::ConfirmQuit => {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => return Action::Quit,
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::Palette(palette) => {
+                let control = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Enter => {
+                        let invoke = palette.matches.get(palette.selected).map(|&i| ACTIONS[i].2);
+                        self.mode = Mode::Normal;
+                        return match invoke {
+                            Some(Invoke::Key(code)) => self.handle_normal_key(KeyEvent::from(code)),
+                            Some(Invoke::Prompt(start)) => {
+                                self.mode = Mode::Command { input: start.to_string() };
+                                Action::Continue
+                            }
+                            Some(Invoke::Run(command)) => self.run_command(command),
+                            None => Action::Continue,
+                        };
+                    }
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    KeyCode::Down | KeyCode::Tab => {
+                        palette.selected = (palette.selected + 1).min(palette.matches.len().saturating_sub(1));
+                    }
+                    KeyCode::Char('n') if control => {
+                        palette.selected = (palette.selected + 1).min(palette.matches.len().saturating_sub(1));
+                    }
+                    KeyCode::Up | KeyCode::BackTab => palette.selected = palette.selected.saturating_sub(1),
+                    KeyCode::Char('p') if control => palette.selected = palette.selected.saturating_sub(1),
+                    KeyCode::Backspace => {
+                        palette.input.pop();
+                        palette.search();
+                    }
+                    KeyCode::Char(c) if !control => {
+                        palette.input.push(c);
+                        palette.search();
+                    }
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::Search { input, origin, previous } => {
+                match key.code {
+                    KeyCode::Enter => {
+                        if input.is_empty() {
+                            self.search = previous.take();
+                        }
+                        self.mode = Mode::Normal;
+                        return Action::Continue;
+                    }
+                    KeyCode::Esc => {
+                        self.selected = *origin;
+                        self.search = previous.take();
+                        self.mode = Mode::Normal;
+                        return Action::Continue;
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => return Action::Continue,
+                }
+                self.search = Some(input.to_lowercase()).filter(|q| !q.is_empty());
+                self.selected = *origin;
+                if self.search.is_some() {
+                    self.find_match(true, true);
+                }
+                Action::Continue
+            }
+            Mode::Command { input } => {
+                match key.code {
+                    KeyCode::Enter => {
+                        let line = std::mem::take(input);
+                        self.mode = Mode::Normal;
+                        return self.run_command(line.trim());
+                    }
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    KeyCode::Backspace if input.is_empty() => self.mode = Mode::Normal,
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::EditNote { input } => {
+                match key.code {
+                    KeyCode::Enter => {
+                        let note = input.trim().to_string();
+                        self.mode = Mode::Normal;
+                        if let Some(index) = self.selected_main() {
+                            self.bookmarks[index].note = note;
+                            self.changed();
+                        }
+                    }
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::AddPath { input, candidates, create } => {
+                match key.code {
+                    KeyCode::Enter => {
+                        let input = input.trim().to_string();
+                        let create = *create;
+                        self.mode = Mode::Normal;
+                        self.add_typed_path(&input, create);
+                    }
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    KeyCode::Tab => {
+                        let completion = complete::complete_dir(input);
+                        *input = completion.input;
+                        *candidates = completion.candidates;
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        candidates.clear();
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        candidates.clear();
+                    }
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::ConfirmCreate { path } => {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        let path = std::mem::take(path);
+                        self.mode = Mode::Normal;
+                        self.create_and_add(path);
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::FileAction { path } => {
+                let path = std::mem::take(path);
+                let action = match key.code {
+                    KeyCode::Char('p') | KeyCode::Enter => Action::Select(path),
+                    KeyCode::Char('o') => Action::OpenFile(path),
+                    KeyCode::Char('e') => Action::EditFile(path),
+                    KeyCode::Esc | KeyCode::Char('q') => Action::Continue,
+                    _ => {
+                        self.mode = Mode::FileAction { path };
+                        return Action::Continue;
+                    }
+                };
+                self.mode = Mode::Normal;
+                action
+            }
+            Mode::Browse(browser) => {
+                let target = match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('b') => {
+                        self.mode = Mode::Normal;
+                        return Action::Continue;
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        browser.selected = (browser.selected + 1).min(browser.entries.len().saturating_sub(1));
+                        return Action::Continue;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        browser.selected = browser.selected.saturating_sub(1);
+                        return Action::Continue;
+                    }
+                    KeyCode::Char('u') => {
+                        let path = browser.highlighted().unwrap_or_else(|| browser.dir.clone());
+                        self.add_bookmark(path.to_string_lossy().into_owned(), Kind::Dir);
+                        return Action::Continue;
+                    }
+                    KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right => browser.highlighted(),
+                    KeyCode::Backspace | KeyCode::Char('h') | KeyCode::Left => browser.dir.parent().map(Path::to_path_buf),
+                    _ => None,
+                };
+                if let Some(dir) = target {
+                    let came_from = browser.dir.file_name().map(|n| n.to_string_lossy().into_owned());
+                    match Browser::open(dir) {
+                        Ok(mut next) => {
+                            // Going up keeps the directory we came from highlighted.
+                            if let Some(pos) = came_from.and_then(|name| next.entries.iter().position(|e| *e == name)) {
+                                next.selected = pos;
+                            }
+                            *browser = next;
+                        }
+                        Err(e) => self.status = Some(format!("Can't open directory: {e}")),
+                    }
+                }
+                Action::Continue
+            }
+            Mode::Archive(view) => {
+                match key.code {
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        view.selected = (view.selected + 1).min(view.bookmarks.len().saturating_sub(1));
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+                    KeyCode::Char('r') | KeyCode::Enter => {
+                        if let Some(b) = view.bookmarks.get(view.selected).cloned() {
+                            self.restore_bookmark(b);
+                        }
+                    }
+                    KeyCode::Esc | KeyCode::Char('A') | KeyCode::Char('q') => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::Stats { lines, scroll } => {
+                match key.code {
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        *scroll = (*scroll + 1).min(lines.len().saturating_sub(1) as u16);
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => *scroll = scroll.saturating_sub(1),
+                    _ => self.mode = Mode::Normal,
+                }
+                Action::Continue
+            }
+            Mode::Error { retry, .. } => {
+                match key.code {
+                    KeyCode::Char('r') if retry.is_some() => {
+                        let retry = retry.take();
+                        self.mode = Mode::Normal;
+                        match retry {
+                            Some(Retry::Save) => self.save(),
+                            Some(Retry::EditNote) => return Action::EditNoteExternally,
+                            None => {}
+                        }
+                    }
+                    KeyCode::Char('i') | KeyCode::Esc | KeyCode::Enter => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                Action::Continue
+            }
+            Mode::Hint { labels, targets, typed } => {
+                let KeyCode::Char(c) = key.code else {
+                    self.mode = Mode::Normal;
+                    return Action::Continue;
+                };
+                typed.push(c);
+                let matched = labels.iter().position(|l| l == typed).map(|pos| targets[pos]);
+                let pending = labels.iter().any(|l| l.starts_with(typed.as_str()));
+                if let Some(row) = matched {
+                    self.mode = Mode::Normal;
+                    self.selected = row;
+                    return self.pick_target();
+                } else if !pending {
+                    self.mode = Mode::Normal;
+                }
+                Action::Continue
+            }
+        }
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) -> Action {
+        // `o` and a digit sort by that column; any other key after `o` is
+        // handled as usual.
+        if std::mem::take(&mut self.sort_prefix)
+            && let KeyCode::Char(digit @ '1'..='9') = key.code
+        {
+            let column = digit.to_digit(10).and_then(|n| self.columns.get(n as usize - 1)).copied();
+            match column {
+                Some(column) => {
+                    self.sort = Sort::By(column);
+                    self.rebuild_rows();
+                    self.status = Some(format!("Sorted by {}", column_title(column).to_lowercase()));
+                }
+                None => self.status = Some(format!("There is no column {digit}")),
+            }
+            return Action::Continue;
+        }
+        // A count typed before a move or `!` repeats it.
+        let count = self.count.take();
+        if let KeyCode::Char(digit @ '0'..='9') = key.code {
+            let digit = digit as usize - '0' as usize;
+            if count.is_none() && digit == 0 {
+                return Action::Continue;
+            }
+            let n = count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+            self.count = Some(n);
+            self.status = Some(n.to_string());
+            return Action::Continue;
+        }
+        if let Some(n) = count
+            && matches!(key.code, KeyCode::Char('j' | 'k' | '!') | KeyCode::Down | KeyCode::Up)
+        {
+            match key.code {
+                KeyCode::Char('!') => self.delete(n),
+                code => {
+                    let down = matches!(code, KeyCode::Char('j') | KeyCode::Down);
+                    for _ in 0..n.min(self.rows.len()) {
+                        self.move_selection(down);
+                    }
+                }
+            }
+            return Action::Continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => return self.quit(),
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.mode = Mode::Palette(Palette::new());
+            }
+            KeyCode::Char('p') => return self.cycle_profile(),
+            KeyCode::Char(':') => self.mode = Mode::Command { input: String::new() },
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search { input: String::new(), origin: self.selected, previous: self.search.take() };
+            }
+            KeyCode::Char('n') => self.find_match(true, false),
+            KeyCode::Char('N') => self.find_match(false, false),
+            KeyCode::Esc if self.search.is_some() => self.search = None,
+            KeyCode::Esc => self.marked.clear(),
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('y') => self.yank(),
+            KeyCode::Char('Y') => {
+                let paths = self.marked_paths();
+                if !paths.is_empty() {
+                    return Action::PrintPaths(paths);
+                }
+            }
+            KeyCode::Char('j') | KeyCode::Down => self.move_selection(true),
+            KeyCode::Char('k') | KeyCode::Up => self.move_selection(false),
+            KeyCode::Char('u') => {
+                let cwd = self.cwd.clone().or_else(|| std::env::current_dir().ok());
+                if let Some(cwd) = cwd
+                    && let Some(cwd_str) = cwd.to_str()
+                {
+                    if cwd.is_dir() {
+                        self.add_bookmark(cwd_str.to_string(), Kind::Dir);
+                    } else {
+                        self.status = Some(format!("Can't bookmark {cwd_str}: not a directory"));
+                    }
+                }
+            }
+            KeyCode::Char('U') => {
+                let cwd = self.cwd.clone().or_else(|| std::env::current_dir().ok());
+                if let Some(cwd) = cwd {
+                    match add::git_root(&cwd).map(|root| root.to_str()) {
+                        Some(Some(root)) => self.add_bookmark(root.to_string(), Kind::Dir),
+                        Some(None) => self.status = Some("Can't bookmark the repository: path is not valid UTF-8".to_string()),
+                        None => self.status = Some(format!("{} is not inside a git repository", cwd.display())),
+                    }
+                }
+            }
+            KeyCode::Char('P') => self.add_pasted_path(),
+            KeyCode::Char('+') => match self.selected_entry() {
+                Some((Source::Suggested, index)) => {
+                    let path = self.suggestions[index].path.clone();
+                    self.add_bookmark(path, Kind::Dir);
+                }
+                _ => self.status = Some("Select a suggested directory to bookmark it".to_string()),
+            },
+            KeyCode::Char('z') => {
+                self.suggestions_collapsed = !self.suggestions_collapsed;
+                self.rebuild_rows();
+            }
+            KeyCode::Char('b') => {
+                let start = self
+                    .selected_bookmark()
+                    .and_then(|b| b.target().ok())
+                    .map(PathBuf::from)
+                    .filter(|path| path.is_dir())
+                    .or_else(dirs::home_dir)
+                    .unwrap_or_else(|| PathBuf::from("/"));
+                match Browser::open(start) {
+                    Ok(browser) => self.mode = Mode::Browse(browser),
+                    Err(e) => self.status = Some(format!("Can't open directory: {e}")),
+                }
+            }
+            KeyCode::Char('a' | 'c' | 'e' | 'E') if self.refuses_changes() => {}
+            KeyCode::Char('a') => {
+                self.mode = Mode::AddPath { input: String::new(), candidates: Vec::new(), create: false };
+            }
+            KeyCode::Char('A') => match archive::open_archive(self.store.as_ref()).load() {
+                Ok(bookmarks) => {
+                    self.mode = Mode::Archive(ArchiveView { bookmarks, selected: 0, list_state: TableState::default() });
+                }
+                Err(e) => self.status = Some(format!("Could not read the archive: {e}")),
+            },
+            KeyCode::Char('S') => {
+                let status: Vec<Status> = self
+                    .bookmarks
+                    .iter()
+                    .map(|b| self.liveness.get(&b.path).copied().unwrap_or(Status::Unknown))
+                    .collect();
+                let lines = Stats::new(&self.bookmarks, &status, unix_now()).report();
+                self.mode = Mode::Stats { lines, scroll: 0 };
+            }
+            KeyCode::Char('s') => {
+                let (sort, message) = match self.sort {
+                    Sort::File => (Sort::Used, format!("Sorted by use ({})", self.ranker.ranking.label())),
+                    Sort::By(_) | Sort::Used => (Sort::ProjectType, "Grouped by project type".to_string()),
+                    Sort::ProjectType => (Sort::File, "Sorted in file order".to_string()),
+                };
+                self.sort = sort;
+                self.rebuild_rows();
+                self.status = Some(message);
+            }
+            KeyCode::Char('o') => {
+                self.sort_prefix = true;
+                self.status = Some("Sort by column: 1–9".to_string());
+            }
+            KeyCode::Char('c') => {
+                self.mode = Mode::AddPath { input: String::new(), candidates: Vec::new(), create: true };
+            }
+            KeyCode::Char('F') => {
+                // All, then each type in turn, then all again.
+                let next = match self.type_filter {
+                    None => Some(0),
+                    Some(current) => ProjectType::ALL.iter().position(|&t| t == current).map(|i| i + 1),
+                };
+                self.type_filter = next.and_then(|i| ProjectType::ALL.get(i).copied());
+                self.rebuild_rows();
+                self.status = Some(match self.type_filter {
+                    Some(wanted) => format!("Showing {} only", wanted.label()),
+                    None => "Showing all bookmarks".to_string(),
+                });
+            }
+            KeyCode::Char('!') => self.delete(1),
+            KeyCode::Char('e') => {
+                if let Some(index) = self.selected_main() {
+                    self.mode = Mode::EditNote { input: self.bookmarks[index].note.clone() };
+                }
+            }
+            KeyCode::Char('E') if self.selected_main().is_some() => return Action::EditNoteExternally,
+            KeyCode::Char('f') => {
+                let targets: Vec<usize> = self
+                    .visible_range()
+                    .filter(|&row| self.rows[row].entry().is_some())
+                    .collect();
+                if !targets.is_empty() {
+                    self.mode = Mode::Hint {
+                        labels: hint_labels(targets.len()),
+                        targets,
+                        typed: String::new(),
+                    };
+                }
+            }
+            KeyCode::Enter => return self.pick_target(),
+            KeyCode::Char('x') => {
+                let Some(b) = self.selected_bookmark().cloned() else {
+                    return Action::Continue;
+                };
+                if b.command.is_none() {
+                    self.status = Some(format!("{} has no command; set `command` for it in the bookmarks file", b.name));
+                    return Action::Continue;
+                }
+                return match self.select_target() {
+                    Action::Select(path) => Action::Run { bookmark: b, path },
+                    action => action,
+                };
+            }
+            KeyCode::Char('T') => {
+                let bookmark = self.selected_bookmark().map(|b| (b.name.clone(), b.workspace.clone()));
+                return match (bookmark, self.select_target()) {
+                    (Some((name, workspace)), Action::Select(path)) => Action::Tmux { name, path, workspace },
+                    (_, action) => action,
+                };
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+}
+
+impl Drop for App {
+    /// Last chance to write pending changes when the event loop unwinds from
+    /// a panic; normal exits have flushed already.
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// How long ago `time` was, in the largest unit that fits: `5m ago`, `3d ago`.
+fn ago(time: u64, now: u64) -> String {
+    let secs = now.saturating_sub(time);
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        86_400..2_592_000 => format!("{}d ago", secs / 86_400),
+        2_592_000..31_536_000 => format!("{}mo ago", secs / 2_592_000),
+        _ => format!("{}y ago", secs / 31_536_000),
+    }
+}
+
+/// Room the highlight symbol takes at the start of every row.
+const HIGHLIGHT_WIDTH: usize = 2;
+
+/// What the list shows for every bookmark, and how wide its columns are.
+#[derive(Clone)]
+struct ListView<'a> {
+    /// The columns shown, in order; the path is always one of them.
+    columns: &'a [Column],
+    /// Widths set in `config.toml`, used instead of fitted ones.
+    fixed_widths: &'a HashMap<Column, u16>,
+    /// The column the list is sorted by, marked in the header.
+    sorted_by: Option<Column>,
+    /// Flags bookmarks unused for this many days.
+    stale_after: Option<u64>,
+    now: u64,
+    liveness: &'a HashMap<String, Status>,
+    icons: Icons,
+    project_types: &'a HashMap<String, ProjectType>,
+    /// Width of the hint label column in front, zero when there are no labels.
+    label_width: usize,
+    /// Width of each of `columns`; long paths are shortened in the middle,
+    /// and other columns are left out at zero.
+    widths: Vec<usize>,
+    /// The `/` search, in lower case, highlighted in names and paths.
+    search: Option<&'a str>,
+}
+
+impl ListView<'_> {
+    /// Divides `width` columns between the cells of `bookmarks`: the name
+    /// and the tags get what they need up to a quarter each, the path gets
+    /// the rest. `titles` are the section headers, shown in the first column.
+    fn fit(&mut self, bookmarks: &[&Bookmark], titles: &[&str], width: usize) {
+        let room = width.saturating_sub(HIGHLIGHT_WIDTH);
+        let quarter = (room / 4).max(8);
+        let widest = |widths: &mut dyn Iterator<Item = usize>| widths.max().unwrap_or(0);
+        self.widths = self
+            .columns
+            .iter()
+            .map(|&column| match (self.fixed_widths.get(&column), column) {
+                (Some(&fixed), _) => fixed as usize,
+                (None, Column::Name) => {
+                    let names = bookmarks.iter().map(|b| width::columns(&b.name));
+                    widest(&mut names.chain(titles.iter().map(|t| width::columns(t)))).min(quarter)
+                }
+                (None, Column::Tags) => widest(&mut bookmarks.iter().map(|b| Line::from(tags_spans(b)).width())).min(quarter),
+                (None, Column::LastUsed) => 8,
+                (None, Column::Hits) => 6,
+                // Filled in below.
+                (None, Column::Path) => 0,
+            })
+            .collect();
+        if let Some(path) = self.columns.iter().position(|&c| c == Column::Path)
+            && !self.fixed_widths.contains_key(&Column::Path)
+        {
+            let shown: Vec<usize> = self.shown().map(|(_, width)| width).collect();
+            let cells = shown.len() + usize::from(self.label_width > 0);
+            let used = self.label_width + shown.iter().sum::<usize>() + cells.saturating_sub(1);
+            self.widths[path] = room.saturating_sub(used);
+        }
+    }
+
+    /// The columns drawn and their widths.
+    fn shown(&self) -> impl Iterator<Item = (Column, usize)> + '_ {
+        self.columns
+            .iter()
+            .copied()
+            .zip(self.widths.iter().copied())
+            .filter(|&(column, width)| width > 0 || column == Column::Path)
+    }
+
+    fn path_width(&self) -> usize {
+        self.shown().find(|&(column, _)| column == Column::Path).map_or(0, |(_, width)| width)
+    }
+
+    fn constraints(&self) -> Vec<Constraint> {
+        let label = (self.label_width > 0).then_some(self.label_width);
+        label.into_iter().chain(self.shown().map(|(_, width)| width)).map(|width| Constraint::Length(width as u16)).collect()
+    }
+
+    /// The row naming the columns.
+    fn header(&self) -> TableRow<'static> {
+        let label = (self.label_width > 0).then(Cell::default);
+        let titles = self.shown().map(|(column, _)| {
+            let title = column_title(column);
+            let title = if self.sorted_by == Some(column) { format!("{title} ▾") } else { title.to_string() };
+            Cell::from(aligned(column, Line::from(title)))
+        });
+        TableRow::new(label.into_iter().chain(titles).collect::<Vec<_>>()).style(Style::default().add_modifier(Modifier::BOLD))
+    }
+
+    /// Where the path cell starts, counted from the first column after the border.
+    fn path_offset(&self) -> usize {
+        let label = if self.label_width > 0 { self.label_width + 1 } else { 0 };
+        let before: usize = self.shown().take_while(|&(column, _)| column != Column::Path).map(|(_, width)| width + 1).sum();
+        HIGHLIGHT_WIDTH + label + before
+    }
+}
+
+fn column_title(column: Column) -> &'static str {
+    match column {
+        Column::Name => Text::ColumnName.get(),
+        Column::Path => Text::ColumnPath.get(),
+        Column::Tags => Text::ColumnTags.get(),
+        Column::LastUsed => Text::ColumnLastUsed.get(),
+        Column::Hits => Text::ColumnHits.get(),
+    }
+}
+
+/// How `a` and `b` are ordered when sorting by `column`. Bookmarks without
+/// tags, or never used, go last.
+fn compare_by(column: Column, a: &Bookmark, b: &Bookmark) -> std::cmp::Ordering {
+    use std::cmp::Reverse;
+    match column {
+        Column::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        Column::Path => a.path.cmp(&b.path),
+        Column::Tags => (a.tags.is_empty(), &a.tags).cmp(&(b.tags.is_empty(), &b.tags)),
+        Column::LastUsed => Reverse(a.last_used).cmp(&Reverse(b.last_used)),
+        Column::Hits => Reverse(a.hits).cmp(&Reverse(b.hits)),
+    }
+}
+
+/// `line` aligned the way `column` is: numbers and times to the right.
+fn aligned(column: Column, line: Line) -> Line {
+    match column {
+        Column::LastUsed | Column::Hits => line.right_aligned(),
+        Column::Name | Column::Path | Column::Tags => line,
+    }
+}
+
+/// A section header, with its title in the first column.
+fn header_row<'a>(title: &'a str, detail: Option<&'a str>, view: &ListView) -> TableRow<'a> {
+    let label = (view.label_width > 0).then_some("");
+    let cells: Vec<&str> = label.into_iter().chain([title, detail.unwrap_or("")]).collect();
+    TableRow::new(cells).style(Style::default().add_modifier(Modifier::BOLD | Modifier::DIM))
+}
+
+/// The row of `b`: the hint `label`, if labels are shown, then a cell for
+/// every column.
+fn bookmark_row<'a>(b: &'a Bookmark, view: &ListView, label: Option<Span<'a>>) -> TableRow<'a> {
+    let label = (view.label_width > 0).then(|| Cell::from(label.unwrap_or_default()));
+    let cells = view.shown().map(|(column, _)| {
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        match column {
+            Column::Name => Cell::from(Line::from(match_spans(&b.name, Style::default(), view.search))),
+            Column::Path => {
+                let (mut spans, path) = path_spans(b, view);
+                let path_span = spans.remove(path);
+                spans.splice(path..path, match_spans(&path_span.content, path_span.style, view.search));
+                Cell::from(Line::from(spans))
+            }
+            Column::Tags => Cell::from(Line::from(tags_spans(b))),
+            Column::LastUsed => {
+                let ago = b.last_used.map(|t| ago(t, view.now)).unwrap_or_default();
+                Cell::from(aligned(column, Line::from(ago))).style(dim)
+            }
+            Column::Hits => Cell::from(aligned(column, Line::from(b.hits.to_string()))).style(dim),
+        }
+    });
+    TableRow::new(label.into_iter().chain(cells).collect::<Vec<_>>())
+}
+
+/// `text` in `style`, with every occurrence of `query`, ignoring case, highlighted.
+fn match_spans(text: &str, style: Style, query: Option<&str>) -> Vec<Span<'static>> {
+    let lower = text.to_lowercase();
+    // Lower case that changes lengths would put the matches elsewhere in `text`.
+    let Some(query) = query.filter(|q| !q.is_empty() && lower.len() == text.len()) else {
+        return vec![Span::styled(text.to_string(), style)];
+    };
+    let found = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for (i, _) in lower.match_indices(query) {
+        let end = i + query.len();
+        if !text.is_char_boundary(i) || !text.is_char_boundary(end) {
+            continue;
+        }
+        if i > start {
+            spans.push(Span::styled(text[start..i].to_string(), style));
+        }
+        spans.push(Span::styled(text[i..end].to_string(), style.patch(found)));
+        start = end;
+    }
+    spans.push(Span::styled(text[start..].to_string(), style));
+    spans
+}
+
+/// The path cell of `b`, with the icon before the path and what is known
+/// about it after, and which of the spans is the path.
+fn path_spans<'a>(b: &'a Bookmark, view: &ListView) -> (Vec<Span<'a>>, usize) {
+    let mut spans = Vec::new();
+    if let Some((icon, color)) = icons::icon(view.icons, b.kind, view.project_types.get(&b.path).copied()) {
+        spans.push(Span::styled(format!("{icon} "), Style::default().fg(color)));
+    }
+    let path = spans.len();
+    match view.stale_after.and_then(|days| archive::idle_days(b, view.now).filter(|&idle| idle >= days)) {
+        Some(idle) => {
+            spans.push(Span::styled("", Style::default().fg(Color::DarkGray)));
+            spans.push(Span::styled(format!(" (unused for {idle}d)"), Style::default().fg(Color::Yellow)));
+        }
+        None => spans.push(Span::raw("")),
+    }
+    match b.kind {
+        Kind::Dir => {}
+        Kind::File => spans.push(Span::styled(" (file)", Style::default().add_modifier(Modifier::DIM))),
+        Kind::Remote => spans.push(Span::styled(" (ssh)", Style::default().fg(Color::Magenta))),
+    }
+    match (b.target(), view.liveness.get(&b.path)) {
+        (Err(var), _) => spans.push(Span::styled(format!(" (${var} not set)"), Style::default().fg(Color::Red))),
+        (Ok(_), Some(Status::Missing)) => spans.push(Span::styled(" (missing)", Style::default().fg(Color::Red))),
+        (Ok(_), Some(Status::Unknown)) => spans.push(Span::styled(" (unknown)", Style::default().fg(Color::DarkGray))),
+        (Ok(_), Some(Status::Present) | None) => {}
+    }
+    // What is said about the path should stay visible.
+    let rest: usize = spans.iter().map(Span::width).sum();
+    let shown = match b.kind {
+        Kind::Remote => b.path.clone(),
+        _ => paths::contract_tilde(&b.path),
+    };
+    spans[path].content = width::ellipsize_path(&shown, view.path_width().saturating_sub(rest)).into_owned().into();
+    (spans, path)
+}
+
+/// The tags cell: the `#tag`s, then the first line of the note.
+fn tags_spans(b: &Bookmark) -> Vec<Span<'_>> {
+    let mut spans: Vec<Span> =
+        b.tags.iter().map(|tag| Span::styled(format!("#{tag} "), Style::default().fg(Color::Cyan))).collect();
+    let mut lines = b.note.lines();
+    if let Some(note) = lines.next() {
+        let more = if lines.next().is_some() { " …" } else { "" };
+        spans.push(Span::styled(format!("{note}{more}"), Style::default().add_modifier(Modifier::DIM)));
+    }
+    spans
+}
+
+/// A rectangle `percent_x` of the width of `area` and `height` rows tall,
+/// centered inside it.
+fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x / 100;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height: height.min(area.height),
+    }
+}
+
+fn draw(f: &mut Frame, app: &mut App) {
+    let size = f.area();
+    let footer_height = match app.mode {
+        Mode::ConfirmDelete { .. }
+        | Mode::ConfirmQuit
+        | Mode::EditNote { .. }
+        | Mode::AddPath { .. }
+        | Mode::ConfirmCreate { .. }
+        | Mode::FileAction { .. } => 3,
+        _ => 1,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(footer_height),
+        ])
+        .split(size);
+
+    // Inside the borders and below the column titles.
+    app.list_height = chunks[0].height.saturating_sub(3) as usize;
+
+    let hints = match &app.mode {
+        Mode::Hint { labels, targets, typed } => Some((labels, targets, typed)),
+        _ => None,
+    };
+    let mut view = ListView {
+        columns: &app.columns,
+        fixed_widths: &app.column_widths,
+        sorted_by: match app.sort {
+            Sort::By(column) => Some(column),
+            Sort::File | Sort::Used | Sort::ProjectType => None,
+        },
+        stale_after: app.stale_after,
+        now: unix_now(),
+        liveness: &app.liveness,
+        icons: app.icons,
+        project_types: &app.project_types,
+        label_width: hints.map_or(0, |(labels, _, _)| labels.first().map_or(0, |l| l.len()) + 2),
+        widths: Vec::new(),
+        search: app.search.as_deref(),
+    };
+    let shown: Vec<&Bookmark> = app.rows.iter().filter_map(|row| row.entry()).filter_map(|(s, i)| app.bookmark(s, i)).collect();
+    let titles: Vec<&str> = app
+        .rows
+        .iter()
+        .filter_map(|row| match row {
+            Row::Header(title, _) => Some(title.as_str()),
+            _ => None,
+        })
+        .collect();
+    view.fit(&shown, &titles, chunks[0].width.saturating_sub(2) as usize);
+    let rows: Vec<TableRow> = app
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let b = match *row {
+                Row::Header(ref title, ref detail) => return header_row(title, detail.as_deref(), &view),
+                // Looked up field by field so `list_state` stays free for rendering.
+                Row::Bookmark(Source::Main, index) | Row::Recent(Source::Main, index) => &app.bookmarks[index],
+                Row::Bookmark(Source::Project, index) | Row::Recent(Source::Project, index) => {
+                    &app.project.as_ref().expect("project row without a project").bookmarks[index]
+                }
+                Row::Bookmark(Source::Suggested, index) | Row::Recent(Source::Suggested, index) => &app.suggestions[index],
+            };
+            let marked = matches!(row, Row::Bookmark(Source::Main, _) | Row::Recent(Source::Main, _))
+                && app.marked.contains(&b.path);
+            let style = if marked { Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD) } else { Style::default() };
+            let Some((labels, targets, typed)) = hints else {
+                return bookmark_row(b, &view, None).style(style);
+            };
+            let label = targets
+                .iter()
+                .position(|&row| row == i)
+                .map(|pos| &labels[pos])
+                .filter(|l| l.starts_with(typed.as_str()));
+            let label = match label {
+                Some(l) => Span::styled(format!("[{l}]"), Style::default().fg(Color::Black).bg(Color::Yellow)),
+                None => Span::raw(""),
+            };
+            bookmark_row(b, &view, Some(label)).style(style)
+        })
+        .collect();
+
+    let mut title = match &app.profile {
+        Some(name) => format!("{} ({name})", Text::TitleBookmarks.get()),
+        None => Text::TitleBookmarks.get().to_string(),
+    };
+    if app.read_only {
+        title.push_str(" [read-only]");
+    }
+    if !app.marked.is_empty() {
+        title.push_str(&format!(" [{} marked]", app.marked.len()));
+    }
+    let list = Table::new(rows, view.constraints())
+        .header(view.header())
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .row_highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black))
+        .highlight_symbol("→ ");
+
+    app.list_state.select(Some(app.selected));
+    f.render_stateful_widget(list, chunks[0], &mut app.list_state);
+    // Dialogs drawn over the list would be painted over again.
+    app.links = if app.hyperlinks && matches!(app.mode, Mode::Normal | Mode::Hint { .. }) {
+        find_links(f.buffer_mut(), chunks[0], app, &view)
+    } else {
+        Vec::new()
+    };
+
+    match &mut app.mode {
+        Mode::Browse(browser) => {
+            draw_browser(f, browser, &app.bookmarks, app.status.as_deref(), chunks[0], chunks[1]);
+        }
+        Mode::Archive(archive) => {
+            draw_archive(f, archive, &view, app.status.as_deref(), chunks[0], chunks[1]);
+        }
+        Mode::Palette(palette) => draw_palette(f, palette, chunks[0], chunks[1]),
+        _ => {}
+    }
+
+    match &app.mode {
+        Mode::LoadFailed { message } => {
+            let mut text = vec![
+                Line::styled(
+                    i18n::could_not_load(&app.store.path().display().to_string()),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+                Line::raw(""),
+            ];
+            text.extend(message.lines().map(|l| Line::raw(l.to_string())));
+            text.extend([
+                Line::raw(""),
+                Line::raw(Text::LoadFailedRestore.get()),
+                Line::raw(Text::LoadFailedReadOnly.get()),
+                Line::raw(Text::LoadFailedQuit.get()),
+            ]);
+            if let Some(status) = &app.status {
+                text.push(Line::raw(""));
+                text.push(Line::styled(status.as_str(), Style::default().fg(Color::Yellow)));
+            }
+            let error = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL).title(Text::TitleError.get()))
+                .wrap(Wrap { trim: false });
+            f.render_widget(Clear, size);
+            f.render_widget(error, size);
+        }
+        Mode::ConfirmDelete { count } => {
+            // Confirmation dialog
+            let question = match *count {
+                1 => Text::ConfirmArchive.get().to_string(),
+                count => i18n::confirm_archive_many(count),
+            };
+            let confirm = Paragraph::new(question)
+                .block(Block::default().borders(Borders::ALL).title(Text::TitleConfirm.get()))
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(confirm, chunks[1]);
+        }
+        Mode::ConfirmQuit => {
+            let confirm = Paragraph::new(Text::ConfirmQuit.get())
+                .block(Block::default().borders(Borders::ALL).title(Text::TitleConfirm.get()))
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(confirm, chunks[1]);
+        }
+        Mode::EditNote { input } => {
+            let editor = Paragraph::new(input.as_str())
+                .block(Block::default().borders(Borders::ALL).title(Text::HelpNote.get()));
+            f.render_widget(editor, chunks[1]);
+            let width = width::columns(input) as u16;
+            f.set_cursor_position((chunks[1].x + 1 + width, chunks[1].y + 1));
+        }
+        Mode::AddPath { input, candidates, create } => {
+            let title = if *create { Text::HelpNewDir.get() } else { Text::HelpAddPath.get() };
+            let mut block = Block::default().borders(Borders::ALL).title(title);
+            if !candidates.is_empty() {
+                block = block.title_bottom(Line::styled(
+                    candidates.join("  "),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+            }
+            let editor = Paragraph::new(input.as_str()).block(block);
+            f.render_widget(editor, chunks[1]);
+            let width = width::columns(input) as u16;
+            f.set_cursor_position((chunks[1].x + 1 + width, chunks[1].y + 1));
+        }
+        Mode::ConfirmCreate { path } => {
+            let confirm = Paragraph::new(i18n::confirm_create(path))
+                .block(Block::default().borders(Borders::ALL).title(Text::TitleConfirm.get()))
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(confirm, chunks[1]);
+        }
+        Mode::FileAction { path } => {
+            let prompt = Paragraph::new(path.as_str())
+                .block(Block::default().borders(Borders::ALL).title(Text::HelpFileAction.get()));
+            f.render_widget(prompt, chunks[1]);
+        }
+        Mode::Error { message, retry } => {
+            let keys = if retry.is_some() { Text::HelpRetry.get() } else { Text::HelpDismiss.get() };
+            let text = vec![
+                Line::raw(message.as_str()),
+                Line::raw(""),
+                Line::styled(keys, Style::default().add_modifier(Modifier::DIM)),
+            ];
+            let area = centered_rect(60, 7, size);
+            let popup = Paragraph::new(text)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(Text::TitleError.get())
+                        .border_style(Style::default().fg(Color::Red)),
+                )
+                .wrap(Wrap { trim: false });
+            f.render_widget(Clear, area);
+            f.render_widget(popup, area);
+            f.render_widget(Block::default().borders(Borders::BOTTOM), chunks[1]);
+        }
+        Mode::Stats { lines, scroll } => {
+            let text: Vec<Line> = lines.iter().map(|l| Line::raw(l.as_str())).collect();
+            let stats = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL).title(Text::TitleStats.get()))
+                .scroll((*scroll, 0));
+            f.render_widget(Clear, chunks[0]);
+            f.render_widget(stats, chunks[0]);
+            let help = Span::raw(Text::HelpStats.get());
+            f.render_widget(Block::default().title(help).borders(Borders::BOTTOM), chunks[1]);
+        }
+        // Drawn above, as they need the mode mutably.
+        Mode::Browse(_) | Mode::Archive(_) | Mode::Palette(_) => {}
+        Mode::Search { input, .. } => {
+            let line = format!("/{input}");
+            f.render_widget(Paragraph::new(line.as_str()), chunks[1]);
+            f.set_cursor_position((chunks[1].x + width::columns(&line) as u16, chunks[1].y));
+        }
+        Mode::Command { input } => {
+            let line = format!(":{input}");
+            f.render_widget(Paragraph::new(line.as_str()), chunks[1]);
+            f.set_cursor_position((chunks[1].x + width::columns(&line) as u16, chunks[1].y));
+        }
+        Mode::Hint { .. } => {
+            let help = Span::raw(Text::HelpHint.get());
+            f.render_widget(Block::default().title(help).borders(Borders::BOTTOM), chunks[1]);
+        }
+        Mode::Normal => {
+            // Help message at bottom
+            let help_text = Text::HelpNormal.get();
+            let help = match &app.status {
+                Some(status) => Span::styled(status.as_str(), Style::default().fg(Color::Yellow)),
+                None => Span::raw(help_text),
+            };
+            f.render_widget(
+                Block::default()
+                    .title(help)
+                    .borders(Borders::BOTTOM),
+                chunks[1],
+            );
+        }
+    }
+}
+
+/// A path on screen and the cells it was drawn into.
+struct Link {
+    x: u16,
+    y: u16,
+    url: String,
+    cells: Vec<ratatui::buffer::Cell>,
+}
+
+/// Where the visible bookmarks' paths ended up in the list drawn into
+/// `area`. Paths cut off at the edge are left out.
+fn find_links(buffer: &ratatui::buffer::Buffer, area: Rect, app: &App, view: &ListView) -> Vec<Link> {
+    let inner = area.inner(Margin::new(1, 1));
+    let offset = app.list_state.offset();
+    let mut links = Vec::new();
+    for (line, row) in app.rows.iter().skip(offset).take(app.list_height).enumerate() {
+        let Some(b) = row.entry().and_then(|(source, index)| app.bookmark(source, index)) else {
+            continue;
+        };
+        if b.kind == Kind::Remote {
+            continue;
+        }
+        let Ok(target) = b.target() else {
+            continue;
+        };
+        // Below the column titles.
+        let y = inner.y + 1 + line as u16;
+        let url = paths::file_url(&target);
+        let (spans, path) = path_spans(b, view);
+        let shown = &spans[path].content;
+        let cells = inner.left() + view.path_offset() as u16..inner.right();
+        let found = cells.clone().find_map(|x| cells_showing(buffer, x, y, cells.end, shown));
+        if let Some(columns) = found {
+            let cells = columns.iter().map(|&x| buffer[(x, y)].clone()).collect();
+            links.push(Link { x: columns[0], y, url, cells });
+        }
+    }
+    links
+}
+
+/// The columns of the cells that show `text` from `x` on, if they do.
+/// Each cell holds one grapheme; the cell after a wide one is left out, as
+/// printing the wide one covers it.
+fn cells_showing(buffer: &ratatui::buffer::Buffer, x: u16, y: u16, right: u16, text: &str) -> Option<Vec<u16>> {
+    let mut rest = text;
+    let mut columns = Vec::new();
+    let mut next = x;
+    while !rest.is_empty() {
+        if next >= right {
+            return None;
+        }
+        let symbol = buffer[(next, y)].symbol();
+        rest = rest.strip_prefix(symbol).filter(|_| !symbol.is_empty())?;
+        columns.push(next);
+        next += width::columns(symbol).max(1) as u16;
+    }
+    Some(columns)
+}
+
+/// Prints the cells of each link again, wrapped in an OSC 8 hyperlink.
+/// ratatui can't draw these itself, as it counts the escape sequence as
+/// visible text; its buffer still matches what is on screen afterwards.
+fn write_links(out: &mut impl io::Write, links: &[Link]) -> io::Result<()> {
+    // ratatui's own crossterm, which its colors convert into.
+    use ratatui::crossterm::{
+        cursor::{MoveTo, RestorePosition, SavePosition},
+        queue,
+        style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    };
+    if links.is_empty() {
+        return Ok(());
+    }
+    queue!(out, SavePosition)?;
+    for link in links {
+        queue!(out, MoveTo(link.x, link.y), Print(format!("\x1b]8;;{}\x1b\\", link.url)))?;
+        for cell in &link.cells {
+            queue!(
+                out,
+                SetAttribute(Attribute::Reset),
+                SetForegroundColor(cell.fg.into()),
+                SetBackgroundColor(cell.bg.into()),
+            )?;
+            for (modifier, attribute) in [
+                (Modifier::BOLD, Attribute::Bold),
+                (Modifier::DIM, Attribute::Dim),
+                (Modifier::ITALIC, Attribute::Italic),
+                (Modifier::UNDERLINED, Attribute::Underlined),
+                (Modifier::REVERSED, Attribute::Reverse),
+            ] {
+                if cell.modifier.contains(modifier) {
+                    queue!(out, SetAttribute(attribute))?;
+                }
+            }
+            queue!(out, Print(cell.symbol()))?;
+        }
+        queue!(out, SetAttribute(Attribute::Reset), Print("\x1b]8;;\x1b\\"))?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
+}
+
+/// Draws the archived bookmarks over the list.
+fn draw_archive(
+    f: &mut Frame,
+    archive: &mut ArchiveView,
+    view: &ListView,
+    status: Option<&str>,
+    list_area: Rect,
+    footer: Rect,
+) {
+    let mut view = view.clone();
+    let bookmarks: Vec<&Bookmark> = archive.bookmarks.iter().collect();
+    view.fit(&bookmarks, &[], list_area.width.saturating_sub(2) as usize);
+    let rows: Vec<TableRow> = archive.bookmarks.iter().map(|b| bookmark_row(b, &view, None)).collect();
+    let list = Table::new(rows, view.constraints())
+        .header(view.header())
+        .block(Block::default().borders(Borders::ALL).title(Text::TitleArchive.get()))
+        .row_highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black))
+        .highlight_symbol("→ ");
+    archive.list_state.select(Some(archive.selected));
+    f.render_widget(Clear, list_area);
+    f.render_stateful_widget(list, list_area, &mut archive.list_state);
+    let help = match status {
+        Some(status) => Span::styled(status, Style::default().fg(Color::Yellow)),
+        None => Span::raw(Text::HelpArchive.get()),
+    };
+    f.render_widget(Block::default().title(help).borders(Borders::BOTTOM), footer);
+}
+
+/// Draws the command palette in a popup over the list: what was typed, and
+/// below it the matching actions with their keys.
+fn draw_palette(f: &mut Frame, palette: &mut Palette, list_area: Rect, footer: Rect) {
+    let area = centered_rect(60, list_area.height.min(ACTIONS.len() as u16 + 3), list_area);
+    let block = Block::default().borders(Borders::ALL).title(Text::TitlePalette.get());
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+    let [input_area, list_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+    let prompt = format!("> {}", palette.input);
+    f.render_widget(Paragraph::new(prompt.as_str()), input_area);
+    f.set_cursor_position((input_area.x + width::columns(&prompt) as u16, input_area.y));
+    let width = list_area.width.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = palette
+        .matches
+        .iter()
+        .map(|&i| {
+            let (title, key, _) = ACTIONS[i];
+            let gap = width.saturating_sub(width::columns(title) + width::columns(key)).max(1);
+            ListItem::new(Line::from(vec![
+                Span::raw(title),
+                Span::raw(" ".repeat(gap)),
+                Span::styled(key, Style::default().add_modifier(Modifier::DIM)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black))
+        .highlight_symbol("→ ");
+    palette.list_state.select(Some(palette.selected));
+    f.render_stateful_widget(list, list_area, &mut palette.list_state);
+    let help = Span::raw(Text::HelpPalette.get());
+    f.render_widget(Block::default().title(help).borders(Borders::BOTTOM), footer);
+}
+
+/// Draws the directory navigator over the list, marking directories that
+/// are bookmarked already.
+fn draw_browser(
+    f: &mut Frame,
+    browser: &mut Browser,
+    bookmarks: &[Bookmark],
+    status: Option<&str>,
+    list_area: Rect,
+    footer: Rect,
+) {
+    let items: Vec<ListItem> = browser
+        .entries
+        .iter()
+        .map(|name| {
+            let path = browser.dir.join(name);
+            let bookmarked = bookmarks.iter().any(|b| Path::new(&b.path) == path);
+            let style = if bookmarked { Style::default().fg(Color::Cyan) } else { Style::default() };
+            ListItem::new(Line::styled(format!("{name}/"), style))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!("{}: {}", Text::TitleBrowse.get(), browser.dir.display())))
+        .highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black))
+        .highlight_symbol("→ ");
+    browser.list_state.select(Some(browser.selected));
+    f.render_widget(Clear, list_area);
+    f.render_stateful_widget(list, list_area, &mut browser.list_state);
+    let help = match status {
+        Some(status) => Span::styled(status, Style::default().fg(Color::Yellow)),
+        None => Span::raw(Text::HelpBrowse.get()),
+    };
+    f.render_widget(Block::default().title(help).borders(Borders::BOTTOM), footer);
+}
+
+/// Leaves the alternate screen for the duration of an `$EDITOR` session on the
+/// selected bookmark's note, then restores the TUI.
+fn edit_note_externally(
+    terminal: &mut Term,
+    app: &mut App,
+) -> Result<()> {
+    let Some(index) = app.selected_main() else {
+        return Ok(());
+    };
+    let note = &app.bookmarks[index].note;
+    if let Some(note) = suspended(terminal, || edit_text_externally(note))?? {
+        app.bookmarks[index].note = note;
+        app.changed();
+    }
+    Ok(())
+}
+
+/// Opens a tmux window for the bookmark, or a session outside tmux. The
+/// attached session takes over the terminal until it is detached.
+fn open_in_tmux(
+    terminal: &mut Term,
+    name: &str,
+    path: &str,
+    workspace: &[String],
+) -> Result<()> {
+    if tmux::inside() {
+        return tmux::open(name, path, workspace, tmux::Target::Window);
+    }
+    suspended(terminal, || tmux::open(name, path, workspace, tmux::Target::Session))?
+}
+
+/// Hands the terminal to `f`, e.g. to run an interactive program, and
+/// takes it back for the UI afterwards.
+fn suspended<T>(terminal: &mut Term, f: impl FnOnce() -> T) -> Result<T> {
+    disable_raw_mode().map_err(BmError::Terminal)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, crossterm::cursor::Show)
+        .map_err(BmError::Terminal)?;
+    restore_title().map_err(BmError::Terminal)?;
+
+    let result = f();
+
+    enable_raw_mode().map_err(BmError::Terminal)?;
+    save_title().map_err(BmError::Terminal)?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, crossterm::cursor::Hide)
+        .map_err(BmError::Terminal)?;
+    terminal.clear().map_err(BmError::Terminal)?;
+    Ok(result)
+}
+
+/// The pane title from before bm changed it, inside tmux.
+static PANE_TITLE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Whether the title was saved and not restored yet.
+static TITLE_SAVED: AtomicBool = AtomicBool::new(false);
+
+/// Whether bm's own title is shown, i.e. set since the last restore.
+static TITLE_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// What the UI is drawn on, see [`tty`].
+type Tty = io::BufWriter<Box<dyn io::Write + Send>>;
+
+/// The terminal the UI draws on.
+type Term = Terminal<CrosstermBackend<Tty>>;
+
+/// The controlling terminal, so stdout only ever carries the path that was
+/// picked and `cd "$(bm)"` works; stderr where there is no `/dev/tty`, as
+/// on Windows.
+fn tty() -> Tty {
+    let out: Box<dyn io::Write + Send> = match fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(file) => Box::new(file),
+        _ => Box::new(io::stderr()),
+    };
+    io::BufWriter::new(out)
+}
+
+/// Keeps the terminal's title for `restore_title`: xterm-like terminals
+/// push it onto their title stack, tmux is asked for the pane's title.
+fn save_title() -> io::Result<()> {
+    if tmux::inside() {
+        PANE_TITLE.get_or_init(tmux::pane_title);
+    }
+    TITLE_SAVED.store(true, Ordering::SeqCst);
+    execute!(tty(), Print("\x1b[22;0t"))
+}
+
+/// Puts back the title `save_title` kept. Does nothing if it wasn't saved.
+fn restore_title() -> io::Result<()> {
+    TITLE_SHOWN.store(false, Ordering::SeqCst);
+    if !TITLE_SAVED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    execute!(tty(), Print("\x1b[23;0t"))?;
+    if let Some(Some(title)) = PANE_TITLE.get() {
+        let _ = tmux::set_pane_title(title);
+    }
+    Ok(())
+}
+
+/// Puts the terminal back into its normal state. Safe to call more than once.
+fn restore_terminal() -> io::Result<()> {
+    logging::pause_stderr(false);
+    disable_raw_mode()?;
+    execute!(tty(), LeaveAlternateScreen, crossterm::cursor::Show)?;
+    restore_title()
+}
+
+/// Restores the terminal when dropped, so early returns and panics that unwind
+/// out of the event loop leave the shell usable.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore_terminal();
+    }
+}
+
+/// Chains a panic hook that restores the terminal before the panic message is
+/// printed; otherwise the message would land on the alternate screen and vanish.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = restore_terminal();
+            previous(info);
+        }));
+    });
+}
+
+/// Everything that can wake the event loop up.
+enum AppEvent {
+    Input(io::Result<Event>),
+    /// The bookmarks file changed on disk.
+    FileChanged,
+    /// Background existence checks finished, by path as written.
+    Probed(Vec<(String, Status, Option<ProjectType>)>),
+    /// bm was asked to terminate (SIGTERM, SIGHUP, or a SIGINT sent by `kill`).
+    Terminate,
+}
+
+/// Thread reading terminal input into the event channel. It has to be
+/// stopped while `$EDITOR` owns the terminal, or it would steal keystrokes.
+struct InputReader {
+    stop: Arc<AtomicBool>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl InputReader {
+    fn spawn(events: Sender<AppEvent>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            while !flag.load(Ordering::Relaxed) {
+                // The timeout only bounds how long `stop` takes; nothing is
+                // redrawn when it expires.
+                let ready = match event::poll(INPUT_STOP_LATENCY) {
+                    Ok(ready) => ready,
+                    Err(e) => {
+                        let _ = events.send(AppEvent::Input(Err(e)));
+                        return;
+                    }
+                };
+                if ready && events.send(AppEvent::Input(event::read())).is_err() {
+                    return;
+                }
+            }
+        });
+        InputReader { stop, handle }
+    }
+
+    /// Stops the thread and waits until it no longer reads the terminal.
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+
+    /// Asks the thread to stop without waiting for it. crossterm can spin
+    /// forever on a terminal that has hung up, which must not keep bm from
+    /// exiting.
+    fn detach(self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Starts watching the store's file, forwarding changes into the event
+/// channel. Live reload is best effort; without a watcher bm simply won't
+/// notice external edits until it is restarted.
+fn watch_store(store: &dyn BookmarkStore, events: &Sender<AppEvent>) -> Option<RecommendedWatcher> {
+    let (watcher, changes) = store.watch().ok()?;
+    let events = events.clone();
+    // Ends once the watcher is dropped and its sender goes away.
+    thread::spawn(move || {
+        while changes.recv().is_ok() {
+            if events.send(AppEvent::FileChanged).is_err() {
+                return;
+            }
+        }
+    });
+    Some(watcher)
+}
+
+/// Forwards termination signals into the event channel so the loop can exit
+/// through normal cleanup. Closing the returned handle stops the forwarding.
+#[cfg(unix)]
+fn forward_termination_signals(events: &Sender<AppEvent>) -> Option<signal_hook::iterator::Handle> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    let mut signals = signal_hook::iterator::Signals::new([SIGTERM, SIGINT, SIGHUP]).ok()?;
+    let handle = signals.handle();
+    let events = events.clone();
+    thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            let _ = events.send(AppEvent::Terminate);
+        }
+    });
+    Some(handle)
+}
+
+/// Checks whether the shown bookmarks' paths exist on a thread of its own
+/// and sends the result into the event channel. Drawing never waits for a
+/// stat, which can hang on a network mount.
+fn probe_paths(app: &App, events: &Sender<AppEvent>) {
+    let mut bookmarks = app.bookmarks.clone();
+    if let Some(project) = &app.project {
+        bookmarks.extend(project.bookmarks.iter().cloned());
+    }
+    let events = events.clone();
+    thread::spawn(move || {
+        let found = probe::probe_all(&bookmarks, probe::TIMEOUT);
+        let found = bookmarks.into_iter().zip(found).map(|(b, (status, project))| (b.path, status, project));
+        let _ = events.send(AppEvent::Probed(found.collect()));
+    });
+}
+
+/// Loads the `.bm.toml` nearest to the working directory, if there is one.
+fn discover_project() -> Option<Result<Project>> {
+    let cwd = std::env::current_dir().ok()?;
+    project::discover(&cwd).map(|file| Project::load(&file))
+}
+
+/// Switches to raw mode and the alternate screen; the terminal is restored
+/// when the guard is dropped.
+fn enter_terminal() -> Result<(TerminalGuard, Term)> {
+    install_panic_hook();
+    logging::pause_stderr(true);
+    enable_raw_mode().map_err(BmError::Terminal)?;
+    let guard = TerminalGuard;
+    save_title().map_err(BmError::Terminal)?;
+    let mut out = tty();
+    execute!(
+        out,
+        EnterAlternateScreen,
+        crossterm::cursor::Hide
+    ).map_err(BmError::Terminal)?;
+
+    let backend = CrosstermBackend::new(out);
+    let mut terminal = Terminal::new(backend).map_err(BmError::Terminal)?;
+    terminal.clear().map_err(BmError::Terminal)?;
+    Ok((guard, terminal))
+}
+
+/// What the bookmark UI was left with.
+pub enum Picked {
+    /// The path of the bookmark picked to jump to.
+    Dir(String),
+    /// The paths of the marked bookmarks, to print with `Y`.
+    Paths(Vec<String>),
+}
+
+/// Runs the bookmark UI on `path` and returns what was picked, if anything;
+/// quitting without one fails with [`BmError::NoSelection`], or with
+/// [`BmError::Cancelled`] on Ctrl-C. `cwd` is the directory `u` bookmarks
+/// instead of the working directory.
+pub fn run_tui(
+    store: Box<dyn BookmarkStore>,
+    profile: Option<String>,
+    cwd: Option<PathBuf>,
+    config: &Config,
+) -> Result<Option<Picked>> {
+    let (guard, mut terminal) = enter_terminal()?;
+
+    let (events, incoming) = mpsc::channel();
+    let mut watcher = watch_store(store.as_ref(), &events);
+    let (project, project_error) = match discover_project() {
+        Some(Ok(project)) => (Some(project), None),
+        Some(Err(e)) => (None, Some(format!("Ignoring project bookmarks: {e}"))),
+        None => (None, None),
+    };
+    let archived = match config.stale_after_days {
+        Some(days) if config.archive_stale && !config.read_only => match archive::archive_stale(store.as_ref(), days, unix_now()) {
+            Ok(stale) if stale.is_empty() => None,
+            Ok(stale) => {
+                let message = format!("Archived {} bookmarks unused for {days} days", stale.len());
+                let removed = journal::changes(&stale, &[]);
+                let recorded = journal::record(journal::Source::Tui, store.path(), &removed)
+                    .and_then(|()| config.hooks.run_for_changes(&removed, Output::Discard));
+                Some(match recorded {
+                    Ok(()) => message,
+                    Err(e) => format!("{message}; {e}"),
+                })
+            }
+            Err(e) => Some(format!("Could not archive unused bookmarks: {e}")),
+        },
+        _ => None,
+    };
+    let conflicts = sync::take_conflicts(store.path());
+    let synced = (!conflicts.is_empty()).then(|| {
+        format!("bm sync kept this machine's version of {}, changed on both", conflicts.join(", "))
+    });
+    let mut app = match store.load() {
+        Ok(bookmarks) => App::new(store, bookmarks, project),
+        Err(e) => App::load_failed(store, &e),
+    };
+    app.profile = profile;
+    app.cwd = cwd;
+    app.canonicalize = config.canonicalize;
+    app.columns = config.list_columns();
+    app.column_widths = config.column_widths.clone();
+    if let Some(column) = config.sort {
+        app.sort = Sort::By(column);
+    }
+    app.stale_after = config.stale_after_days;
+    app.ranker = Ranker::new(config);
+    app.confirm = config.confirm;
+    app.exclude = config.exclude.clone();
+    app.hooks = config.hooks.clone();
+    app.hyperlinks = config.hyperlinks;
+    app.icons = config.icons;
+    let history_error = match history::load() {
+        Ok(recent) => {
+            app.recent = recent;
+            app.rebuild_rows();
+            // Start on the most recent jump rather than the top of the file.
+            app.selected = 0;
+            app.clamp_selection();
+            None
+        }
+        Err(e) => Some(format!("Could not read the jump history: {e}")),
+    };
+    let visits_error = match visits::load() {
+        Ok(visits) => {
+            app.suggestions = visits::suggestions(&visits, &app.bookmarks, &app.ranker)
+                .into_iter()
+                .filter(|v| add::excluded(&config.exclude, &v.path).is_none())
+                .take(SUGGESTIONS_SHOWN)
+                .map(|v| {
+                    let mut b = Bookmark::new(add::name_from_path(&v.path), v.path);
+                    b.hits = v.count;
+                    b.last_used = Some(v.last);
+                    b
+                })
+                .collect();
+            app.rebuild_rows();
+            None
+        }
+        Err(e) => Some(format!("Could not read the visited directories: {e}")),
+    };
+    if let Some(state) = SavedState::load() {
+        app.sort = state.sort;
+        app.type_filter = state.type_filter;
+        app.suggestions_collapsed = state.suggestions_collapsed;
+        app.rebuild_rows();
+        if let Some(path) = state.selected {
+            app.select_path(&path);
+            if app.type_filter.is_some() {
+                app.pending_selection = Some(path);
+            }
+        }
+    }
+    app.status = app.status.take().or(project_error).or(synced).or(archived).or(history_error).or(visits_error);
+    let mut reload_pending = false;
+    #[cfg(unix)]
+    let signals = forward_termination_signals(&events);
+    let mut input = Some(InputReader::spawn(events.clone()));
+    probe_paths(&app, &events);
+
+    // The screen only changes in response to an event, so the loop sleeps in
+    // `recv` and redraws once per event.
+    let result = loop {
+        if let Err(e) = terminal.draw(|f| draw(f, &mut app)) {
+            break Err(BmError::Terminal(e));
+        }
+        if let Err(e) = write_links(terminal.backend_mut(), &app.links) {
+            break Err(BmError::Terminal(e));
+        }
+        let title = match app.selected_bookmark() {
+            Some(b) => format!("bm — {}", b.name),
+            None => "bm".to_string(),
+        };
+        // Programs run in between may have set a title of their own.
+        if app.title.as_ref() != Some(&title) || !TITLE_SHOWN.load(Ordering::SeqCst) {
+            if let Err(e) = execute!(terminal.backend_mut(), SetTitle(&title)) {
+                break Err(BmError::Terminal(e));
+            }
+            TITLE_SHOWN.store(true, Ordering::SeqCst);
+            app.title = Some(title);
+        }
+        // Pending changes are written once things have been quiet for a
+        // moment, but not while a dialog is open.
+        let save_due = app.save_due.filter(|_| matches!(app.mode, Mode::Normal | Mode::Browse(_) | Mode::Archive(_)));
+        let received = match save_due {
+            Some(due) => incoming.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => incoming.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let event = match received {
+            Err(RecvTimeoutError::Timeout) => {
+                app.save();
+                continue;
+            }
+            Ok(AppEvent::Input(Ok(event))) => Some(event),
+            Ok(AppEvent::Input(Err(e))) => break Err(BmError::Terminal(e)),
+            Ok(AppEvent::FileChanged) => {
+                reload_pending = true;
+                None
+            }
+            Ok(AppEvent::Probed(found)) => {
+                for (path, status, project) in found {
+                    match project {
+                        Some(project) => app.project_types.insert(path.clone(), project),
+                        None => app.project_types.remove(&path),
+                    };
+                    app.liveness.insert(path, status);
+                }
+                app.rebuild_rows();
+                if let Some(path) = app.pending_selection.take() {
+                    app.select_path(&path);
+                }
+                None
+            }
+            Ok(AppEvent::Terminate) | Err(RecvTimeoutError::Disconnected) => break Ok(None),
+        };
+        if let Some(Event::Resize(..)) = event {
+            // Labels were assigned to the rows that used to be visible.
+            if matches!(app.mode, Mode::Hint { .. }) {
+                app.mode = Mode::Normal;
+            }
+            if let Err(e) = terminal.autoresize() {
+                break Err(BmError::Terminal(e));
+            }
+        }
+        if let Some(Event::Key(key)) = event
+            && key.kind == KeyEventKind::Press
+        {
+            match app.handle_key(key) {
+                Action::Continue => {}
+                Action::Quit => break Err(BmError::NoSelection),
+                Action::Cancel => break Err(BmError::Cancelled),
+                Action::Select(path) => break Ok(Some(Picked::Dir(path))),
+                Action::PrintPaths(paths) => break Ok(Some(Picked::Paths(paths))),
+                Action::EditNoteExternally => {
+                    if let Some(reader) = input.take() {
+                        reader.stop();
+                    }
+                    let edited = edit_note_externally(&mut terminal, &mut app);
+                    input = Some(InputReader::spawn(events.clone()));
+                    if let Err(e) = edited {
+                        app.show_error(format!("Could not edit note: {e}"), Some(Retry::EditNote));
+                    }
+                }
+                Action::Run { bookmark, path } => {
+                    if let Some(reader) = input.take() {
+                        reader.stop();
+                    }
+                    let ran = suspended(&mut terminal, || launch::run_command(&bookmark, &path));
+                    input = Some(InputReader::spawn(events.clone()));
+                    match ran {
+                        Ok(Ok(())) => break Ok(None),
+                        Ok(Err(e)) => app.show_error(e.to_string(), None),
+                        Err(e) => break Err(e),
+                    }
+                }
+                Action::OpenFile(path) => {
+                    if let Some(reader) = input.take() {
+                        reader.stop();
+                    }
+                    let opened = suspended(&mut terminal, || launch::open_file(&path));
+                    input = Some(InputReader::spawn(events.clone()));
+                    match opened {
+                        Ok(Ok(())) => break Ok(None),
+                        Ok(Err(e)) => app.show_error(format!("Could not open {path}: {e}"), None),
+                        Err(e) => break Err(e),
+                    }
+                }
+                Action::EditFile(path) => {
+                    if let Some(reader) = input.take() {
+                        reader.stop();
+                    }
+                    let edited = suspended(&mut terminal, || editor::run_editor(Path::new(&path)));
+                    input = Some(InputReader::spawn(events.clone()));
+                    match edited {
+                        Ok(Ok(_)) => break Ok(None),
+                        Ok(Err(e)) => app.show_error(format!("Could not edit {path}: {e}"), None),
+                        Err(e) => break Err(e),
+                    }
+                }
+                Action::Ssh(path) => {
+                    if let Some(reader) = input.take() {
+                        reader.stop();
+                    }
+                    let connected = suspended(&mut terminal, || remote::connect(&path));
+                    input = Some(InputReader::spawn(events.clone()));
+                    match connected {
+                        Ok(Ok(())) => break Ok(None),
+                        Ok(Err(e)) => app.show_error(e.to_string(), None),
+                        Err(e) => break Err(e),
+                    }
+                }
+                Action::Tmux { name, path, workspace } => {
+                    if let Some(reader) = input.take() {
+                        reader.stop();
+                    }
+                    let opened = open_in_tmux(&mut terminal, &name, &path, &workspace);
+                    input = Some(InputReader::spawn(events.clone()));
+                    match opened {
+                        Ok(()) => break Ok(None),
+                        Err(e) => app.show_error(format!("Could not open {name} in tmux: {e}"), None),
+                    }
+                }
+                Action::ProfileSwitched => {
+                    watcher = watch_store(app.store.as_ref(), &events);
+                    reload_pending = false;
+                    probe_paths(&app, &events);
+                }
+            }
+        }
+        // Only swap the list out while no dialog refers to a specific entry.
+        // Unsaved changes are merged with the file instead of being dropped.
+        if reload_pending && matches!(app.mode, Mode::Normal | Mode::Browse(_) | Mode::Archive(_)) {
+            if app.dirty {
+                app.save();
+            } else {
+                app.reload();
+            }
+            reload_pending = false;
+            probe_paths(&app, &events);
+        }
+    };
+
+    // Every way out of the loop, including signals, writes pending changes.
+    let flushed = app.flush();
+    let state = SavedState {
+        selected: app.selected_bookmark().map(|b| b.path.clone()),
+        sort: app.sort,
+        type_filter: app.type_filter,
+        suggestions_collapsed: app.suggestions_collapsed,
+    };
+
+    // Cleanup
+    drop(watcher);
+    if let Some(reader) = input.take() {
+        reader.detach();
+    }
+    #[cfg(unix)]
+    if let Some(signals) = signals {
+        signals.close();
+    }
+    drop(guard);
+    if let Err(e) = state.save() {
+        eprintln!("bm: could not save the UI state: {e}");
+    }
+    // A failed save matters more than how the UI was left.
+    flushed?;
+    let selection = result?;
+    // Run once the terminal is back to normal, so the hook can print.
+    if let Some(Picked::Dir(path)) = &selection {
+        let name = app.selected_bookmark().map(|b| b.name.clone()).unwrap_or_default();
+        if let Err(e) = app.hooks.run(hooks::Event::PreJump, &name, path, Output::Stderr) {
+            eprintln!("bm: {e}");
+        }
+    }
+    Ok(selection)
+}
+
+/// Shows each group of duplicates side by side for `bm dedup --interactive`
+/// and `bm merge`, under `title`, and returns the groups the user chose to
+/// merge, with what survives. Quitting early keeps the choices made so far.
+pub fn run_dedup(bookmarks: &[Bookmark], groups: &[Vec<usize>], title: &str) -> Result<Vec<(Vec<usize>, Choice)>> {
+    let (_guard, mut terminal) = enter_terminal()?;
+    let mut picked = Vec::new();
+    for (number, group) in groups.iter().enumerate() {
+        let mut choice = Choice::new(group.len());
+        let mut column = 0;
+        loop {
+            terminal
+                .draw(|f| draw_dedup(f, bookmarks, group, &choice, column, title, (number + 1, groups.len())))
+                .map_err(BmError::Terminal)?;
+            let Event::Key(key) = event::read().map_err(BmError::Terminal)? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('h') | KeyCode::Left => column = column.saturating_sub(1),
+                KeyCode::Char('l') | KeyCode::Right => column = (column + 1).min(group.len() - 1),
+                KeyCode::Char('n') => choice.name = column,
+                KeyCode::Char('p') => choice.path = column,
+                KeyCode::Char('o') => choice.note = column,
+                KeyCode::Char('t') => choice.tags[column] = !choice.tags[column],
+                KeyCode::Char('a') => {
+                    choice.name = column;
+                    choice.path = column;
+                    choice.note = column;
+                }
+                KeyCode::Enter => {
+                    picked.push((group.clone(), choice));
+                    break;
+                }
+                KeyCode::Char('s') => break,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(picked),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(picked),
+                _ => {}
+            }
+        }
+    }
+    Ok(picked)
+}
+
+/// One column per duplicate; the values that will survive the merge are
+/// highlighted.
+fn draw_dedup(
+    f: &mut Frame,
+    bookmarks: &[Bookmark],
+    group: &[usize],
+    choice: &Choice,
+    column: usize,
+    title: &str,
+    (number, total): (usize, usize),
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.area());
+    let picked = Style::default().fg(Color::Green).add_modifier(Modifier::BOLD);
+    let cell = |text: String, chosen: bool| {
+        if chosen { Cell::from(format!("* {text}")).style(picked) } else { Cell::from(format!("  {text}")) }
+    };
+    let entries = group.iter().map(|&i| &bookmarks[i]);
+    let header = TableRow::new(std::iter::once(Cell::from("")).chain(group.iter().enumerate().map(|(pos, _)| {
+        let style = if pos == column { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+        Cell::from(format!("  #{}", pos + 1)).style(style)
+    })));
+    let rows = vec![
+        TableRow::new(std::iter::once(Cell::from("name")).chain(
+            entries.clone().enumerate().map(|(pos, b)| cell(b.name.clone(), pos == choice.name)),
+        )),
+        TableRow::new(std::iter::once(Cell::from("path")).chain(
+            entries.clone().enumerate().map(|(pos, b)| cell(b.path.clone(), pos == choice.path)),
+        )),
+        TableRow::new(std::iter::once(Cell::from("note")).chain(
+            entries.clone().enumerate().map(|(pos, b)| cell(b.note.lines().next().unwrap_or("").to_string(), pos == choice.note)),
+        )),
+        TableRow::new(std::iter::once(Cell::from("tags")).chain(
+            entries.enumerate().map(|(pos, b)| cell(b.tags.join(" "), choice.tags[pos])),
+        )),
+    ];
+    let widths = std::iter::once(Constraint::Length(5)).chain(group.iter().map(|_| Constraint::Fill(1)));
+    let merged = choice.merged(bookmarks, group);
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("{title} {number}/{total}"))
+            .title_bottom(format!("Result: {} -> {}", merged.name, merged.path)),
+    );
+    f.render_widget(table, chunks[0]);
+    let help = Text::HelpDedup.get();
+    f.render_widget(Paragraph::new(help).style(Style::default().add_modifier(Modifier::DIM)), chunks[1]);
+}
+
+/// Rows the picker of `bm pick --tui` lists at most.
+const PICKER_ROWS: usize = 12;
+
+/// Indices of the `bookmarks` matching `query` like `bm pick` matches them,
+/// best first; all of them ranked by `ranker` for an empty query.
+fn picker_matches(bookmarks: &[Bookmark], query: &str, ranker: &Ranker) -> Vec<usize> {
+    let now = unix_now();
+    let mut found: Vec<(i64, usize)> = bookmarks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| Some((fuzzy::bookmark_score(query, b)?, i)))
+        .collect();
+    found.sort_by(|&(a_score, a), &(b_score, b)| {
+        let (a_b, b_b) = (&bookmarks[a], &bookmarks[b]);
+        b_score
+            .cmp(&a_score)
+            .then_with(|| ranker.compare((a_b.hits, a_b.last_used), (b_b.hits, b_b.last_used), now))
+    });
+    found.into_iter().map(|(_, i)| i).collect()
+}
+
+/// The picker of `bm pick --tui`, for a keybinding that only switches
+/// directories: the query typed so far and the matches under it, centered,
+/// without borders or help. Returns the index of the bookmark picked with
+/// Enter, or `None` on Esc.
+pub fn run_picker(bookmarks: &[Bookmark], query: &str, ranker: &Ranker) -> Result<Option<usize>> {
+    let (_guard, mut terminal) = enter_terminal()?;
+    let mut input = query.to_string();
+    let mut matches = picker_matches(bookmarks, &input, ranker);
+    let mut selected = 0;
+    let mut list_state = ListState::default();
+    loop {
+        terminal
+            .draw(|f| {
+                let rows = matches.len().min(PICKER_ROWS) as u16;
+                let area = centered_rect(60, rows + 1, f.area());
+                let [input_area, list_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+                let prompt = format!("> {input}");
+                f.render_widget(Paragraph::new(prompt.as_str()), input_area);
+                f.set_cursor_position((input_area.x + width::columns(&prompt) as u16, input_area.y));
+                let items: Vec<ListItem> = matches
+                    .iter()
+                    .map(|&i| {
+                        let b = &bookmarks[i];
+                        ListItem::new(Line::from(vec![
+                            Span::styled(b.name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                            Span::raw("  "),
+                            Span::styled(paths::contract_tilde(&b.path), Style::default().add_modifier(Modifier::DIM)),
+                        ]))
+                    })
+                    .collect();
+                let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                list_state.select((!matches.is_empty()).then_some(selected));
+                f.render_stateful_widget(list, list_area, &mut list_state);
+            })
+            .map_err(BmError::Terminal)?;
+        let Event::Key(key) = event::read().map_err(BmError::Terminal)? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let last = matches.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Enter => return Ok(matches.get(selected).copied()),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if control => return Err(BmError::Cancelled),
+            KeyCode::Down | KeyCode::Tab => selected = (selected + 1).min(last),
+            KeyCode::Char('n') if control => selected = (selected + 1).min(last),
+            KeyCode::Up | KeyCode::BackTab => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if control => selected = selected.saturating_sub(1),
+            KeyCode::Char('u') if control => input.clear(),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) if !control => input.push(c),
+            _ => continue,
+        }
+        if matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace) {
+            matches = picker_matches(bookmarks, &input, ranker);
+            selected = selected.min(matches.len().saturating_sub(1));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::history::{read_lines, write_lines};
+use crate::paths::{data_dir, same_path};
+use crate::rank::Ranker;
+use crate::store::{Bookmark, unix_now};
+
+/// Name of the visit counts inside the data directory.
+const VISITS_FILE: &str = "visits";
+
+/// Visits a directory needs before it is suggested for bookmarking.
+const SUGGEST_AFTER: u64 = 3;
+
+/// How often a directory was visited, whether or not it is bookmarked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Visit {
+    pub path: String,
+    pub count: u64,
+    /// Seconds since the Unix epoch of the latest visit.
+    pub last: u64,
+}
+
+/// The visit counts shared by every bookmarks file and profile.
+pub fn visits_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join(VISITS_FILE))
+}
+
+/// Every visited directory, one `count<TAB>last<TAB>path` line each. Lines
+/// that don't parse are skipped, and a file that doesn't exist has no visits.
+pub fn load() -> Result<Vec<Visit>> {
+    let lines = read_lines(&visits_path()?)?;
+    Ok(lines
+        .iter()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let count = fields.next()?.parse().ok()?;
+            let last = fields.next()?.parse().ok()?;
+            let path = fields.next().filter(|p| !p.is_empty())?;
+            Some(Visit { path: path.to_string(), count, last })
+        })
+        .collect())
+}
+
+/// Counts a visit to `dir`, as the `bm hook` scripts do on every change
+/// of directory. Past `max` directories that aren't in `bookmarks`, the one
+/// of them visited longest ago is forgotten; 0 keeps every directory.
+pub fn record(dir: &str, max: usize, bookmarks: &[Bookmark]) -> Result<()> {
+    let mut visits = load()?;
+    let now = unix_now();
+    match visits.iter_mut().find(|v| same_path(&v.path, dir)) {
+        Some(visit) => {
+            visit.count += 1;
+            visit.last = now;
+        }
+        None => visits.push(Visit { path: dir.to_string(), count: 1, last: now }),
+    }
+    let bookmarked = |v: &Visit| bookmarks.iter().any(|b| b.target().is_ok_and(|t| same_path(&t, &v.path)));
+    let tracked = visits.iter().filter(|v| !bookmarked(v)).count();
+    if max > 0 && tracked > max {
+        visits.sort_by_key(|v| std::cmp::Reverse(v.last));
+        let mut kept = 0;
+        visits.retain(|v| {
+            if bookmarked(v) {
+                return true;
+            }
+            kept += 1;
+            kept <= max
+        });
+    }
+    let lines: Vec<String> = visits.iter().map(|v| format!("{}\t{}\t{}", v.count, v.last, v.path)).collect();
+    write_lines(&visits_path()?, &lines)
+}
+
+/// The directories of `visits` visited often enough to be worth a bookmark
+/// and not in `bookmarks` yet, best ranked by `ranker` first.
+pub fn suggestions(visits: &[Visit], bookmarks: &[Bookmark], ranker: &Ranker) -> Vec<Visit> {
+    let mut found: Vec<Visit> = visits
+        .iter()
+        .filter(|v| v.count >= SUGGEST_AFTER)
+        .filter(|v| !bookmarks.iter().any(|b| b.target().is_ok_and(|t| same_path(&t, &v.path))))
+        .cloned()
+        .collect();
+    let now = unix_now();
+    found.sort_by(|a, b| ranker.compare((a.count, Some(a.last)), (b.count, Some(b.last)), now));
+    found
+}
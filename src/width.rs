@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// How many terminal columns `text` takes: two for most CJK characters and
+/// emoji, none for combining marks.
+pub fn columns(text: &str) -> usize {
+    text.width()
+}
+
+/// `text` followed by enough spaces to fill `width` columns. `format!`'s
+/// padding counts characters, which misaligns wide ones.
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{text}{}", " ".repeat(width.saturating_sub(columns(text))))
+}
+
+/// `path` shortened to `max` columns by putting `…` in place of the
+/// components in the middle: the first one (`~`, or the root) and as many
+/// of the last ones as fit are kept, then as many leading ones as fit.
+/// Paths too long even for that keep their end.
+pub fn ellipsize_path(path: &str, max: usize) -> Cow<'_, str> {
+    if columns(path) <= max {
+        return Cow::Borrowed(path);
+    }
+    let separator = if path.contains('/') || !path.contains('\\') { '/' } else { '\\' };
+    let parts: Vec<&str> = path.trim_end_matches(separator).split(separator).collect();
+    let joined = |head: usize, tail: usize| {
+        let sep = separator.to_string();
+        format!("{}{sep}…{sep}{}", parts[..head].join(&sep), parts[parts.len() - tail..].join(&sep))
+    };
+    let fits = |head, tail| head + tail < parts.len() && columns(&joined(head, tail)) <= max;
+    if !fits(1, 1) {
+        return Cow::Owned(keep_end(path, max));
+    }
+    let (mut head, mut tail) = (1, 1);
+    while fits(head, tail + 1) {
+        tail += 1;
+    }
+    while fits(head + 1, tail) {
+        head += 1;
+    }
+    Cow::Owned(joined(head, tail))
+}
+
+/// `…` and as much of the end of `text` as fits in `max` columns.
+fn keep_end(text: &str, max: usize) -> String {
+    let mut width = 1;
+    let mut start = text.len();
+    for (i, c) in text.char_indices().rev() {
+        width += c.width().unwrap_or(0);
+        if width > max {
+            break;
+        }
+        start = i;
+    }
+    if max == 0 { String::new() } else { format!("…{}", &text[start..]) }
+}
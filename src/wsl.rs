@@ -0,0 +1,64 @@
+use std::fs;
+use std::sync::OnceLock;
+
+/// Where WSL mounts the Windows drives, as in its default `/etc/wsl.conf`.
+const MOUNT_ROOT: &str = "/mnt/";
+
+/// Whether bm runs inside the Windows Subsystem for Linux. Checked once.
+pub fn inside() -> bool {
+    static INSIDE: OnceLock<bool> = OnceLock::new();
+    *INSIDE.get_or_init(|| {
+        cfg!(target_os = "linux")
+            && (std::env::var_os("WSL_DISTRO_NAME").is_some_and(|v| !v.is_empty())
+                || fs::read_to_string("/proc/sys/kernel/osrelease")
+                    .is_ok_and(|release| release.to_lowercase().contains("microsoft")))
+    })
+}
+
+/// `/mnt/c/Users/me` as Windows writes it, `C:\Users\me`, the way
+/// `wslpath -w` translates drive mounts. `None` for paths outside them.
+pub fn to_windows(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(MOUNT_ROOT)?;
+    let mut chars = rest.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    let rest = chars.as_str();
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+    let rest = rest.trim_start_matches('/').replace('/', "\\");
+    Some(format!("{}:\\{rest}", drive.to_ascii_uppercase()))
+}
+
+/// `C:\Users\me` (or `C:/Users/me`) as WSL sees it, `/mnt/c/Users/me`, the
+/// way `wslpath -u` translates drive letters. `None` for other paths.
+pub fn to_wsl(path: &str) -> Option<String> {
+    let mut chars = path.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    let rest = chars.as_str().strip_prefix(':')?;
+    if !rest.is_empty() && !rest.starts_with(['\\', '/']) {
+        // `C:src` is relative to the drive's working directory.
+        return None;
+    }
+    let rest = rest.trim_start_matches(['\\', '/']).replace('\\', "/");
+    let dir = format!("{MOUNT_ROOT}{}", drive.to_ascii_lowercase());
+    Some(if rest.is_empty() { dir } else { format!("{dir}/{rest}") })
+}
+
+/// `path` in the form this system uses: drive paths become `/mnt/c/...`
+/// inside WSL and `/mnt/c/...` becomes `C:\...` on Windows.
+pub fn native(path: &str) -> String {
+    let translated = if cfg!(windows) {
+        to_windows(path)
+    } else if inside() {
+        to_wsl(path)
+    } else {
+        None
+    };
+    translated.unwrap_or_else(|| path.to_string())
+}
+
+/// `path` as it is written to a bookmarks file shared between WSL and
+/// Windows: drive mounts as `C:\...`, which both sides translate back.
+pub fn portable(path: &str) -> String {
+    to_windows(path).unwrap_or_else(|| path.to_string())
+}